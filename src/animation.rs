@@ -0,0 +1,282 @@
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+
+use crate::camera::Camera;
+use crate::ray::Point3;
+use crate::transform::Transform;
+use crate::vec::Vec3;
+
+/// Interpolation between keyframes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Straight lines between the keys, the velocity jumps at every key.
+    Linear,
+    /// Catmull-Rom spline through the keys, smooth velocity.
+    CatmullRom,
+}
+
+/// Values which can be interpolated, e.g. numbers and vectors.
+pub trait Interpolate:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f64, Output = Self>
+{
+}
+
+impl<V> Interpolate for V where V: Copy + Add<Output = V> + Sub<Output = V> + Mul<f64, Output = V> {}
+
+/// Value changing over time, defined by keyframes.
+#[derive(Debug, Clone)]
+pub struct Track<V> {
+    interpolation: Interpolation,
+    // keys sorted by time
+    keys: Vec<(f64, V)>,
+}
+
+impl<V: Interpolate> Track<V> {
+    /// Create a new track without keys.
+    ///
+    /// * `interpolation` - Interpolation between the keys.
+    pub fn new(interpolation: Interpolation) -> Self {
+        Track {
+            interpolation,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Adds a key.
+    ///
+    /// * `time` - Time of the key in seconds.
+    /// * `value` - Value at that time.
+    pub fn key(mut self, time: f64, value: V) -> Self {
+        let index = self.keys.partition_point(|(t, _)| *t <= time);
+        self.keys.insert(index, (time, value));
+        self
+    }
+
+    /// Returns the value at a given time, or none if the track has no keys.
+    ///
+    /// Before the first and after the last key, the value of that key is held.
+    ///
+    /// * `time` - Time in seconds.
+    pub fn at(&self, time: f64) -> Option<V> {
+        let (first, last) = (self.keys.first()?, self.keys.last()?);
+        if time <= first.0 {
+            return Some(first.1);
+        }
+        if time >= last.0 {
+            return Some(last.1);
+        }
+
+        // segment [i, i + 1] containing the time
+        let i = self.keys.partition_point(|(t, _)| *t <= time) - 1;
+        let (t0, p0) = self.keys[i];
+        let (t1, p1) = self.keys[i + 1];
+        let dt = t1 - t0;
+        if dt <= 0.0 {
+            return Some(p1);
+        }
+        let s = (time - t0) / dt;
+
+        match self.interpolation {
+            Interpolation::Linear => Some(p0 + (p1 - p0) * s),
+            Interpolation::CatmullRom => {
+                // Cubic Hermite spline whose tangents are the finite differences of the
+                // neighboring keys (one sided at the ends), scaled to the segment length:
+                //
+                //     p(s) = h00⋅p0 + h10⋅m0 + h01⋅p1 + h11⋅m1
+                let tangent = |k: usize| {
+                    let a = k.saturating_sub(1);
+                    let b = (k + 1).min(self.keys.len() - 1);
+                    let (ta, pa) = self.keys[a];
+                    let (tb, pb) = self.keys[b];
+                    (pb - pa) * (dt / (tb - ta))
+                };
+                let (m0, m1) = (tangent(i), tangent(i + 1));
+
+                let s2 = s * s;
+                let s3 = s2 * s;
+                let h00 = 2.0 * s3 - 3.0 * s2 + 1.0;
+                let h10 = s3 - 2.0 * s2 + s;
+                let h01 = -2.0 * s3 + 3.0 * s2;
+                let h11 = s3 - s2;
+                Some(p0 * h00 + m0 * h10 + p1 * h01 + m1 * h11)
+            }
+        }
+    }
+}
+
+/// Keyframed camera motion.
+///
+/// Every property has its own track, properties without keys keep the value the camera was set
+/// up with.
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    /// Camera position (lookfrom).
+    pub position: Track<Point3<f64>>,
+    /// Point the camera looks at (lookat).
+    pub target: Track<Point3<f64>>,
+    /// Vertical field of view in degrees.
+    pub vfov: Track<f64>,
+    /// Focus distance.
+    pub focus: Track<f64>,
+}
+
+impl CameraPath {
+    /// Create a new camera path without keys.
+    ///
+    /// * `interpolation` - Interpolation between the keys.
+    pub fn new(interpolation: Interpolation) -> Self {
+        CameraPath {
+            position: Track::new(interpolation),
+            target: Track::new(interpolation),
+            vfov: Track::new(interpolation),
+            focus: Track::new(interpolation),
+        }
+    }
+
+    /// Load a camera path from a text file.
+    ///
+    /// Each line holds one key, everything after a '#' and empty lines are ignored:
+    ///
+    ///     # time  position  target  [vfov  [focus]]
+    ///     0.0     13 2 3    0 0 0   20     10
+    ///     2.5     8 3 8     0 1 0   # keeps vfov and focus
+    ///
+    /// * `path` - Path to the file.
+    /// * `interpolation` - Interpolation between the keys.
+    pub fn load<P: AsRef<Path>>(path: P, interpolation: Interpolation) -> io::Result<Self> {
+        let mut camera_path = CameraPath::new(interpolation);
+
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(|value| value.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid_key(number))?;
+            if values.len() < 7 || values.len() > 9 {
+                return Err(invalid_key(number));
+            }
+
+            let time = values[0];
+            camera_path.position = camera_path
+                .position
+                .key(time, Vec3::new3(values[1], values[2], values[3]));
+            camera_path.target = camera_path
+                .target
+                .key(time, Vec3::new3(values[4], values[5], values[6]));
+            if let Some(&vfov) = values.get(7) {
+                camera_path.vfov = camera_path.vfov.key(time, vfov);
+            }
+            if let Some(&focus) = values.get(8) {
+                camera_path.focus = camera_path.focus.key(time, focus);
+            }
+        }
+
+        Ok(camera_path)
+    }
+
+    /// Create a turntable: the camera circles around its target once.
+    ///
+    /// The circle is approximated by a Catmull-Rom spline through evenly spaced keys, which is
+    /// indistinguishable from a true circle at this key count.
+    ///
+    /// * `start` - Initial camera position.
+    /// * `target` - Center of the circle, the camera keeps looking at it.
+    /// * `duration` - Time for one revolution in seconds.
+    pub fn orbit(start: Point3<f64>, target: Point3<f64>, duration: f64) -> Self {
+        const KEYS: usize = 32;
+        let offset = start - target;
+        let radius = (offset.x() * offset.x() + offset.z() * offset.z()).sqrt();
+        let angle = offset.z().atan2(offset.x());
+
+        let mut camera_path = CameraPath::new(Interpolation::CatmullRom);
+        for k in 0..=KEYS {
+            let fraction = k as f64 / KEYS as f64;
+            let phi = angle + 2.0 * PI * fraction;
+            let position = Vec3::new3(
+                target.x() + radius * phi.cos(),
+                start.y(),
+                target.z() + radius * phi.sin(),
+            );
+            camera_path.position = camera_path.position.key(fraction * duration, position);
+        }
+        camera_path.target = camera_path.target.key(0.0, target);
+        camera_path
+    }
+
+    /// Returns the camera at a given time.
+    ///
+    /// * `camera` - Camera with the initial settings.
+    /// * `time` - Time in seconds.
+    pub fn apply(&self, mut camera: Camera, time: f64) -> Camera {
+        if let Some(position) = self.position.at(time) {
+            camera = camera.lookfrom(position);
+        }
+        if let Some(target) = self.target.at(time) {
+            camera = camera.lookat(target);
+        }
+        if let Some(vfov) = self.vfov.at(time) {
+            camera = camera.vfov(vfov);
+        }
+        if let Some(focus) = self.focus.at(time) {
+            camera = camera.focus(focus);
+        }
+        camera
+    }
+}
+
+/// Returns the error for a malformed line in a camera path file.
+fn invalid_key(number: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("bad camera key in line {}", number + 1),
+    )
+}
+
+/// Keyframed object motion.
+#[derive(Debug, Clone)]
+pub struct TransformTrack {
+    /// Offset of the object.
+    pub translation: Track<Vec3<f64>>,
+    /// Rotation angles around the x, y and z axis in degrees.
+    pub rotation: Track<Vec3<f64>>,
+    /// Uniform scale factor.
+    pub scale: Track<f64>,
+}
+
+impl TransformTrack {
+    /// Create a new transform track without keys.
+    ///
+    /// * `interpolation` - Interpolation between the keys.
+    pub fn new(interpolation: Interpolation) -> Self {
+        TransformTrack {
+            translation: Track::new(interpolation),
+            rotation: Track::new(interpolation),
+            scale: Track::new(interpolation),
+        }
+    }
+
+    /// Returns the transformation at a given time.
+    ///
+    /// * `time` - Time in seconds.
+    pub fn at(&self, time: f64) -> Transform {
+        let mut transform = Transform::new();
+        if let Some(translation) = self.translation.at(time) {
+            transform = transform.translate(translation);
+        }
+        if let Some(rotation) = self.rotation.at(time) {
+            transform = transform.rotate(rotation);
+        }
+        if let Some(scale) = self.scale.at(time) {
+            transform = transform.scale(scale);
+        }
+        transform
+    }
+}
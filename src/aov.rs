@@ -0,0 +1,340 @@
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+
+use crate::color::{self, Color};
+use crate::ppm::Image;
+use crate::ray::{Point3, Ray};
+use crate::render::PathLight;
+use crate::vec::Vec3;
+use crate::world::World;
+
+/// Arbitrary output variable (AOV), an image rendered alongside the beauty image.
+///
+/// Except for the lighting and the variance, all of them describe the first surface a camera ray
+/// hits. Where the rays escape the scene, they are zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aov {
+    /// Albedo of the surface, see `Material::albedo`.
+    Albedo,
+    /// Unit shading normal, facing the camera.
+    Normal,
+    /// Position in world space.
+    Position,
+    /// Distance from the camera.
+    Depth,
+    /// Index of the object plus one, see `World::hit`.
+    ObjectId,
+    /// Index of the material plus one, see `World::material_id`.
+    MaterialId,
+    /// Light emitted towards the camera and light scattered once by the first surface.
+    Direct,
+    /// Light scattered more than once, adds up to the beauty image with the direct light.
+    Indirect,
+    /// Variance of the pixel luminance, estimated from the spread of its samples.
+    Variance,
+}
+
+impl Aov {
+    /// All AOVs, in the order their images are written.
+    pub const ALL: [Aov; 9] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Position,
+        Aov::Depth,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Variance,
+    ];
+
+    /// Returns the name used on the command line and in file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Position => "position",
+            Aov::Depth => "depth",
+            Aov::ObjectId => "object-id",
+            Aov::MaterialId => "material-id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Variance => "variance",
+        }
+    }
+
+    /// Parse a comma separated list of AOV names, "all" selects every AOV.
+    ///
+    /// * `list` - List like "albedo,normal,depth".
+    pub fn parse_list(list: &str) -> io::Result<Vec<Aov>> {
+        if list == "all" {
+            return Ok(Aov::ALL.to_vec());
+        }
+
+        let mut aovs = Vec::new();
+        for name in list.split(',').map(str::trim) {
+            match Aov::ALL.iter().find(|aov| aov.name() == name) {
+                Some(aov) if !aovs.contains(aov) => aovs.push(*aov),
+                Some(_) => {}
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("unknown AOV: {}", name),
+                    ))
+                }
+            }
+        }
+        Ok(aovs)
+    }
+
+    /// Returns the number of channels: one for scalars, three for colors and vectors.
+    pub fn channels(&self) -> usize {
+        match self {
+            Aov::Depth | Aov::ObjectId | Aov::MaterialId | Aov::Variance => 1,
+            _ => 3,
+        }
+    }
+
+    /// Whether the values are identifiers, which must not be averaged.
+    fn is_id(&self) -> bool {
+        matches!(self, Aov::ObjectId | Aov::MaterialId)
+    }
+}
+
+/// Values of all AOVs for one camera ray.
+pub struct AovSample {
+    albedo: Color,
+    normal: Vec3<f64>,
+    position: Point3<f64>,
+    depth: f64,
+    object_id: usize,
+    material_id: usize,
+    direct: Color,
+    indirect: Color,
+    luminance: f64,
+}
+
+impl AovSample {
+    /// Compute the AOVs of a camera ray.
+    ///
+    /// * `ray` - Camera ray.
+    /// * `world` - Scene the ray was traced in.
+    /// * `light` - Light arriving along the ray, see `path_light`.
+    pub fn new(ray: &Ray<f64>, world: &World<f64>, light: &PathLight) -> Self {
+        let black = Color::new3(0.0, 0.0, 0.0);
+        let mut sample = AovSample {
+            albedo: black,
+            normal: Vec3::new3(0.0, 0.0, 0.0),
+            position: Point3::new3(0.0, 0.0, 0.0),
+            depth: 0.0,
+            object_id: 0,
+            material_id: 0,
+            // light emitted by the first surface reaches the camera without any scattering
+            direct: light.emitted + light.direct,
+            indirect: light.indirect,
+            luminance: color::luminance(&light.total()),
+        };
+
+        // the same first hit the path started with
+        if let Some((rec, object)) = world.hit(ray, 0.001, f64::MAX) {
+            sample.albedo = world.material(object).albedo(&rec);
+            sample.normal = rec.normal;
+            sample.position = rec.point;
+            sample.depth = rec.t * ray.direction().length();
+            sample.object_id = object + 1;
+            sample.material_id = world.material_id(object) + 1;
+        }
+        sample
+    }
+
+    /// Returns the value of an AOV, scalars in all three channels.
+    ///
+    /// The variance cannot be computed from a single sample, so this returns the luminance and
+    /// its square instead. Their averages give the variance in the end.
+    fn value(&self, aov: Aov) -> Color {
+        let gray = |value: f64| Color::new3(value, value, value);
+        match aov {
+            Aov::Albedo => self.albedo,
+            Aov::Normal => self.normal,
+            Aov::Position => self.position,
+            Aov::Depth => gray(self.depth),
+            Aov::ObjectId => gray(self.object_id as f64),
+            Aov::MaterialId => gray(self.material_id as f64),
+            Aov::Direct => self.direct,
+            Aov::Indirect => self.indirect,
+            Aov::Variance => Color::new3(self.luminance, self.luminance * self.luminance, 0.0),
+        }
+    }
+}
+
+/// AOV sums and sample counts of one row.
+struct AovRow {
+    // the AOVs of a pixel are next to each other
+    sums: Vec<Color>,
+    samples: Vec<usize>,
+}
+
+/// Film which collects the AOVs of the camera rays.
+///
+/// Unlike the beauty film, every pixel simply averages the samples taken inside it (a box
+/// filter): feature images should not blur across edges, e.g. for denoising. Identifiers cannot
+/// be averaged at all, a pixel keeps the one of its first sample.
+pub struct AovFilm {
+    width: usize,
+    height: usize,
+    aovs: Vec<Aov>,
+    rows: Vec<Mutex<AovRow>>,
+}
+
+impl AovFilm {
+    /// Create a new, empty AOV film.
+    ///
+    /// * `width` - Width in pixels.
+    /// * `height` - Height in pixels.
+    /// * `aovs` - AOVs to collect.
+    pub fn new(width: usize, height: usize, aovs: &[Aov]) -> Self {
+        AovFilm {
+            width,
+            height,
+            aovs: aovs.to_vec(),
+            rows: (0..height)
+                .map(|_| {
+                    Mutex::new(AovRow {
+                        sums: vec![Color::new3(0.0, 0.0, 0.0); width * aovs.len()],
+                        samples: vec![0; width],
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Add the AOVs of a camera ray to a pixel.
+    ///
+    /// * `i` - Column index.
+    /// * `j` - Row index, zero is the bottom row.
+    /// * `sample` - AOVs of a ray through the pixel.
+    pub fn add_sample(&self, i: usize, j: usize, sample: &AovSample) {
+        let mut row = self.rows[j].lock().unwrap();
+        let first = row.samples[i] == 0;
+        row.samples[i] += 1;
+        let offset = i * self.aovs.len();
+        for (k, aov) in self.aovs.iter().enumerate() {
+            if !aov.is_id() {
+                row.sums[offset + k] = row.sums[offset + k] + sample.value(*aov);
+            } else if first {
+                row.sums[offset + k] = sample.value(*aov);
+            }
+        }
+    }
+
+    /// Write the raw AOV contents (sample counts and sums), e.g. for a checkpoint.
+    ///
+    /// Starts with the number of AOVs and their indices in `Aov::ALL`, followed by the pixels row
+    /// by row from the bottom: the sample count and three sums per AOV, all as little endian
+    /// 64 bit values. The film must not be rendered to at the same time.
+    ///
+    /// * `writer` - Destination of the data.
+    pub fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.aovs.len() as u64).to_le_bytes())?;
+        for aov in &self.aovs {
+            let index = Aov::ALL.iter().position(|other| other == aov).unwrap();
+            writer.write_all(&(index as u64).to_le_bytes())?;
+        }
+
+        for row in &self.rows {
+            let row = row.lock().unwrap();
+            for (i, samples) in row.samples.iter().enumerate() {
+                writer.write_all(&(*samples as u64).to_le_bytes())?;
+                for sum in &row.sums[i * self.aovs.len()..(i + 1) * self.aovs.len()] {
+                    for value in &[sum.x(), sum.y(), sum.z()] {
+                        writer.write_all(&value.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore raw AOV contents written by `save`.
+    ///
+    /// The saved AOVs may include more than the ones collected by this film, the others are
+    /// skipped. Fails if one of the collected AOVs was not saved, since its samples would be
+    /// missing. The film must have the same size as the one that was saved.
+    ///
+    /// * `reader` - Source of the data.
+    pub fn load<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut bytes = [0u8; 8];
+        let mut read = |reader: &mut R| -> io::Result<u64> {
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+
+        let count = read(reader)? as usize;
+        let mut saved = Vec::with_capacity(count.min(Aov::ALL.len()));
+        for _ in 0..count {
+            match Aov::ALL.get(read(reader)? as usize) {
+                Some(aov) => saved.push(*aov),
+                None => return Err(invalid("unknown AOV".to_string())),
+            }
+        }
+        // position of each collected AOV among the saved ones
+        let mut positions = Vec::with_capacity(self.aovs.len());
+        for aov in &self.aovs {
+            match saved.iter().position(|other| other == aov) {
+                Some(k) => positions.push(k),
+                None => return Err(invalid(format!("{} was not saved", aov.name()))),
+            }
+        }
+
+        let mut sums = vec![Color::new3(0.0, 0.0, 0.0); saved.len()];
+        for row in &mut self.rows {
+            let row = row.get_mut().unwrap();
+            for i in 0..self.width {
+                row.samples[i] = read(reader)? as usize;
+                for sum in sums.iter_mut() {
+                    let r = f64::from_bits(read(reader)?);
+                    let g = f64::from_bits(read(reader)?);
+                    let b = f64::from_bits(read(reader)?);
+                    *sum = Color::new3(r, g, b);
+                }
+                for (k, position) in positions.iter().enumerate() {
+                    row.sums[i * self.aovs.len() + k] = sums[*position];
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the image of an AOV, none if it was not collected.
+    ///
+    /// * `aov` - AOV to return.
+    pub fn image(&self, aov: Aov) -> Option<Image<Color>> {
+        let k = self.aovs.iter().position(|other| *other == aov)?;
+        let mut img = Image::new(self.width, self.height, Color::new3(0.0, 0.0, 0.0));
+        for j in 0..self.height {
+            let row = self.rows[j].lock().unwrap();
+            for i in 0..self.width {
+                let samples = row.samples[i];
+                let sum = row.sums[i * self.aovs.len() + k];
+                img[j][i] = if aov == Aov::Variance {
+                    // unbiased sample variance, divided by the number of samples for the
+                    // variance of their mean
+                    let n = samples as f64;
+                    let mean = sum.x() / n;
+                    let variance = if samples > 1 {
+                        (sum.y() / n - mean * mean).max(0.0) / (n - 1.0)
+                    } else {
+                        0.0
+                    };
+                    Color::new3(variance, variance, variance)
+                } else if samples == 0 || aov.is_id() {
+                    sum
+                } else {
+                    sum / samples as f64
+                };
+            }
+        }
+        Some(img)
+    }
+}
@@ -0,0 +1,117 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::color::{self, Color};
+use crate::distribution::Distribution2D;
+use crate::ppm::Image;
+use crate::rtweekend;
+
+/// Shape of the lens aperture.
+///
+/// The aperture shape determines the shape of out-of-focus highlights (bokeh): every point light
+/// outside the focus plane is imaged as a scaled copy of the aperture.
+#[derive(Clone)]
+pub enum ApertureShape {
+    /// Perfectly round aperture.
+    Circle,
+    /// Regular polygon formed by straight diaphragm blades.
+    Polygon {
+        /// Number of blades (corners), at least three.
+        blades: usize,
+        /// Rotation of the polygon in radians.
+        rotation: f64,
+    },
+    /// Arbitrary aperture described by a grayscale transmission image.
+    Mask(Arc<ApertureMask>),
+}
+
+impl ApertureShape {
+    /// Sample a point on the aperture.
+    ///
+    /// Returns a point in [-1, 1]² (the unit disk for round apertures), uniformly distributed over
+    /// the open area of the aperture.
+    pub fn sample(&self) -> (f64, f64) {
+        match self {
+            ApertureShape::Circle => random_in_unit_disk(),
+            ApertureShape::Polygon { blades, rotation } => random_in_polygon(*blades, *rotation),
+            ApertureShape::Mask(mask) => mask.sample(),
+        }
+    }
+}
+
+/// Aperture transmission image.
+pub struct ApertureMask {
+    distribution: Distribution2D,
+}
+
+impl ApertureMask {
+    /// Create a new aperture mask from an image.
+    ///
+    /// The image is stretched over the lens, its luminance is the transmission of the aperture
+    /// (black is closed, white is open).
+    ///
+    /// * `image` - Mask image, the first row is the top of the aperture.
+    pub fn new(image: &Image<Color>) -> Self {
+        let mut func = vec![0.0; image.width() * image.height()];
+        for j in 0..image.height() {
+            for i in 0..image.width() {
+                func[j * image.width() + i] = color::luminance(&image[j][i]).max(0.0);
+            }
+        }
+
+        ApertureMask {
+            distribution: Distribution2D::new(&func, image.width(), image.height()),
+        }
+    }
+
+    /// Sample a point on the aperture, proportional to the transmission.
+    fn sample(&self) -> (f64, f64) {
+        let xi = (rtweekend::random(0.0..1.0), rtweekend::random(0.0..1.0));
+        let ((u, v), _) = self.distribution.sample(xi);
+        // the first image row is the top, so flip v
+        (u * 2.0 - 1.0, 1.0 - v * 2.0)
+    }
+}
+
+/// Find a random point in the unit disk.
+///
+/// Uses the concentric mapping by Shirley and Chiu, which maps concentric squares of the unit
+/// square to concentric circles of the disk. Unlike rejection sampling or taking the square root
+/// of a random radius, it keeps neighboring samples close together and needs exactly two random
+/// numbers.
+fn random_in_unit_disk() -> (f64, f64) {
+    // map the random numbers to [-1, 1]²
+    let a: f64 = 2.0 * rtweekend::random(0.0..1.0) - 1.0;
+    let b: f64 = 2.0 * rtweekend::random(0.0..1.0) - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    // the larger coordinate determines the radius, the ratio of both the angle
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Find a random point in a regular polygon inscribed in the unit circle.
+///
+/// The polygon is made up of identical triangles fanning out from the center, so we pick one at
+/// random and then pick a uniformly distributed point inside it.
+fn random_in_polygon(blades: usize, rotation: f64) -> (f64, f64) {
+    let blades = blades.max(3);
+    let step = 2.0 * PI / blades as f64;
+    let index = rtweekend::random(0..blades);
+    let angle_a = rotation + step * index as f64;
+    let angle_b = angle_a + step;
+
+    // Uniform point in the triangle (center, A, B): the square root keeps the density uniform,
+    // since the triangle grows linearly with the distance from the center.
+    let s = rtweekend::random(0.0f64..1.0).sqrt();
+    let t = rtweekend::random(0.0..1.0);
+    let x = s * ((1.0 - t) * angle_a.cos() + t * angle_b.cos());
+    let y = s * ((1.0 - t) * angle_a.sin() + t * angle_b.sin());
+    (x, y)
+}
@@ -1,8 +1,38 @@
+use std::f64::consts::PI;
+
+use crate::aperture::ApertureShape;
 use crate::ray::{Point3, Ray};
 use crate::rtweekend;
 use crate::vec::Vec3;
 
+/// Mapping of a fisheye lens from the angle θ of an incoming ray (measured from the optical axis)
+/// to the distance r of its image from the image center.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FisheyeMapping {
+    /// Equidistant: r ∝ θ, so angles are preserved along the radius.
+    Equidistant,
+    /// Equisolid angle: r ∝ sin(θ/2), so areas (solid angles) are preserved.
+    Equisolid,
+}
+
+/// Model used by the camera to map image coordinates to rays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Pinhole camera (or thin lens with depth of field), straight lines stay straight.
+    Perspective,
+    /// Parallel rays, objects keep their size regardless of their distance. The visible area
+    /// matches the perspective view at the distance of the target point.
+    Orthographic,
+    /// Circular fisheye image inscribed into the image height. The vertical FOV is the full
+    /// opening angle of the lens and may exceed 180 degrees.
+    Fisheye(FisheyeMapping),
+    /// Full 360° x 180° panorama in latitude/longitude layout, the image center looks at the
+    /// target point.
+    Equirectangular,
+}
+
 /// Simple virtual camera.
+#[derive(Clone)]
 pub struct Camera {
     // width, not adjusted for FOV
     width: f64,
@@ -14,9 +44,28 @@ pub struct Camera {
     aperture: f64,
     // focus distance for defocus blur
     focus_distance: f64,
+    // shape of the aperture, determines the shape of the bokeh
+    aperture_shape: ApertureShape,
+    // horizontal squeeze factor of an anamorphic lens
+    squeeze: f64,
+    // strength of the mechanical (cat's eye) vignetting
+    cats_eye: f64,
     lookfrom: Point3<f64>,
     lookat: Point3<f64>,
     up: Point3<f64>,
+    projection: Projection,
+    // distance between the eyes for stereo rendering, zero for mono
+    eye_separation: f64,
+    // lens shift in fractions of the image width and height
+    shift: (f64, f64),
+    // rotation of the focus plane around the horizontal (tilt) and vertical (swing) axis
+    tilt: f64,
+    swing: f64,
+    // explicit focus plane through three points, overrides tilt and swing
+    focus_points: Option<[Point3<f64>; 3]>,
+    // point on and normal of the focus plane
+    focus_origin: Point3<f64>,
+    focus_normal: Vec3<f64>,
     w: Vec3<f64>,
     u: Vec3<f64>,
     v: Vec3<f64>,
@@ -68,9 +117,20 @@ impl Camera {
             vfov,
             aperture,
             focus_distance,
+            aperture_shape: ApertureShape::Circle,
+            squeeze: 1.0,
+            cats_eye: 0.0,
             lookfrom,
             lookat,
             up,
+            projection: Projection::Perspective,
+            eye_separation: 0.0,
+            shift: (0.0, 0.0),
+            tilt: 0.0,
+            swing: 0.0,
+            focus_points: None,
+            focus_origin: Vec3::new3(0.0, 0.0, 0.0),
+            focus_normal: Vec3::new3(0.0, 0.0, 0.0),
             w: Vec3::new3(0.0, 0.0, 0.0),
             u: Vec3::new3(0.0, 0.0, 0.0),
             v: Vec3::new3(0.0, 0.0, 0.0),
@@ -101,6 +161,23 @@ impl Camera {
         self
     }
 
+    /// Returns the camera position (lookfrom) and target point (lookat).
+    pub fn placement(&self) -> (Point3<f64>, Point3<f64>) {
+        (self.lookfrom, self.lookat)
+    }
+
+    /// Returns the vertical field of view in degrees.
+    #[cfg(feature = "minifb")]
+    pub fn field_of_view(&self) -> f64 {
+        self.vfov.to_degrees()
+    }
+
+    /// Returns the aperture diameter and the focus distance.
+    #[cfg(feature = "minifb")]
+    pub fn lens_settings(&self) -> (f64, f64) {
+        (self.aperture, self.focus_distance)
+    }
+
     /// Adjusts the camera direction.
     ///
     /// * `up`: New up vector (pointing towards the sky).
@@ -131,24 +208,250 @@ impl Camera {
         self
     }
 
+    /// Adjusts the focus distance, keeping the aperture.
+    ///
+    /// * `focus_dist`: Distance from the camera to the focus plane.
+    pub fn focus(mut self, focus_dist: f64) -> Self {
+        self.focus_distance = focus_dist;
+        self.update_perspective();
+        self
+    }
+
+    /// Adjusts the shape of the aperture.
+    ///
+    /// * `shape`: New aperture shape, the default is a circle.
+    pub fn aperture_shape(mut self, shape: ApertureShape) -> Self {
+        self.aperture_shape = shape;
+        self
+    }
+
+    /// Simulates an anamorphic lens.
+    ///
+    /// Anamorphic lenses squeeze the image horizontally, which squeezes the aperture as seen from
+    /// the film as well. Out-of-focus highlights become ovals which are taller than wide.
+    ///
+    /// * `squeeze`: Squeeze factor, e.g. 1.33 or 2.0. 1.0 is a regular (spherical) lens.
+    pub fn anamorphic(mut self, squeeze: f64) -> Self {
+        self.squeeze = squeeze.max(1e-3);
+        self
+    }
+
+    /// Simulates mechanical vignetting (cat's eye bokeh).
+    ///
+    /// Towards the image borders, the lens barrel cuts off part of the aperture. The visible
+    /// aperture is the intersection of the lens opening with a second opening of the same size,
+    /// shifted outwards in proportion to the distance from the image center. Highlights turn into
+    /// cat's eyes and the image corners get darker.
+    ///
+    /// * `strength`: Shift of the second opening at the top and bottom image border in units of
+    ///   the aperture radius, 0.0 disables the effect.
+    pub fn cats_eye(mut self, strength: f64) -> Self {
+        self.cats_eye = strength.max(0.0);
+        self
+    }
+
+    /// Shifts the lens parallel to the film (off-axis projection).
+    ///
+    /// The viewing direction stays the same, only the visible part of the image plane moves. This
+    /// is how architectural photographers keep vertical lines vertical: level the camera (lookat at
+    /// the height of lookfrom) and shift the lens upwards until the building fits into the frame.
+    ///
+    /// * `x`: Horizontal shift in fractions of the image width, positive values move to the right.
+    /// * `y`: Vertical shift in fractions of the image height, positive values move up.
+    pub fn shift(mut self, x: f64, y: f64) -> Self {
+        self.shift = (x, y);
+        self.update_perspective();
+        self
+    }
+
+    /// Tilts the focus plane (Scheimpflug principle).
+    ///
+    /// Tilting the lens against the film tilts the plane of sharp focus. The plane still passes
+    /// through the point at the focus distance in front of the camera, but is rotated around the
+    /// horizontal (tilt) and the vertical (swing) axis through that point:
+    ///
+    ///            focus plane
+    ///              /
+    ///             /  tilt
+    ///   camera   /__
+    ///     O ----x----------> -w
+    ///          /
+    ///         /
+    ///
+    /// Only has an effect with a non-zero aperture.
+    ///
+    /// * `tilt`: Rotation around the horizontal axis in degrees, positive values move the upper
+    ///   part of the plane away from the camera (e.g. to keep a floor in focus).
+    /// * `swing`: Rotation around the vertical axis in degrees, positive values move the right
+    ///   part of the plane away from the camera.
+    pub fn tilt(mut self, tilt: f64, swing: f64) -> Self {
+        self.tilt = rtweekend::degrees_to_radians(tilt);
+        self.swing = rtweekend::degrees_to_radians(swing);
+        self.focus_points = None;
+        self.update_perspective();
+        self
+    }
+
+    /// Places the focus plane through three points in the scene.
+    ///
+    /// Overrides the focus distance and any tilt, the points must not be on a common line.
+    ///
+    /// * `a`, `b`, `c`: Points which shall be in focus.
+    pub fn focus_plane(mut self, a: Point3<f64>, b: Point3<f64>, c: Point3<f64>) -> Self {
+        self.focus_points = Some([a, b, c]);
+        self.update_perspective();
+        self
+    }
+
+    /// Adjusts the projection model.
+    ///
+    /// * `projection`: New projection, the default is perspective.
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self.update_perspective();
+        self
+    }
+
+    /// Renders a stereo pair for VR in over-under layout.
+    ///
+    /// The upper half of the image shows the left eye, the lower half the right eye. The viewport
+    /// size given to the constructor describes a single eye, so the image needs twice its height.
+    /// Equirectangular panoramas use omni-directional stereo: the eyes move on a circle, so the
+    /// offset between them is correct for every viewing direction.
+    ///
+    /// * `eye_separation`: Distance between the eyes (interpupillary distance) in scene units.
+    pub fn stereo(mut self, eye_separation: f64) -> Self {
+        self.eye_separation = eye_separation;
+        self
+    }
+
     /// Returns the ray for a given horizontal/vertical offset.
-    pub fn ray(&self, u: f64, v: f64) -> Ray<f64> {
-        // Instead of sending all rays through the lookfrom point (lens of size zero), send them
-        // through a disk centered at the lookfrom position. This way, we can achieve defocus blur
-        // (depth of field).
-        let origin = if self.aperture > 0.0 {
-            let lens_radius = self.aperture / 2.0;
-            let random = rtweekend::random_vec_in_unit_sphere() * lens_radius;
-            let offset = self.u * random.x() + self.v * random.y();
-            self.lookfrom + offset
+    ///
+    /// Both offsets range from 0.0 to 1.0, starting in the lower left corner. Returns none for
+    /// image positions which are not covered by the projection, e.g. the corners of a circular
+    /// fisheye image.
+    pub fn ray(&self, u: f64, v: f64) -> Option<Ray<f64>> {
+        if self.eye_separation <= 0.0 {
+            return self.eye_ray(u, v, 0.0);
+        }
+
+        // over-under layout: left eye on top, right eye at the bottom
+        if v >= 0.5 {
+            self.eye_ray(u, (v - 0.5) * 2.0, -self.eye_separation / 2.0)
         } else {
-            self.lookfrom
-        };
+            self.eye_ray(u, v * 2.0, self.eye_separation / 2.0)
+        }
+    }
 
-        Ray::new(
-            origin,
-            self.lower_left_corner + self.horizontal * u + self.vertical * v - origin,
-        )
+    /// Returns the ray for a given offset as seen by one eye.
+    ///
+    /// * `u` - Horizontal offset in [0, 1].
+    /// * `v` - Vertical offset in [0, 1].
+    /// * `eye` - Offset of the eye to the right of the camera position, zero for mono.
+    fn eye_ray(&self, u: f64, v: f64, eye: f64) -> Option<Ray<f64>> {
+        match self.projection {
+            Projection::Perspective => {
+                // Instead of sending all rays through the lookfrom point (lens of size zero), send
+                // them through the aperture centered at the lookfrom position. This way, we can
+                // achieve defocus blur (depth of field).
+                let lookfrom = self.lookfrom + self.u * eye;
+                let origin = if self.aperture > 0.0 {
+                    let (x, y) = self.aperture_shape.sample();
+
+                    if self.cats_eye > 0.0 {
+                        // the second opening is shifted towards the image position
+                        let aspect_ratio = self.width / self.height;
+                        let cx = (u - 0.5) * 2.0 * aspect_ratio * self.cats_eye;
+                        let cy = (v - 0.5) * 2.0 * self.cats_eye;
+                        if (x - cx) * (x - cx) + (y - cy) * (y - cy) > 1.0 {
+                            // blocked by the lens barrel
+                            return None;
+                        }
+                    }
+
+                    let lens_radius = self.aperture / 2.0;
+                    let offset =
+                        self.u * (x / self.squeeze * lens_radius) + self.v * (y * lens_radius);
+                    lookfrom + offset
+                } else {
+                    lookfrom
+                };
+
+                // shift the focus plane along with the eye, so the eyes look in parallel
+                let target =
+                    self.lower_left_corner + self.u * eye + self.horizontal * u + self.vertical * v;
+                if self.aperture <= 0.0 {
+                    return Some(Ray::new(origin, target - origin));
+                }
+
+                // All rays through the lens meet again where the chief ray (the one through the
+                // lens center) crosses the focus plane:
+                //
+                //     t = ((P - O) · n) / (d · n)
+                //
+                // With an untilted focus plane, that is exactly the target on the viewport.
+                let chief = target - lookfrom;
+                let denominator = Vec3::dot(&chief, &self.focus_normal);
+                let t =
+                    Vec3::dot(&(self.focus_origin - lookfrom), &self.focus_normal) / denominator;
+                let focus = if t > 0.0 && t.is_finite() {
+                    lookfrom + chief * t
+                } else {
+                    // the chief ray does not hit the focus plane in front of the camera, so the
+                    // scene is out of focus in this direction anyway
+                    target
+                };
+                Some(Ray::new(origin, focus - origin))
+            }
+            Projection::Orthographic => {
+                // all rays are parallel, the origins are spread over the viewport instead
+                let distance = (self.lookat - self.lookfrom).length();
+                let scale = distance / self.focus_distance;
+                let origin = self.lookfrom
+                    + self.u * eye
+                    + self.horizontal * ((u - 0.5 + self.shift.0) * scale)
+                    + self.vertical * ((v - 0.5 + self.shift.1) * scale);
+                Some(Ray::new(origin, -self.w))
+            }
+            Projection::Fisheye(mapping) => {
+                // Coordinates relative to the image center, scaled so the image circle has a
+                // radius of 1.0 and touches the top and bottom of the image.
+                let aspect_ratio = self.width / self.height;
+                let x = (u - 0.5) * 2.0 * aspect_ratio;
+                let y = (v - 0.5) * 2.0;
+                let r = (x * x + y * y).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+
+                // invert the lens mapping to find the angle from the optical axis
+                let theta_max = self.vfov / 2.0;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * theta_max,
+                    FisheyeMapping::Equisolid => {
+                        2.0 * rtweekend::clamp(r * (theta_max / 2.0).sin(), -1.0, 1.0).asin()
+                    }
+                };
+                let phi = y.atan2(x);
+
+                let direction = self.u * (theta.sin() * phi.cos())
+                    + self.v * (theta.sin() * phi.sin())
+                    - self.w * theta.cos();
+                Some(Ray::new(self.lookfrom + self.u * eye, direction))
+            }
+            Projection::Equirectangular => {
+                // longitude φ in [-π, π] (0 = forward), latitude θ in [-π/2, π/2] (0 = horizon)
+                let phi = (u - 0.5) * 2.0 * PI;
+                let theta = (v - 0.5) * PI;
+
+                let direction = self.u * (theta.cos() * phi.sin()) + self.v * theta.sin()
+                    - self.w * (theta.cos() * phi.cos());
+                // omni-directional stereo: the eye offset is perpendicular to the horizontal
+                // viewing direction
+                let right = self.u * phi.cos() + self.w * phi.sin();
+                Some(Ray::new(self.lookfrom + right * eye, direction))
+            }
+        }
     }
 
     fn update_perspective(&mut self) {
@@ -173,8 +476,23 @@ impl Camera {
         //  1. go left as far as possible (half the viewport)
         //  2. go down as far as possible (half the viewport)
         //  3. move forward (negative z direction) so we lay flat on the surface
-        let lower_left_corner =
-            self.lookfrom - horizontal / 2.0 - vertical / 2.0 - w * self.focus_distance;
+        //  4. apply the lens shift
+        let lower_left_corner = self.lookfrom - horizontal / 2.0 - vertical / 2.0
+            + horizontal * self.shift.0
+            + vertical * self.shift.1
+            - w * self.focus_distance;
+
+        // The focus plane either goes through the given points or through the center of the
+        // (unshifted) viewport, rotated by tilt and swing. Without rotation, the normal is w.
+        let (focus_origin, focus_normal) = match self.focus_points {
+            Some([a, b, c]) => (a, Vec3::cross(&(b - a), &(c - a)).normalized()),
+            None => {
+                let normal = w * (self.tilt.cos() * self.swing.cos())
+                    + v * self.tilt.sin()
+                    + u * (self.tilt.cos() * self.swing.sin());
+                (self.lookfrom - w * self.focus_distance, normal)
+            }
+        };
 
         self.w = w;
         self.u = u;
@@ -182,5 +500,7 @@ impl Camera {
         self.horizontal = horizontal;
         self.vertical = vertical;
         self.lower_left_corner = lower_left_corner;
+        self.focus_origin = focus_origin;
+        self.focus_normal = focus_normal;
     }
 }
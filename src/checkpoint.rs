@@ -0,0 +1,199 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::aov::AovFilm;
+use crate::cli::Args;
+use crate::film::{Film, Filter, FilterKind};
+
+/// File signature, the last two bytes are the format version.
+const MAGIC: &[u8; 8] = b"RTCKPT02";
+
+/// Render settings which have to match when resuming from a checkpoint.
+///
+/// The number of samples per pixel is not part of the settings: every sample uses its own random
+/// sequence derived from the seed, so a checkpoint may be resumed with a larger sample count and
+/// the result is the same as rendering that many samples right away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// Image width in pixels.
+    pub width: usize,
+    /// Image height in pixels.
+    pub height: usize,
+    /// Maximum number of bounces per path.
+    pub max_depth: usize,
+    /// Reconstruction filter of the film.
+    pub filter: Filter,
+    /// Base seed of the random number generator.
+    pub seed: u64,
+    /// Hash of the scene description, see `scene_hash`.
+    pub scene_hash: u64,
+}
+
+impl Settings {
+    /// Returns the settings as named 64 bit values, in the order they are stored.
+    fn fields(&self) -> Vec<(&'static str, u64)> {
+        let (kind, a, b) = match self.filter.kind() {
+            FilterKind::Box => (0, 0.0, 0.0),
+            FilterKind::Tent => (1, 0.0, 0.0),
+            FilterKind::Gaussian { alpha } => (2, alpha, 0.0),
+            FilterKind::Mitchell { b, c } => (3, b, c),
+            FilterKind::Lanczos => (4, 0.0, 0.0),
+        };
+
+        vec![
+            ("image width", self.width as u64),
+            ("image height", self.height as u64),
+            ("maximum depth", self.max_depth as u64),
+            ("filter", kind),
+            ("filter", a.to_bits()),
+            ("filter", b.to_bits()),
+            ("filter radius", self.filter.radius().to_bits()),
+            ("seed", self.seed),
+            ("scene", self.scene_hash),
+        ]
+    }
+
+    /// Write the settings as little endian 64 bit values.
+    ///
+    /// * `writer` - Destination of the settings.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for (_, value) in self.fields() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read settings written by `write` and compare them to these ones.
+    ///
+    /// Returns the name of the first setting which differs, if any.
+    ///
+    /// * `reader` - Source of the settings.
+    pub fn compare<R: Read>(&self, reader: &mut R) -> io::Result<Option<&'static str>> {
+        for (name, expected) in self.fields() {
+            let mut bytes = [0u8; 8];
+            reader.read_exact(&mut bytes)?;
+            if u64::from_le_bytes(bytes) != expected {
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Compute a hash of the scene description given on the command line.
+///
+/// Scenes are built from the command line arguments (and the seed for random scenes), so hashing
+/// the arguments detects attempts to resume a checkpoint with a different scene or camera. Uses
+/// 64 bit FNV-1a, which unlike the standard library hasher is stable across builds.
+///
+/// * `args` - Command line arguments.
+/// * `ignore` - Options which do not affect the image, e.g. the checkpoint path.
+pub fn scene_hash(args: &Args, ignore: &[&str]) -> u64 {
+    let mut options: Vec<_> = args
+        .options()
+        .filter(|(name, _)| !ignore.contains(name))
+        .collect();
+    options.sort();
+
+    let mut text = String::new();
+    for arg in args.positionals() {
+        text.push_str(arg);
+        text.push('\0');
+    }
+    for (name, value) in options {
+        text.push_str("--");
+        text.push_str(name);
+        if let Some(value) = value {
+            text.push('=');
+            text.push_str(value);
+        }
+        text.push('\0');
+    }
+
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Write a checkpoint.
+///
+/// The checkpoint holds the settings followed by the film contents, that is the accumulated
+/// radiance, the filter weights and the per-pixel sample counts, and the AOV sums if any. Since
+/// the random sequence of each sample only depends on the seed, the pixel and the sample index,
+/// the counts also capture the state of the random number generator. The file is written to a
+/// temporary file first and then renamed, so an interruption never destroys the previous
+/// checkpoint.
+///
+/// * `path` - Checkpoint file.
+/// * `settings` - Current render settings.
+/// * `film` - Film to save, must not be rendered to at the same time.
+/// * `aovs` - AOV film to save, if AOVs are collected.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    settings: &Settings,
+    film: &Film,
+    aovs: Option<&AovFilm>,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+
+    {
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        writer.write_all(MAGIC)?;
+        settings.write(&mut writer)?;
+        film.save(&mut writer)?;
+        match aovs {
+            Some(aovs) => aovs.save(&mut writer)?,
+            // no AOVs
+            None => writer.write_all(&0u64.to_le_bytes())?,
+        }
+        writer.flush()?;
+    }
+
+    fs::rename(&temp, path)
+}
+
+/// Load a checkpoint into a film.
+///
+/// Fails if the checkpoint was written with different settings, or without the AOVs which are
+/// collected now.
+///
+/// * `path` - Checkpoint file.
+/// * `settings` - Current render settings.
+/// * `film` - Film to restore, must have the size given in the settings.
+/// * `aovs` - AOV film to restore, if AOVs are collected.
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    settings: &Settings,
+    film: &mut Film,
+    aovs: Option<&mut AovFilm>,
+) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a checkpoint file".to_string()));
+    }
+
+    if let Some(name) = settings.compare(&mut reader)? {
+        return Err(invalid(format!(
+            "checkpoint was written with a different {}",
+            name
+        )));
+    }
+
+    film.load(&mut reader)?;
+    match aovs {
+        Some(aovs) => aovs.load(&mut reader).map_err(|err| {
+            invalid(format!(
+                "checkpoint does not hold the AOVs: {}, render without --resume",
+                err
+            ))
+        }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Command line arguments.
+///
+/// Arguments starting with two dashes are options, either given as flags (`--name`) or with a
+/// value (`--name=value`). All other arguments are positional, e.g. the scene name.
+#[derive(Clone)]
+pub struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    /// Parse the arguments of the current process.
+    pub fn parse() -> Self {
+        Args::from(std::env::args().skip(1))
+    }
+
+    /// Parse arguments from an iterator, not including the program name.
+    ///
+    /// * `args` - Arguments to parse.
+    pub fn from<I: Iterator<Item = String>>(args: I) -> Self {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+
+        for arg in args {
+            if let Some(option) = arg.strip_prefix("--") {
+                match option.split_once('=') {
+                    Some((name, value)) => {
+                        options.insert(name.to_string(), Some(value.to_string()));
+                    }
+                    None => {
+                        options.insert(option.to_string(), None);
+                    }
+                }
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        Args {
+            positional,
+            options,
+        }
+    }
+
+    /// Returns a positional argument.
+    ///
+    /// * `index` - Index among the positional arguments, starting at zero.
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positional.get(index).map(|arg| arg.as_str())
+    }
+
+    /// Returns a positional argument parsed into a given type.
+    ///
+    /// Arguments which cannot be parsed are treated like missing ones.
+    ///
+    /// * `index` - Index among the positional arguments, starting at zero.
+    pub fn positional_as<T: FromStr>(&self, index: usize) -> Option<T> {
+        self.positional(index).and_then(|arg| arg.parse().ok())
+    }
+
+    /// Returns the value of an option given as `--name=value`.
+    ///
+    /// Values which cannot be parsed are treated like missing ones.
+    ///
+    /// * `name` - Option name without the dashes.
+    pub fn value<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.options
+            .get(name)
+            .and_then(|value| value.as_ref())
+            .and_then(|value| value.parse().ok())
+    }
+
+    /// Returns all positional arguments.
+    pub fn positionals(&self) -> impl Iterator<Item = &str> {
+        self.positional.iter().map(|arg| arg.as_str())
+    }
+
+    /// Returns all options with their values, in no particular order.
+    pub fn options(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.options
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+    }
+
+    /// Returns the arguments as strings, e.g. to pass them on to another process.
+    ///
+    /// * `ignore` - Options to leave out.
+    pub fn to_vec(&self, ignore: &[&str]) -> Vec<String> {
+        let mut args = self.positional.clone();
+        for (name, value) in self.options() {
+            if ignore.contains(&name) {
+                continue;
+            }
+            args.push(match value {
+                Some(value) => format!("--{}={}", name, value),
+                None => format!("--{}", name),
+            });
+        }
+        args
+    }
+
+    /// Whether an option was given, with or without a value.
+    ///
+    /// * `name` - Option name without the dashes.
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+}
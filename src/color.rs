@@ -1,4 +1,41 @@
+use crate::rtweekend;
 use crate::vec::Vec3;
 
 /// RGB color with each channel ranging from 0.0 to 1.0
 pub type Color = Vec3<f64>;
+
+/// Returns the luminance (perceived brightness) of a linear RGB color.
+///
+/// Uses the Rec. 709 primaries, which are the ones of sRGB.
+pub fn luminance(color: &Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+/// Post processing to transform RGB channels into PPM RGB color values.
+///
+/// We perform two steps:
+///     1. Gamma correction using gamma=2
+///     2. Color value mapping from [0.0, 1.0] to [0, 255]
+pub fn color_to_rgb8(color: &Color) -> [u8; 3] {
+    let mut r = color.x();
+    let mut g = color.y();
+    let mut b = color.z();
+
+    // gamma correction: raise color to the power of 1/gamma
+    // here: use gamma=2 as first approximation
+    r = r.sqrt();
+    g = g.sqrt();
+    b = b.sqrt();
+
+    // clamp to [0.0, 1.0] range
+    r = rtweekend::clamp(r, 0.0, 0.999);
+    g = rtweekend::clamp(g, 0.0, 0.999);
+    b = rtweekend::clamp(b, 0.0, 0.999);
+
+    // map to [0, 255] range
+    r = 256.0 * r;
+    g = 256.0 * g;
+    b = 256.0 * b;
+
+    [r as u8, g as u8, b as u8]
+}
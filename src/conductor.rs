@@ -0,0 +1,299 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend;
+use crate::spectrum::Wavelengths;
+use crate::vec::Vec3;
+
+/// Complex index of refraction η + i⋅k of a conductor, tabulated over the visible spectrum.
+///
+/// The presets are measurements of the pure metals (Johnson and Christy for gold, copper and
+/// silver, Rakić for aluminium), sampled every 50 nm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComplexIor {
+    // wavelength in nm, η, k, sorted by wavelength
+    table: &'static [(f64, f64, f64)],
+}
+
+impl ComplexIor {
+    /// Gold (Au).
+    pub const GOLD: ComplexIor = ComplexIor {
+        table: &[
+            (400.0, 1.47, 1.95),
+            (450.0, 1.38, 1.91),
+            (500.0, 0.97, 1.87),
+            (550.0, 0.42, 2.46),
+            (600.0, 0.25, 3.07),
+            (650.0, 0.16, 3.61),
+            (700.0, 0.13, 4.07),
+        ],
+    };
+
+    /// Copper (Cu).
+    pub const COPPER: ComplexIor = ComplexIor {
+        table: &[
+            (400.0, 1.18, 2.21),
+            (450.0, 1.15, 2.43),
+            (500.0, 1.12, 2.60),
+            (550.0, 0.94, 2.58),
+            (600.0, 0.26, 3.35),
+            (650.0, 0.21, 3.67),
+            (700.0, 0.21, 4.05),
+        ],
+    };
+
+    /// Aluminium (Al).
+    pub const ALUMINIUM: ComplexIor = ComplexIor {
+        table: &[
+            (400.0, 0.49, 4.86),
+            (450.0, 0.62, 5.47),
+            (500.0, 0.77, 6.08),
+            (550.0, 0.96, 6.69),
+            (600.0, 1.20, 7.26),
+            (650.0, 1.49, 7.82),
+            (700.0, 1.83, 8.31),
+        ],
+    };
+
+    /// Silver (Ag).
+    pub const SILVER: ComplexIor = ComplexIor {
+        table: &[
+            (400.0, 0.05, 2.10),
+            (450.0, 0.04, 2.66),
+            (500.0, 0.05, 3.13),
+            (550.0, 0.06, 3.59),
+            (600.0, 0.06, 4.02),
+            (650.0, 0.05, 4.43),
+            (700.0, 0.04, 4.84),
+        ],
+    };
+
+    /// Returns η and k at a wavelength, linearly interpolated between the table entries.
+    ///
+    /// * `wavelength` - Wavelength in nm, clamped to the range of the table.
+    pub fn at(&self, wavelength: f64) -> (f64, f64) {
+        let table = self.table;
+        let last = table.len() - 1;
+        let index = table
+            .iter()
+            .position(|entry| entry.0 > wavelength)
+            .unwrap_or(last + 1);
+        if index == 0 {
+            return (table[0].1, table[0].2);
+        }
+        if index > last {
+            return (table[last].1, table[last].2);
+        }
+
+        let (l0, eta0, k0) = table[index - 1];
+        let (l1, eta1, k1) = table[index];
+        let t = (wavelength - l0) / (l1 - l0);
+        (eta0 + (eta1 - eta0) * t, k0 + (k1 - k0) * t)
+    }
+
+    /// Returns η and k for the red, green and blue channels, for rendering in RGB.
+    ///
+    /// Each channel is represented by the dominant wavelength of the sRGB primary: 612 nm,
+    /// 549 nm and 464 nm.
+    pub fn rgb(&self) -> (Color, Color) {
+        let (r_eta, r_k) = self.at(612.0);
+        let (g_eta, g_k) = self.at(549.0);
+        let (b_eta, b_k) = self.at(464.0);
+        (Color::new3(r_eta, g_eta, b_eta), Color::new3(r_k, g_k, b_k))
+    }
+}
+
+/// Rough metal, described by the GGX microfacet model.
+///
+/// Light is reflected by microfacets which act as perfect mirrors, the conductor Fresnel term
+/// gives the color and its change towards grazing angles. The BSDF is:
+///
+///     f(ωo, ωi) = F(ωo⋅m)⋅D(m)⋅G2(ωo, ωi) / (4⋅cosθo⋅cosθi),  m = (ωo + ωi) / |ωo + ωi|
+///
+/// Directions are sampled from the visible normals, so the weight of a sample reduces to:
+///
+///     f⋅cosθi / p = F(ωo⋅m)⋅G2(ωo, ωi) / G1(ωo)
+///
+/// which never exceeds one. Light scattered between the microfacets more than once is lost,
+/// so very rough metals are slightly too dark.
+///
+/// Metals created from a preset evaluate the Fresnel term at the wavelengths of a spectral path,
+/// so their color comes from the measured spectrum. Otherwise, it is the RGB color converted like
+/// any other.
+///
+/// Anisotropic metals, like brushed metal, are rougher across the brushing direction. That
+/// direction is the tangent axis projected onto the surface, e.g. the y axis gives brushing
+/// along the meridians of a sphere.
+pub struct Conductor {
+    /// Real part of the index of refraction per channel.
+    eta: Color,
+    /// Extinction coefficient per channel.
+    k: Color,
+    /// Complex index of refraction by wavelength, if known.
+    ior: Option<ComplexIor>,
+    roughness: f64,
+    anisotropy: f64,
+    distribution: Ggx,
+    /// Direction the microfacet distribution is stretched along.
+    tangent: Vec3<f64>,
+}
+
+impl Conductor {
+    /// Create a new metal from its complex index of refraction.
+    ///
+    /// * `eta` - Real part of the index of refraction per channel.
+    /// * `k` - Extinction coefficient per channel.
+    /// * `roughness` - Perceptual roughness in [0, 1], zero is a perfect mirror.
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Conductor {
+            eta,
+            k,
+            ior: None,
+            roughness,
+            anisotropy: 0.0,
+            distribution: Ggx::from_roughness(roughness, 0.0),
+            tangent: Vec3::new3(0.0, 1.0, 0.0),
+        }
+    }
+
+    /// Create a new metal from a preset, e.g. `ComplexIor::GOLD`.
+    ///
+    /// * `ior` - Complex index of refraction.
+    /// * `roughness` - Perceptual roughness in [0, 1], zero is a perfect mirror.
+    pub fn preset(ior: &ComplexIor, roughness: f64) -> Self {
+        let (eta, k) = ior.rgb();
+        Conductor {
+            ior: Some(*ior),
+            ..Conductor::new(eta, k, roughness)
+        }
+    }
+
+    /// Sets the anisotropy, see `Ggx::from_roughness`.
+    ///
+    /// * `anisotropy` - Anisotropy in [0, 1], zero is isotropic.
+    pub fn anisotropy(mut self, anisotropy: f64) -> Self {
+        self.anisotropy = anisotropy;
+        self.distribution = Ggx::from_roughness(self.roughness, anisotropy);
+        self
+    }
+
+    /// Sets the axis which gives the brushing direction of anisotropic metals.
+    ///
+    /// * `tangent` - Axis in world space, does not need to be normalized.
+    pub fn tangent(mut self, tangent: Vec3<f64>) -> Self {
+        self.tangent = tangent;
+        self
+    }
+
+    /// Returns the local frame at a hit point: normal along z, brushing direction along x.
+    fn frame(&self, rec: &HitRecord<f64>) -> Onb {
+        Onb::from_tangent(&rec.normal, &self.tangent)
+    }
+
+    /// Returns the Fresnel reflectance per channel, or per wavelength of a spectral path.
+    ///
+    /// * `cos_theta` - Cosine of the angle between the direction and the microfacet normal.
+    /// * `wavelengths` - Wavelengths of a spectral path, none for RGB.
+    fn fresnel(&self, cos_theta: f64, wavelengths: Option<&Wavelengths>) -> Color {
+        if let (Some(ior), Some(wavelengths)) = (&self.ior, wavelengths) {
+            return wavelengths.map(|lambda| {
+                let (eta, k) = ior.at(lambda);
+                microfacet::fresnel_conductor(cos_theta, eta, k)
+            });
+        }
+        Color::new3(
+            microfacet::fresnel_conductor(cos_theta, self.eta.x(), self.k.x()),
+            microfacet::fresnel_conductor(cos_theta, self.eta.y(), self.k.y()),
+            microfacet::fresnel_conductor(cos_theta, self.eta.z(), self.k.z()),
+        )
+    }
+}
+
+impl Material<f64> for Conductor {
+    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<ScatterRecord<f64>> {
+        let frame = self.frame(rec);
+        let wo = frame.project(&-ray.direction().normalized());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        // perfect mirror
+        if self.distribution.is_smooth() {
+            let wi = Vec3::new3(-wo.x(), -wo.y(), wo.z());
+            return Some(ScatterRecord {
+                ray: Ray::new(rec.point, frame.local(&wi)),
+                attenuation: self.fresnel(wo.z(), rec.wavelengths.as_ref()),
+                pdf: None,
+            });
+        }
+
+        let m = self.distribution.sample_visible(
+            &wo,
+            rtweekend::random(0.0..1.0),
+            rtweekend::random(0.0..1.0),
+        );
+        let wi = microfacet::reflect(&wo, &m);
+        // reflected below the surface: the light would hit another microfacet, which the
+        // single scattering model does not follow
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        let cos_om = Vec3::dot(&wo, &m);
+        let weight = self.distribution.g(&wo, &wi) / self.distribution.g1(&wo);
+        Some(ScatterRecord {
+            ray: Ray::new(rec.point, frame.local(&wi)),
+            attenuation: self.fresnel(cos_om, rec.wavelengths.as_ref()) * weight,
+            pdf: Some(self.distribution.pdf_visible(&wo, &m) / (4.0 * cos_om)),
+        })
+    }
+
+    fn eval(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> Color {
+        let black = Color::new3(0.0, 0.0, 0.0);
+        if self.distribution.is_smooth() {
+            return black;
+        }
+
+        let frame = self.frame(rec);
+        let wo = frame.project(&-ray.direction().normalized());
+        let wi = frame.project(direction);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return black;
+        }
+
+        // the cosine of the light direction cancels out
+        let m = (wo + wi).normalized();
+        let d = self.distribution.d(&m);
+        let g = self.distribution.g(&wo, &wi);
+        self.fresnel(Vec3::dot(&wo, &m), rec.wavelengths.as_ref()) * (d * g / (4.0 * wo.z()))
+    }
+
+    fn pdf(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> f64 {
+        if self.distribution.is_smooth() {
+            return 0.0;
+        }
+
+        let frame = self.frame(rec);
+        let wo = frame.project(&-ray.direction().normalized());
+        let wi = frame.project(direction);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+
+        // the reflection maps solid angles around m to four times as large ones around ωi
+        let m = (wo + wi).normalized();
+        self.distribution.pdf_visible(&wo, &m) / (4.0 * Vec3::dot(&wo, &m))
+    }
+
+    fn albedo(&self, _rec: &HitRecord<f64>) -> Color {
+        // reflectance at normal incidence, always in RGB
+        self.fresnel(1.0, None)
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.ior.is_some()
+    }
+}
@@ -0,0 +1,221 @@
+use rayon::prelude::*;
+
+use crate::color::{self, Color};
+use crate::ppm::Image;
+
+/// Feature images guiding the denoiser, as rendered by `AovFilm`.
+pub struct Features<'a> {
+    /// Albedo of the first surface hit.
+    pub albedo: &'a Image<Color>,
+    /// Shading normal of the first surface hit.
+    pub normal: &'a Image<Color>,
+    /// Distance of the first surface hit, zero where the rays escaped.
+    pub depth: &'a Image<Color>,
+    /// Variance of the pixel luminance.
+    pub variance: &'a Image<Color>,
+}
+
+/// Weights of the cubic B-spline kernel, applied in both directions.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Edge-avoiding à-trous wavelet denoiser, after Dammertz et al. and Schied et al. (SVGF).
+///
+/// The image is smoothed by a 5x5 B-spline kernel several times, doubling the distance between
+/// the taps each time ("with holes", hence à trous):
+///
+///     iteration 0:  x x x x x          iteration 1:  x . x . x . x . x
+///
+/// so five iterations cover 125x125 pixels with only 25 taps per pixel each. To keep the edges,
+/// every tap q of pixel p is additionally weighted by how similar the two pixels are:
+///
+///     w(p, q) = h(q - p) ⋅ w_luminance ⋅ w_normal ⋅ w_depth
+///
+///     w_luminance = exp(-|l_p - l_q| / (σ_l ⋅ sqrt(Var(l_p))))
+///     w_normal    = exp(-|n_p - n_q|² / σ_n²)
+///     w_depth     = exp(-|z_p - z_q| / (σ_z ⋅ z_p ⋅ |q - p|))
+///
+/// Noise only averages out where the features agree, i.e. on the same surface. The luminance
+/// difference is measured in standard deviations of the noise, so noisy pixels are smoothed a lot
+/// while converged ones, e.g. reflections of the sky, stay sharp. Every iteration reduces the
+/// variance along with the noise:
+///
+///     Var(l'_p) = Σ w(p, q)² Var(l_q) / (Σ w(p, q))²
+///
+/// Textures and colored surfaces are kept by filtering the illumination instead of the color:
+/// the image is divided by the albedo before and multiplied with it again afterwards.
+#[derive(Debug, Clone)]
+pub struct Denoiser {
+    iterations: usize,
+    sigma_luminance: f64,
+    sigma_normal: f64,
+    sigma_depth: f64,
+}
+
+impl Denoiser {
+    /// Create a denoiser with settings that suit most renders at 16 to 64 samples per pixel.
+    pub fn new() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_luminance: 2.0,
+            sigma_normal: 0.3,
+            sigma_depth: 0.1,
+        }
+    }
+
+    /// Sets the number of iterations, each one doubles the filter size.
+    ///
+    /// * `iterations` - Number of iterations, at least one.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Sets the luminance tolerance σ_l, larger values smooth more but blur shading details.
+    ///
+    /// * `sigma` - Tolerance in standard deviations of the noise.
+    pub fn luminance_sigma(mut self, sigma: f64) -> Self {
+        self.sigma_luminance = sigma.max(1e-6);
+        self
+    }
+
+    /// Remove the noise from a rendered image.
+    ///
+    /// Pixels with a single sample have no variance estimate and are left as they are.
+    ///
+    /// * `img` - Noisy image.
+    /// * `features` - Feature images of the same size.
+    pub fn denoise(&self, img: &Image<Color>, features: &Features) -> Image<Color> {
+        let (width, height) = (img.width(), img.height());
+
+        // filter the illumination, surfaces without albedo (e.g. lights and the background)
+        // keep their color
+        let albedo = |i: usize, j: usize| -> Color {
+            let a = features.albedo[j][i];
+            let channel = |value: f64| if value > 0.01 { value } else { 1.0 };
+            Color::new3(channel(a.x()), channel(a.y()), channel(a.z()))
+        };
+        let mut current = Image::new(width, height, (Color::new3(0.0, 0.0, 0.0), 0.0));
+        for j in 0..height {
+            for i in 0..width {
+                let (c, a) = (img[j][i], albedo(i, j));
+                let scale = color::luminance(&a);
+                current[j][i] = (
+                    Color::new3(c.x() / a.x(), c.y() / a.y(), c.z() / a.z()),
+                    features.variance[j][i].x() / (scale * scale),
+                );
+            }
+        }
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let rows: Vec<Vec<(Color, f64)>> = (0..height)
+                .into_par_iter()
+                .map(|j| {
+                    (0..width)
+                        .map(|i| self.filter_pixel(&current, features, i, j, step))
+                        .collect()
+                })
+                .collect();
+            for (j, row) in rows.into_iter().enumerate() {
+                current[j].copy_from_slice(&row);
+            }
+        }
+
+        let mut denoised = Image::new(width, height, Color::new3(0.0, 0.0, 0.0));
+        for j in 0..height {
+            for i in 0..width {
+                denoised[j][i] = current[j][i].0 * albedo(i, j);
+            }
+        }
+        denoised
+    }
+
+    /// Apply one iteration of the filter to a pixel.
+    ///
+    /// Returns the filtered illumination and its variance.
+    ///
+    /// * `img` - Illumination and variance after the previous iteration.
+    /// * `features` - Feature images.
+    /// * `i` - Column index.
+    /// * `j` - Row index.
+    /// * `step` - Distance between the taps in pixels.
+    fn filter_pixel(
+        &self,
+        img: &Image<(Color, f64)>,
+        features: &Features,
+        i: usize,
+        j: usize,
+        step: usize,
+    ) -> (Color, f64) {
+        let (width, height) = (img.width() as isize, img.height() as isize);
+        let luminance_p = color::luminance(&img[j][i].0);
+        let normal_p = features.normal[j][i];
+        let depth_p = features.depth[j][i].x();
+        // the variance estimate is noisy itself, a small blur makes it more reliable
+        let deviation = self.sigma_luminance * blurred_variance(img, i, j).sqrt() + 1e-9;
+
+        let mut sum = Color::new3(0.0, 0.0, 0.0);
+        let mut variance = 0.0;
+        let mut weight_sum = 0.0;
+        for (dy, ky) in KERNEL.iter().enumerate() {
+            let y = j as isize + (dy as isize - 2) * step as isize;
+            if y < 0 || y >= height {
+                continue;
+            }
+            for (dx, kx) in KERNEL.iter().enumerate() {
+                let x = i as isize + (dx as isize - 2) * step as isize;
+                if x < 0 || x >= width {
+                    continue;
+                }
+                let (x, y) = (x as usize, y as usize);
+
+                let (color_q, variance_q) = img[y][x];
+                let luminance_distance = (color::luminance(&color_q) - luminance_p).abs();
+                let normal_distance = (features.normal[y][x] - normal_p).length_squared();
+                // depth differences grow with the distance on slanted surfaces
+                let pixels = (((dx as isize - 2).pow(2) + (dy as isize - 2).pow(2)) as f64).sqrt()
+                    * step as f64;
+                let depth_distance = if pixels > 0.0 {
+                    (features.depth[y][x].x() - depth_p).abs()
+                        / (self.sigma_depth * depth_p.max(1e-6) * pixels)
+                } else {
+                    0.0
+                };
+
+                let weight = kx
+                    * ky
+                    * (-luminance_distance / deviation
+                        - normal_distance / (self.sigma_normal * self.sigma_normal)
+                        - depth_distance)
+                        .exp();
+                sum = sum + color_q * weight;
+                variance += weight * weight * variance_q;
+                weight_sum += weight;
+            }
+        }
+
+        // the center tap has a weight of (3/8)², so the sum is never zero
+        (sum / weight_sum, variance / (weight_sum * weight_sum))
+    }
+}
+
+/// Returns the variance of a pixel, blurred with a 3x3 Gaussian.
+///
+/// * `img` - Illumination and variance.
+/// * `i` - Column index.
+/// * `j` - Row index.
+fn blurred_variance(img: &Image<(Color, f64)>, i: usize, j: usize) -> f64 {
+    let kernel = [0.25, 0.5, 0.25];
+    let mut sum = 0.0;
+    let mut weight_sum = 0.0;
+    for (dy, ky) in kernel.iter().enumerate() {
+        for (dx, kx) in kernel.iter().enumerate() {
+            let (x, y) = ((i + dx).wrapping_sub(1), (j + dy).wrapping_sub(1));
+            if x < img.width() && y < img.height() {
+                sum += kx * ky * img[y][x].1;
+                weight_sum += kx * ky;
+            }
+        }
+    }
+    sum / weight_sum
+}
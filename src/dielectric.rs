@@ -0,0 +1,235 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::{self, Material, ScatterRecord};
+use crate::medium::Interior;
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend;
+use crate::vec::Vec3;
+
+/// Rough interface between two dielectrics, e.g. frosted glass, etched plastic or water ripples.
+///
+/// Like for `Conductor`, the surface is made of microfacets with GGX distributed normals, but
+/// every microfacet both reflects and refracts light, following Walter et al. ("Microfacet Models
+/// for Refraction through Rough Surfaces", 2007). The exact Fresnel term F decides between the
+/// two. Reflection works like for metals, the transmitted part is:
+///
+///     f(ωo, ωi) = |ωi⋅m|⋅|ωo⋅m| / (|cosθi|⋅cosθo)
+///               ⋅ (1 - F(ωo⋅m))⋅D(m)⋅G2(ωo, ωi) / (η²⋅(ωi⋅m + ωo⋅m / η)²)
+///
+/// where η is the index of refraction of the side ωi is on, relative to the side ωo is on, and
+/// the microfacet normal is the generalized half vector m ∝ ωo + η⋅ωi, flipped to face up. The
+/// η² is the change of radiance across the interface, see `Dielectric`.
+///
+/// Directions are sampled by picking a visible microfacet, then reflecting with probability F
+/// and refracting otherwise. Everything but the shadowing term cancels in the weight:
+///
+///     reflection:    f⋅|cosθi| / p = G2(ωo, ωi) / G1(ωo)
+///     transmission:  f⋅|cosθi| / p = G2(ωo, ωi) / (G1(ωo)⋅η²)
+///
+/// Beyond the critical angle, F is one and all light is reflected (total internal reflection).
+/// The index of refraction belongs to the inside of the object, i.e. the back face of its
+/// surface. Like for `Dielectric`, the inside may absorb light and the outside is air unless the
+/// object is nested inside another dielectric.
+pub struct RoughDielectric {
+    /// Index of refraction of the inside.
+    refraction: f64,
+    distribution: Ggx,
+    /// Absorption coefficient of the inside per channel.
+    absorption: Color,
+    /// Priority of the inside where objects overlap.
+    priority: u32,
+}
+
+impl RoughDielectric {
+    /// Create a new rough dielectric.
+    ///
+    /// * `refraction` - Index of refraction of the inside, e.g. 1.5 for glass or 1.33 for water.
+    /// * `roughness` - Perceptual roughness in [0, 1], zero is perfectly smooth.
+    pub fn new(refraction: f64, roughness: f64) -> Self {
+        RoughDielectric {
+            refraction,
+            distribution: Ggx::from_roughness(roughness, 0.0),
+            absorption: Color::new3(0.0, 0.0, 0.0),
+            priority: 0,
+        }
+    }
+
+    /// Sets the color of the inside, see `material::absorption_coefficient`.
+    ///
+    /// * `transmittance` - Color of white light after traveling the distance inside.
+    /// * `distance` - Distance in scene units.
+    pub fn absorption(mut self, transmittance: Color, distance: f64) -> Self {
+        self.absorption = material::absorption_coefficient(&transmittance, distance);
+        self
+    }
+
+    /// Sets the priority of the inside where it overlaps other dielectrics, see `MediumStack`.
+    ///
+    /// * `priority` - Priority, e.g. higher for a glass than for the liquid in it.
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Returns the local frame at a hit point and the relative index of refraction.
+    ///
+    /// The normal always faces the incoming ray, so the refracted side is below the surface.
+    fn frame(&self, rec: &HitRecord<f64>) -> (Onb, f64) {
+        let eta = if rec.front_face {
+            self.refraction / rec.exterior_refraction
+        } else {
+            rec.exterior_refraction / self.refraction
+        };
+        (Onb::new(&rec.normal), eta)
+    }
+
+    /// Scatter on a perfectly smooth interface, there are just two possible directions.
+    fn scatter_smooth(&self, wo: &Vec3<f64>, eta: f64) -> (Vec3<f64>, f64) {
+        let normal = Vec3::new3(0.0, 0.0, 1.0);
+        let reflectance = microfacet::fresnel_dielectric(wo.z(), eta);
+        match microfacet::refract(wo, &normal, eta) {
+            Some(wi) if rtweekend::random(0.0..1.0) >= reflectance => (wi, 1.0 / (eta * eta)),
+            _ => (Vec3::new3(-wo.x(), -wo.y(), wo.z()), 1.0),
+        }
+    }
+}
+
+impl Material<f64> for RoughDielectric {
+    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<ScatterRecord<f64>> {
+        let (frame, eta) = self.frame(rec);
+        let wo = frame.project(&-ray.direction().normalized());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.is_smooth() {
+            let (wi, weight) = self.scatter_smooth(&wo, eta);
+            return Some(ScatterRecord {
+                ray: Ray::new(rec.point, frame.local(&wi)),
+                attenuation: Color::new3(weight, weight, weight),
+                pdf: None,
+            });
+        }
+
+        let m = self.distribution.sample_visible(
+            &wo,
+            rtweekend::random(0.0..1.0),
+            rtweekend::random(0.0..1.0),
+        );
+        let cos_om = Vec3::dot(&wo, &m);
+        let reflectance = microfacet::fresnel_dielectric(cos_om, eta);
+        let pdf_m = self.distribution.pdf_visible(&wo, &m);
+        let g1 = self.distribution.g1(&wo);
+
+        let refracted = microfacet::refract(&wo, &m, eta);
+        let (wi, weight, pdf) = match refracted {
+            Some(wi) if rtweekend::random(0.0..1.0) >= reflectance => {
+                // refracted back above the surface: the light would hit another microfacet
+                if wi.z() >= 0.0 {
+                    return None;
+                }
+                // the refraction compresses solid angles around m by this factor
+                let denominator = Vec3::dot(&wi, &m) + cos_om / eta;
+                let jacobian = Vec3::dot(&wi, &m).abs() / (denominator * denominator);
+                let weight = self.distribution.g(&wo, &wi) / (g1 * eta * eta);
+                (wi, weight, (1.0 - reflectance) * pdf_m * jacobian)
+            }
+            _ => {
+                let wi = microfacet::reflect(&wo, &m);
+                if wi.z() <= 0.0 {
+                    return None;
+                }
+                let weight = self.distribution.g(&wo, &wi) / g1;
+                (wi, weight, reflectance * pdf_m / (4.0 * cos_om))
+            }
+        };
+
+        Some(ScatterRecord {
+            ray: Ray::new(rec.point, frame.local(&wi)),
+            attenuation: Color::new3(weight, weight, weight),
+            pdf: Some(pdf),
+        })
+    }
+
+    fn eval(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> Color {
+        let black = Color::new3(0.0, 0.0, 0.0);
+        if self.distribution.is_smooth() {
+            return black;
+        }
+
+        let (frame, eta) = self.frame(rec);
+        let wo = frame.project(&-ray.direction().normalized());
+        let wi = frame.project(direction);
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return black;
+        }
+
+        let value = if wi.z() > 0.0 {
+            let m = (wo + wi).normalized();
+            let cos_om = Vec3::dot(&wo, &m);
+            microfacet::fresnel_dielectric(cos_om, eta)
+                * self.distribution.d(&m)
+                * self.distribution.g(&wo, &wi)
+                / (4.0 * wo.z())
+        } else {
+            let m = match microfacet::transmission_normal(&wo, &wi, eta) {
+                Some(m) => m,
+                None => return black,
+            };
+            let (cos_om, cos_im) = (Vec3::dot(&wo, &m), Vec3::dot(&wi, &m));
+            let denominator = cos_im + cos_om / eta;
+            (1.0 - microfacet::fresnel_dielectric(cos_om, eta))
+                * self.distribution.d(&m)
+                * self.distribution.g(&wo, &wi)
+                * (cos_im.abs() * cos_om)
+                / (wo.z() * denominator * denominator * eta * eta)
+        };
+        Color::new3(value, value, value)
+    }
+
+    fn pdf(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> f64 {
+        if self.distribution.is_smooth() {
+            return 0.0;
+        }
+
+        let (frame, eta) = self.frame(rec);
+        let wo = frame.project(&-ray.direction().normalized());
+        let wi = frame.project(direction);
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+
+        if wi.z() > 0.0 {
+            let m = (wo + wi).normalized();
+            let cos_om = Vec3::dot(&wo, &m);
+            microfacet::fresnel_dielectric(cos_om, eta) * self.distribution.pdf_visible(&wo, &m)
+                / (4.0 * cos_om)
+        } else {
+            let m = match microfacet::transmission_normal(&wo, &wi, eta) {
+                Some(m) => m,
+                None => return 0.0,
+            };
+            let (cos_om, cos_im) = (Vec3::dot(&wo, &m), Vec3::dot(&wi, &m));
+            let denominator = cos_im + cos_om / eta;
+            (1.0 - microfacet::fresnel_dielectric(cos_om, eta))
+                * self.distribution.pdf_visible(&wo, &m)
+                * cos_im.abs()
+                / (denominator * denominator)
+        }
+    }
+
+    fn albedo(&self, _rec: &HitRecord<f64>) -> Color {
+        // all light is either reflected or transmitted
+        Color::new3(1.0, 1.0, 1.0)
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior {
+            refraction: self.refraction,
+            absorption: self.absorption,
+            priority: self.priority,
+        })
+    }
+}
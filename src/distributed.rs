@@ -0,0 +1,506 @@
+use std::collections::VecDeque;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rayon::prelude::*;
+
+use crate::checkpoint::Settings;
+use crate::film::Film;
+use crate::interrupt;
+use crate::render::Renderer;
+use crate::tile::Tile;
+
+/// Signature sent by the coordinator, the last two bytes are the protocol version.
+const MAGIC: &[u8; 8] = b"RTDIST01";
+
+/// Upper limit for the length of a scene argument, protects against garbage on the socket.
+const MAX_ARG_LEN: u64 = 1 << 20;
+
+/// Tiles waiting to be rendered, shared by the connections to the workers.
+struct Queue {
+    pending: Mutex<VecDeque<Tile>>,
+    // tiles which are pending or being rendered
+    remaining: AtomicUsize,
+}
+
+impl Queue {
+    /// Take the next tile.
+    ///
+    /// While other workers render the last tiles, we wait: they may disconnect and give their
+    /// tile back. Returns none once all tiles are done or an interruption is requested.
+    fn take(&self) -> Option<Tile> {
+        loop {
+            if self.remaining.load(Ordering::SeqCst) == 0 || interrupt::interrupted() {
+                return None;
+            }
+            if let Some(tile) = self.pending.lock().unwrap().pop_front() {
+                return Some(tile);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Give back a tile whose worker failed, it is handed out next.
+    fn put_back(&self, tile: Tile) {
+        self.pending.lock().unwrap().push_front(tile);
+    }
+
+    /// Record a tile as done.
+    fn finish(&self) {
+        self.remaining.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Whether all tiles are done.
+    fn is_empty(&self) -> bool {
+        self.remaining.load(Ordering::SeqCst) == 0
+    }
+}
+
+/// Hands out the tiles of an image to worker processes and merges their results.
+///
+/// The coordinator listens for workers, which connect over TCP and render tiles for it:
+///
+///     coordinator                              worker
+///          |  <--------------- connect -------------  |
+///          |  --- magic, scene arguments ---------->  |  the worker builds the scene
+///          |  <--------------- settings ------------  |  must match the coordinator's
+///          |  --- tile, samples, sample counts ---->  |
+///          |  <---- region, raw film contents ------  |  repeated for every tile
+///          |  --- empty tile ---------------------->  |  no tiles left, the worker exits
+///
+/// Scenes are built deterministically from the command line arguments and the seed, so the
+/// arguments are all it takes to serialize a scene. The samples of a tile are splatted onto a
+/// margin of pixels around it, so workers return the tile grown by that margin. A result is only
+/// merged once it has arrived completely: if a worker disconnects or times out, its tile goes
+/// back to the queue and is handed to the next free worker. Workers may join at any time.
+pub struct Coordinator<'a> {
+    /// Command line arguments describing the scene.
+    pub args: &'a [String],
+    /// Render settings, workers with different ones are rejected.
+    pub settings: &'a Settings,
+    /// Film to merge the results into.
+    pub film: &'a Film,
+    /// Total number of samples per pixel.
+    pub samples: usize,
+    /// Time a worker may take for a tile, unlimited if none.
+    pub timeout: Option<Duration>,
+}
+
+impl Coordinator<'_> {
+    /// Render the image with the workers connecting to a socket.
+    ///
+    /// Returns once all tiles are merged or an interruption is requested.
+    ///
+    /// * `listener` - Socket the workers connect to.
+    /// * `tiles` - Tiles covering the image, in the order they should be rendered.
+    /// * `done` - Called on the current thread for every merged tile.
+    pub fn run<D: FnMut(&Tile)>(
+        &self,
+        listener: TcpListener,
+        tiles: &[Tile],
+        mut done: D,
+    ) -> io::Result<()> {
+        // tiles which already have all samples, e.g. after resuming, are skipped
+        let pending: VecDeque<Tile> = tiles
+            .iter()
+            .filter(|tile| !complete(self.film, tile, self.samples))
+            .copied()
+            .collect();
+        let queue = Queue {
+            remaining: AtomicUsize::new(pending.len()),
+            pending: Mutex::new(pending),
+        };
+        let (sender, receiver) = mpsc::channel();
+
+        listener.set_nonblocking(true)?;
+        thread::scope(|s| {
+            while !queue.is_empty() && !interrupt::interrupted() {
+                match listener.accept() {
+                    Ok((stream, address)) => {
+                        let sender = sender.clone();
+                        let queue = &queue;
+                        s.spawn(move || {
+                            eprintln!("\n>> Worker {} connected", address);
+                            if let Err(err) = self.serve(stream, queue, &sender) {
+                                eprintln!("\n>> Worker {} lost: {}", address, err);
+                            }
+                        });
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                    Err(err) => return Err(err),
+                }
+
+                for tile in receiver.try_iter() {
+                    done(&tile);
+                }
+            }
+            Ok(())
+        })?;
+
+        // tiles merged while the last connections were shutting down
+        drop(sender);
+        for tile in receiver {
+            done(&tile);
+        }
+        Ok(())
+    }
+
+    /// Serve a worker until all tiles are done.
+    ///
+    /// * `stream` - Connection to the worker.
+    /// * `queue` - Tiles to render, a failed tile is put back.
+    /// * `done` - Receives every merged tile.
+    fn serve(&self, stream: TcpStream, queue: &Queue, done: &mpsc::Sender<Tile>) -> io::Result<()> {
+        // accepted sockets may inherit the non-blocking mode of the listener
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        writer.write_all(MAGIC)?;
+        write_u64(&mut writer, self.args.len() as u64)?;
+        for arg in self.args {
+            write_u64(&mut writer, arg.len() as u64)?;
+            writer.write_all(arg.as_bytes())?;
+        }
+        writer.flush()?;
+
+        // building the scene may take a while, so the timeout only applies to tiles
+        if let Some(name) = self.settings.compare(&mut reader)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("worker uses a different {}", name),
+            ));
+        }
+        reader.get_ref().set_read_timeout(self.timeout)?;
+
+        while let Some(tile) = queue.take() {
+            match self.render(&mut reader, &mut writer, &tile) {
+                Ok(()) => {
+                    queue.finish();
+                    // the receiver only goes away if the coordinator failed
+                    let _ = done.send(tile);
+                }
+                Err(err) => {
+                    queue.put_back(tile);
+                    return Err(err);
+                }
+            }
+        }
+
+        // an empty tile tells the worker that there is no more work
+        write_tile(&mut writer, &Tile::default())?;
+        write_u64(&mut writer, 0)?;
+        writer.flush()
+    }
+
+    /// Let the worker render a tile and merge the result into the film.
+    fn render<R: Read, W: Write>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+        tile: &Tile,
+    ) -> io::Result<()> {
+        // the worker continues with the sample counts of our film
+        write_tile(writer, tile)?;
+        write_u64(writer, self.samples as u64)?;
+        for j in tile.y..tile.y + tile.height {
+            for i in tile.x..tile.x + tile.width {
+                write_u64(writer, self.film.samples(i, j) as u64)?;
+            }
+        }
+        writer.flush()?;
+
+        let region = read_tile(reader)?;
+        let expected = tile.grow(
+            self.film.margin(),
+            self.settings.width,
+            self.settings.height,
+        );
+        if region != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "worker returned the wrong region",
+            ));
+        }
+
+        let mut bytes = vec![0u8; region.width * region.height * 40];
+        reader.read_exact(&mut bytes)?;
+        self.film.merge_region(&bytes, &region)
+    }
+}
+
+/// Returns whether all pixels of a tile have the given number of samples.
+fn complete(film: &Film, tile: &Tile, samples: usize) -> bool {
+    (tile.y..tile.y + tile.height)
+        .all(|j| (tile.x..tile.x + tile.width).all(|i| film.samples(i, j) >= samples))
+}
+
+/// Connection of a worker process to its coordinator.
+pub struct Worker {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    args: Vec<String>,
+}
+
+impl Worker {
+    /// Connect to a coordinator and receive the scene description.
+    ///
+    /// * `address` - Address the coordinator listens on.
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let writer = BufWriter::new(stream);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a render coordinator",
+            ));
+        }
+
+        let count = read_u64(&mut reader)?;
+        let mut args = Vec::new();
+        for _ in 0..count {
+            let len = read_u64(&mut reader)?;
+            if len > MAX_ARG_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "scene argument too long",
+                ));
+            }
+            let mut bytes = vec![0u8; len as usize];
+            reader.read_exact(&mut bytes)?;
+            let arg = String::from_utf8(bytes)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            args.push(arg);
+        }
+
+        Ok(Worker {
+            reader,
+            writer,
+            args,
+        })
+    }
+
+    /// Returns the command line arguments describing the scene.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Render tiles for the coordinator until it has no more work.
+    ///
+    /// The pixels of a tile are rendered in parallel. Partial tiles are never sent: if an
+    /// interruption is requested, the worker just hangs up and the coordinator hands the tile to
+    /// another worker.
+    ///
+    /// * `renderer` - Renderer for the scene received in `connect`.
+    /// * `settings` - Render settings, sent to the coordinator for comparison.
+    /// * `film` - Empty film of the image size, used for one tile at a time.
+    pub fn run(mut self, renderer: &Renderer, settings: &Settings, film: &Film) -> io::Result<()> {
+        settings.write(&mut self.writer)?;
+        self.writer.flush()?;
+
+        loop {
+            let tile = read_tile(&mut self.reader)?;
+            let samples = read_u64(&mut self.reader)? as usize;
+            if tile.width == 0 || tile.height == 0 {
+                return Ok(());
+            }
+            if tile.x + tile.width > renderer.width || tile.y + tile.height > renderer.height {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "tile outside of the image",
+                ));
+            }
+
+            for j in tile.y..tile.y + tile.height {
+                for i in tile.x..tile.x + tile.width {
+                    film.add_samples(i, j, read_u64(&mut self.reader)? as usize);
+                }
+            }
+            (tile.y..tile.y + tile.height)
+                .into_par_iter()
+                .for_each(|j| {
+                    for i in tile.x..tile.x + tile.width {
+                        renderer.render_pixel(film, i, j, samples);
+                    }
+                });
+            if renderer.stopped() {
+                return Ok(());
+            }
+
+            let region = tile.grow(film.margin(), renderer.width, renderer.height);
+            write_tile(&mut self.writer, &region)?;
+            film.save_region(&mut self.writer, &region)?;
+            self.writer.flush()?;
+            film.clear_region(&region);
+        }
+    }
+}
+
+/// Write a little endian 64 bit value.
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+/// Read a little endian 64 bit value.
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Write a tile as four 64 bit values.
+fn write_tile<W: Write>(writer: &mut W, tile: &Tile) -> io::Result<()> {
+    for value in &[tile.x, tile.y, tile.width, tile.height] {
+        write_u64(writer, *value as u64)?;
+    }
+    Ok(())
+}
+
+/// Read a tile written by `write_tile`.
+fn read_tile<R: Read>(reader: &mut R) -> io::Result<Tile> {
+    Ok(Tile {
+        x: read_u64(reader)? as usize,
+        y: read_u64(reader)? as usize,
+        width: read_u64(reader)? as usize,
+        height: read_u64(reader)? as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::color::Color;
+    use crate::film::{Filter, FilterKind};
+    use crate::material;
+    use crate::ray::Point3;
+    use crate::sphere::Sphere;
+    use crate::tile::{self, TileOrder};
+    use crate::vec::Vec3;
+    use crate::world::World;
+
+    const WIDTH: usize = 24;
+    const HEIGHT: usize = 16;
+    const SAMPLES: usize = 4;
+
+    /// A small scene with a diffuse sphere and a light, so paths use light sampling as well.
+    fn scene() -> (World<f64>, Camera) {
+        let mut world = World::new();
+        world.add(
+            Sphere::new(Point3::new3(0.0, -100.5, -1.0), 100.0),
+            material::Lambertian::new(Color::new3(0.5, 0.5, 0.5)),
+        );
+        world.add(
+            Sphere::new(Point3::new3(0.0, 0.0, -1.0), 0.5),
+            material::Lambertian::new(Color::new3(0.7, 0.3, 0.2)),
+        );
+        world.add(
+            Sphere::new(Point3::new3(1.0, 1.5, 0.0), 0.3),
+            material::DiffuseLight::new(Color::new3(4.0, 4.0, 4.0)),
+        );
+        let camera = Camera::new(3.0, 2.0).lookfrom(Vec3::new3(0.0, 0.5, 1.0));
+        (world, camera)
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            width: WIDTH,
+            height: HEIGHT,
+            max_depth: 5,
+            filter: Filter::new(FilterKind::Tent, 1.0),
+            seed: 7,
+            scene_hash: 42,
+        }
+    }
+
+    #[test]
+    fn workers_dropping_out_do_not_change_the_image() {
+        let (world, camera) = scene();
+        let settings = settings();
+        let renderer = Renderer {
+            world: &world,
+            camera: &camera,
+            width: WIDTH,
+            height: HEIGHT,
+            max_depth: settings.max_depth,
+            seed: settings.seed,
+            spectral: false,
+            cancel: None,
+            aovs: None,
+        };
+
+        let local = Film::new(WIDTH, HEIGHT, settings.filter);
+        for j in 0..HEIGHT {
+            for i in 0..WIDTH {
+                renderer.render_pixel(&local, i, j, SAMPLES);
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let args = vec!["test".to_string()];
+        let merged = Film::new(WIDTH, HEIGHT, settings.filter);
+        let coordinator = Coordinator {
+            args: &args,
+            settings: &settings,
+            film: &merged,
+            samples: SAMPLES,
+            timeout: Some(Duration::from_secs(30)),
+        };
+        let tiles = tile::tiles(WIDTH, HEIGHT, 8, TileOrder::Scanline);
+
+        let mut merged_tiles = 0;
+        thread::scope(|s| {
+            s.spawn(|| {
+                // this worker hangs up as soon as it was handed a tile
+                let mut quitter = Worker::connect(address).unwrap();
+                assert_eq!(quitter.args(), &args[..]);
+                settings.write(&mut quitter.writer).unwrap();
+                quitter.writer.flush().unwrap();
+                let tile = read_tile(&mut quitter.reader).unwrap();
+                assert!(tile.width > 0 && tile.height > 0);
+                drop(quitter);
+
+                let worker = Worker::connect(address).unwrap();
+                let film = Film::new(WIDTH, HEIGHT, settings.filter);
+                worker.run(&renderer, &settings, &film).unwrap();
+            });
+            coordinator
+                .run(listener, &tiles, |_| merged_tiles += 1)
+                .unwrap();
+        });
+
+        assert_eq!(merged_tiles, tiles.len());
+        assert!(local
+            .row(HEIGHT / 2)
+            .iter()
+            .any(|color| color.length() > 0.0));
+        for j in 0..HEIGHT {
+            for (i, (a, b)) in local.row(j).iter().zip(merged.row(j)).enumerate() {
+                assert_eq!(merged.samples(i, j), SAMPLES);
+                let difference = (*a - b).length();
+                assert!(
+                    difference < 1e-9,
+                    "pixel ({}, {}) differs by {}",
+                    i,
+                    j,
+                    difference
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+/// Piecewise constant 1D probability distribution.
+///
+/// The function values f(i) describe n equally sized buckets covering [0, 1). We sample the
+/// distribution by inverting its cumulative distribution function (CDF):
+///
+///     cdf(0) = 0
+///     cdf(i + 1) = cdf(i) + f(i) / n
+///
+/// After normalizing by the integral cdf(n), a uniform random number ξ is mapped to the bucket i
+/// with cdf(i) <= ξ < cdf(i + 1) and linearly interpolated inside that bucket. The density of the
+/// sampled value is then f(i) / integral.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// Create a new distribution from non-negative function values.
+    ///
+    /// * `func` - Function values of the buckets, at least one.
+    pub fn new(func: Vec<f64>) -> Self {
+        debug_assert!(!func.is_empty(), "distribution without buckets");
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f64;
+        }
+
+        let integral = cdf[n];
+        if integral > 0.0 {
+            for value in cdf.iter_mut() {
+                *value /= integral;
+            }
+        } else {
+            // all values are zero, fall back to a uniform distribution
+            for (i, value) in cdf.iter_mut().enumerate() {
+                *value = i as f64 / n as f64;
+            }
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    /// Returns the number of buckets.
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Returns the integral of the function over [0, 1).
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Sample a value in [0, 1).
+    ///
+    /// Returns the value, its probability density and the index of the bucket it falls into.
+    ///
+    /// * `xi` - Uniform random number in [0, 1).
+    pub fn sample(&self, xi: f64) -> (f64, f64, usize) {
+        // find the last CDF entry which is less than or equal to xi, this skips empty buckets
+        let index = self
+            .cdf
+            .partition_point(|&value| value <= xi)
+            .saturating_sub(1);
+        let index = index.min(self.count() - 1);
+
+        // linear interpolation inside the bucket
+        let mut offset = xi - self.cdf[index];
+        let width = self.cdf[index + 1] - self.cdf[index];
+        if width > 0.0 {
+            offset /= width;
+        }
+
+        let value = (index as f64 + offset) / self.count() as f64;
+        (value, self.pdf(index), index)
+    }
+
+    /// Returns the probability density of values in a given bucket.
+    ///
+    /// * `index` - Bucket index.
+    pub fn pdf(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[index].abs() / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Piecewise constant 2D probability distribution on [0, 1)².
+///
+/// The function values f(u, v) are given as rows (one per v). We split the joint density into a
+/// marginal density of picking a row and a conditional density of picking a column in that row:
+///
+///     p(u, v) = p(v) * p(u | v)
+///
+/// Each row is a 1D distribution of its own (the conditional), and the integrals of all rows form
+/// the marginal distribution.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Create a new distribution from a grid of non-negative function values.
+    ///
+    /// * `func` - Function values in row major order.
+    /// * `width` - Number of columns (samples in u).
+    /// * `height` - Number of rows (samples in v).
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        let conditional: Vec<Distribution1D> = (0..height)
+            .map(|v| Distribution1D::new(func[v * width..(v + 1) * width].to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Sample a point in [0, 1)².
+    ///
+    /// Returns the point (u, v) and its probability density.
+    ///
+    /// * `xi` - Two uniform random numbers in [0, 1).
+    pub fn sample(&self, xi: (f64, f64)) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(xi.1);
+        let (u, pdf_u, _) = self.conditional[row].sample(xi.0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    /// Returns the probability density of a given point.
+    ///
+    /// * `u` - Horizontal coordinate in [0, 1).
+    /// * `v` - Vertical coordinate in [0, 1).
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.marginal.count() as f64) as usize).min(self.marginal.count() - 1);
+        let conditional = &self.conditional[row];
+        let column = ((u * conditional.count() as f64) as usize).min(conditional.count() - 1);
+
+        if self.marginal.integral() <= 0.0 {
+            return 1.0;
+        }
+        conditional.func[column].abs() / self.marginal.integral()
+    }
+}
@@ -0,0 +1,146 @@
+use std::f64::consts::PI;
+
+use crate::color::{self, Color};
+use crate::distribution::Distribution2D;
+use crate::light::{Light, LightSample};
+use crate::ppm::Image;
+use crate::ray::Point3;
+use crate::rtweekend;
+use crate::vec::Vec3;
+
+/// Environment map in equirectangular (latitude/longitude) projection.
+///
+/// The image wraps around the whole scene like an infinitely large sphere. Each pixel covers an
+/// interval of the spherical coordinates:
+///
+///     u = φ / 2π, where φ is the angle around the y axis (starting at +x, turning towards +z)
+///     v = θ / π, where θ is the angle from the +y axis (so the top row is straight up)
+///
+/// The environment acts as a light source: bright regions such as the sun are found by importance
+/// sampling the pixels proportional to their luminance instead of waiting for randomly scattered
+/// rays to hit them.
+pub struct EnvironmentMap {
+    image: Image<Color>,
+    // rotation around the y axis in radians
+    rotation: f64,
+    // radiance scale
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    /// Create a new environment map from an equirectangular image.
+    ///
+    /// * `image` - Linear radiance values, the first row is the top of the sky.
+    pub fn new(image: Image<Color>) -> Self {
+        let width = image.width();
+        let height = image.height();
+
+        // The pixels do not cover the same solid angle: the rows near the poles are squeezed
+        // together. A pixel at angle θ covers a solid angle proportional to sinθ, so we weight the
+        // luminance accordingly.
+        let mut func = vec![0.0; width * height];
+        for j in 0..height {
+            let sin_theta = (PI * (j as f64 + 0.5) / height as f64).sin();
+            for i in 0..width {
+                func[j * width + i] = color::luminance(&image[j][i]).max(0.0) * sin_theta;
+            }
+        }
+        let distribution = Distribution2D::new(&func, width, height);
+
+        EnvironmentMap {
+            image,
+            rotation: 0.0,
+            intensity: 1.0,
+            distribution,
+        }
+    }
+
+    /// Rotates the environment around the vertical (y) axis.
+    ///
+    /// * `degrees` - Rotation angle.
+    pub fn rotation(mut self, degrees: f64) -> Self {
+        self.rotation = rtweekend::degrees_to_radians(degrees);
+        self
+    }
+
+    /// Scales the radiance of the environment.
+    ///
+    /// * `intensity` - Scale factor, 1.0 leaves the image values as they are.
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Returns the image coordinates in [0, 1)² for a unit direction.
+    fn direction_to_uv(&self, direction: &Vec3<f64>) -> (f64, f64) {
+        let theta = rtweekend::clamp(direction.y(), -1.0, 1.0).acos();
+        let phi = (direction.z().atan2(direction.x()) - self.rotation).rem_euclid(2.0 * PI);
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Returns the unit direction for image coordinates in [0, 1)².
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3<f64> {
+        let theta = v * PI;
+        let phi = u * 2.0 * PI + self.rotation;
+        Vec3::new3(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    }
+
+    /// Returns the radiance arriving from a given direction.
+    ///
+    /// * `direction` - Unit direction, pointing away from the scene.
+    pub fn radiance(&self, direction: &Vec3<f64>) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        // nearest neighbor lookup, which matches the piecewise constant sampling distribution
+        let i = ((u * self.image.width() as f64) as usize).min(self.image.width() - 1);
+        let j = ((v * self.image.height() as f64) as usize).min(self.image.height() - 1);
+        self.image[j][i] * self.intensity
+    }
+
+    /// Convert a density on the image plane into one with respect to solid angle.
+    ///
+    /// The mapping from (u, v) to directions stretches the unit square over 2π² (2π for φ and π
+    /// for θ) and every direction is scaled by sinθ (see above), thus:
+    ///
+    ///     p(ω) = p(u, v) / (2π²⋅sinθ)
+    fn uv_pdf_to_solid_angle(pdf: f64, v: f64) -> f64 {
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        pdf / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+impl Light for EnvironmentMap {
+    fn sample(&self, _point: &Point3<f64>) -> Option<LightSample> {
+        let xi = (rtweekend::random(0.0..1.0), rtweekend::random(0.0..1.0));
+        let ((u, v), pdf) = self.distribution.sample(xi);
+        let pdf = EnvironmentMap::uv_pdf_to_solid_angle(pdf, v);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let direction = self.uv_to_direction(u, v);
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.radiance(&direction),
+            pdf,
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, _point: &Point3<f64>, direction: &Vec3<f64>) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        EnvironmentMap::uv_pdf_to_solid_angle(self.distribution.pdf(u, v), v)
+    }
+
+    fn background(&self, direction: &Vec3<f64>) -> Color {
+        self.radiance(direction)
+    }
+}
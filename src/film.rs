@@ -0,0 +1,349 @@
+use std::convert::TryInto;
+use std::f64::consts::PI;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::color::Color;
+use crate::ppm::Image;
+use crate::tile::Tile;
+
+/// Shape of a pixel reconstruction filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    /// Constant weight, equivalent to averaging the samples inside the filter area.
+    Box,
+    /// Weight falling off linearly towards the radius (a pyramid in 2D).
+    Tent,
+    /// Gaussian bell, shifted down so it reaches zero at the radius.
+    Gaussian {
+        /// Falloff rate, larger values give a narrower bell.
+        alpha: f64,
+    },
+    /// Cubic filter by Mitchell and Netravali, a tunable trade-off between blurring and ringing.
+    Mitchell {
+        /// Blur parameter B.
+        b: f64,
+        /// Ringing parameter C.
+        c: f64,
+    },
+    /// Sinc windowed by a wider sinc, sharp but prone to ringing.
+    Lanczos,
+}
+
+/// Pixel reconstruction filter.
+///
+/// Every sample contributes to all pixels whose center lies within the filter radius, weighted by
+/// the filter function. The final pixel value is the weighted average:
+///
+///     I(x, y) = Σ f(x - xᵢ, y - yᵢ) L(xᵢ, yᵢ) / Σ f(x - xᵢ, y - yᵢ)
+///
+/// All filters are separable, f(x, y) = f(x) f(y).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    kind: FilterKind,
+    // radius in pixels, the same in both directions
+    radius: f64,
+}
+
+impl Filter {
+    /// Create a new filter.
+    ///
+    /// * `kind` - Filter function.
+    /// * `radius` - Filter radius in pixels, 0.5 covers exactly one pixel.
+    pub fn new(kind: FilterKind, radius: f64) -> Self {
+        Filter {
+            kind,
+            radius: radius.max(0.5),
+        }
+    }
+
+    /// Returns the filter function.
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    /// Returns the filter radius in pixels.
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Evaluate the filter at an offset from the pixel center (in pixels).
+    pub fn eval(&self, dx: f64, dy: f64) -> f64 {
+        self.eval_1d(dx) * self.eval_1d(dy)
+    }
+
+    /// Evaluate the one dimensional filter function.
+    fn eval_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        if x > self.radius {
+            return 0.0;
+        }
+
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => self.radius - x,
+            FilterKind::Gaussian { alpha } => {
+                (-alpha * x * x).exp() - (-alpha * self.radius * self.radius).exp()
+            }
+            FilterKind::Mitchell { b, c } => {
+                // the cubic is defined on [-2, 2], so scale it to the radius
+                let x = 2.0 * x / self.radius;
+                if x > 1.0 {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                }
+            }
+            FilterKind::Lanczos => sinc(x) * sinc(x / self.radius),
+        }
+    }
+}
+
+/// Normalized sinc function, sin(πx) / πx.
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+/// Weighted sum of the samples contributing to a pixel.
+#[derive(Debug, Clone, Copy)]
+struct FilmPixel {
+    sum: Color,
+    weight: f64,
+}
+
+/// Film which collects radiance samples and reconstructs the image with a filter.
+///
+/// Samples are splatted into all pixels covered by the filter, so neighboring pixels receive
+/// contributions from samples computed on different threads. Each row is guarded by its own lock,
+/// which keeps contention low while rayon works on many pixels in parallel.
+///
+/// Besides the weighted sums, the film counts the samples taken inside each pixel. Together with
+/// a deterministic random sequence per sample, the counts tell where to continue when rendering
+/// more samples later.
+pub struct Film {
+    width: usize,
+    height: usize,
+    filter: Filter,
+    rows: Vec<Mutex<Vec<FilmPixel>>>,
+    samples: Vec<AtomicUsize>,
+}
+
+impl Film {
+    /// Create a new, black film.
+    ///
+    /// * `width` - Width in pixels.
+    /// * `height` - Height in pixels.
+    /// * `filter` - Reconstruction filter.
+    pub fn new(width: usize, height: usize, filter: Filter) -> Self {
+        let pixel = FilmPixel {
+            sum: Color::new3(0.0, 0.0, 0.0),
+            weight: 0.0,
+        };
+        Film {
+            width,
+            height,
+            filter,
+            rows: (0..height)
+                .map(|_| Mutex::new(vec![pixel; width]))
+                .collect(),
+            samples: (0..width * height).map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+
+    /// Returns the number of samples taken inside a pixel.
+    ///
+    /// * `i` - Column index.
+    /// * `j` - Row index, zero is the bottom row.
+    pub fn samples(&self, i: usize, j: usize) -> usize {
+        self.samples[j * self.width + i].load(Ordering::Relaxed)
+    }
+
+    /// Records samples taken inside a pixel, after they have been added with `add_sample`.
+    ///
+    /// * `i` - Column index.
+    /// * `j` - Row index, zero is the bottom row.
+    /// * `count` - Number of new samples.
+    pub fn add_samples(&self, i: usize, j: usize, count: usize) {
+        self.samples[j * self.width + i].fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Add a sample to the film.
+    ///
+    /// * `x` - Horizontal sample position in pixels, pixel i covers [i, i + 1).
+    /// * `y` - Vertical sample position in pixels, row j covers [j, j + 1).
+    /// * `color` - Radiance carried by the sample.
+    pub fn add_sample(&self, x: f64, y: f64, color: Color) {
+        // range of pixels whose centers (i + 0.5, j + 0.5) are within the filter radius
+        let radius = self.filter.radius();
+        let x0 = (x - 0.5 - radius).ceil().max(0.0) as usize;
+        let x1 = ((x - 0.5 + radius).floor() as isize).min(self.width as isize - 1);
+        let y0 = (y - 0.5 - radius).ceil().max(0.0) as usize;
+        let y1 = ((y - 0.5 + radius).floor() as isize).min(self.height as isize - 1);
+        if x1 < 0 || y1 < 0 {
+            return;
+        }
+
+        for j in y0..=y1 as usize {
+            let dy = j as f64 + 0.5 - y;
+            let mut row = self.rows[j].lock().unwrap();
+            for (i, pixel) in row.iter_mut().enumerate().take(x1 as usize + 1).skip(x0) {
+                let weight = self.filter.eval(i as f64 + 0.5 - x, dy);
+                if weight != 0.0 {
+                    pixel.sum = pixel.sum + color * weight;
+                    pixel.weight += weight;
+                }
+            }
+        }
+    }
+
+    /// Returns the reconstructed colors of one row.
+    ///
+    /// * `j` - Row index, zero is the bottom row.
+    pub fn row(&self, j: usize) -> Vec<Color> {
+        self.rows[j]
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|pixel| {
+                if pixel.weight > 0.0 {
+                    pixel.sum / pixel.weight
+                } else {
+                    Color::new3(0.0, 0.0, 0.0)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the reconstructed image.
+    pub fn image(&self) -> Image<Color> {
+        let mut img = Image::new(self.width, self.height, Color::new3(0.0, 0.0, 0.0));
+        for j in 0..self.height {
+            img[j].copy_from_slice(&self.row(j));
+        }
+        img
+    }
+
+    /// Returns the number of pixels around a pixel which receive contributions from its samples.
+    pub fn margin(&self) -> usize {
+        // samples within [i, i + 1) reach the centers of pixels up to radius + 0.5 away
+        (self.filter.radius() + 0.5).ceil() as usize
+    }
+
+    /// Write the raw film contents (weighted sums, weights and sample counts).
+    ///
+    /// Each pixel is stored as five little endian 64 bit values, row by row starting at the
+    /// bottom. The film must not be rendered to at the same time.
+    pub fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.save_region(writer, &self.bounds())
+    }
+
+    /// Write the raw contents of a part of the film, in the format of `save`.
+    ///
+    /// * `writer` - Destination of the data.
+    /// * `region` - Pixels to write.
+    pub fn save_region<W: Write>(&self, writer: &mut W, region: &Tile) -> io::Result<()> {
+        for j in region.y..region.y + region.height {
+            let row = self.rows[j].lock().unwrap();
+            for i in region.x..region.x + region.width {
+                let pixel = &row[i];
+                for value in &[pixel.sum.x(), pixel.sum.y(), pixel.sum.z(), pixel.weight] {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                writer.write_all(&(self.samples(i, j) as u64).to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add raw contents written by `save_region` to a part of the film.
+    ///
+    /// Weighted sums and weights are added, which is the same as splatting the samples behind
+    /// them onto this film. Sample counts are taken as the maximum: the film which rendered the
+    /// samples started from the counts of this film, so its counts already include them.
+    ///
+    /// * `bytes` - Data written by `save_region`.
+    /// * `region` - Pixels the data belongs to.
+    pub fn merge_region(&self, bytes: &[u8], region: &Tile) -> io::Result<()> {
+        if bytes.len() != region.width * region.height * 40 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "film data does not match the region size",
+            ));
+        }
+
+        let mut values = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+        let mut next = || f64::from_bits(values.next().unwrap());
+        for j in region.y..region.y + region.height {
+            let mut row = self.rows[j].lock().unwrap();
+            for i in region.x..region.x + region.width {
+                let (r, g, b) = (next(), next(), next());
+                row[i].sum = row[i].sum + Color::new3(r, g, b);
+                row[i].weight += next();
+                let samples = next().to_bits() as usize;
+                self.samples[j * self.width + i].fetch_max(samples, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reset a part of the film to black, without any samples.
+    ///
+    /// * `region` - Pixels to reset.
+    pub fn clear_region(&self, region: &Tile) {
+        for j in region.y..region.y + region.height {
+            let mut row = self.rows[j].lock().unwrap();
+            for i in region.x..region.x + region.width {
+                row[i].sum = Color::new3(0.0, 0.0, 0.0);
+                row[i].weight = 0.0;
+                self.samples[j * self.width + i].store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns a tile covering the whole film.
+    fn bounds(&self) -> Tile {
+        Tile {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Restore the raw film contents written by `save`.
+    ///
+    /// The film must have the same size as the one that was saved.
+    pub fn load<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let mut bytes = [0u8; 8];
+        let mut read = |reader: &mut R| -> io::Result<u64> {
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+
+        for j in 0..self.height {
+            let row = self.rows[j].get_mut().unwrap();
+            for (i, pixel) in row.iter_mut().enumerate() {
+                let r = f64::from_bits(read(reader)?);
+                let g = f64::from_bits(read(reader)?);
+                let b = f64::from_bits(read(reader)?);
+                pixel.sum = Color::new3(r, g, b);
+                pixel.weight = f64::from_bits(read(reader)?);
+                *self.samples[j * self.width + i].get_mut() = read(reader)? as usize;
+            }
+        }
+        Ok(())
+    }
+}
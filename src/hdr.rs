@@ -0,0 +1,302 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use crate::color::Color;
+use crate::ppm::{check_dimensions, Image};
+
+/// Returns an error for malformed image files.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read a single header line (without the line break).
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(invalid_data("unexpected end of header"));
+    }
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// Load a high dynamic range image, the format is picked by the file extension.
+///
+/// Supported are Radiance RGBE (.hdr, .pic) and portable float maps (.pfm). The first row of the
+/// returned image is the top row of the picture.
+///
+/// * `path` - Path to the image file.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Image<Color>> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    let mut reader = BufReader::new(File::open(path)?);
+
+    match extension.as_deref() {
+        Some("hdr") | Some("pic") => read_rgbe(&mut reader),
+        Some("pfm") => read_pfm(&mut reader),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unsupported image format, expected .hdr or .pfm",
+        )),
+    }
+}
+
+/// Convert a RGBE pixel into a color.
+///
+/// RGBE stores three 8 bit mantissas that share one 8 bit exponent (biased by 128):
+///
+///     color = (r, g, b) * 2^(e - 128) / 256
+fn rgbe_to_color(rgbe: &[u8]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new3(0.0, 0.0, 0.0);
+    }
+
+    let scale = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    Color::new3(
+        (rgbe[0] as f64 + 0.5) * scale,
+        (rgbe[1] as f64 + 0.5) * scale,
+        (rgbe[2] as f64 + 0.5) * scale,
+    )
+}
+
+/// Read one RGBE scanline, either flat or new-style run length encoded.
+///
+/// In the run length encoded format, a scanline starts with the bytes (2, 2, hi, lo) where
+/// hi/lo encode the width. The four channels follow one after another, each one made up of runs:
+/// a count byte > 128 repeats the next byte (count - 128) times, otherwise count literal bytes
+/// follow.
+fn read_rgbe_scanline<R: Read>(reader: &mut R, width: usize) -> io::Result<Vec<u8>> {
+    let mut scanline = vec![0u8; width * 4];
+    let mut head = [0u8; 4];
+    reader.read_exact(&mut head)?;
+
+    let encoded = (8..0x8000).contains(&width)
+        && head[0] == 2
+        && head[1] == 2
+        && head[2] & 0x80 == 0
+        && ((head[2] as usize) << 8 | head[3] as usize) == width;
+    if !encoded {
+        // flat scanline: the first pixel has already been read
+        scanline[..4].copy_from_slice(&head);
+        reader.read_exact(&mut scanline[4..])?;
+        return Ok(scanline);
+    }
+
+    let mut channel = vec![0u8; width];
+    for c in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            let count = count[0] as usize;
+
+            if count > 128 {
+                let count = count - 128;
+                if x + count > width {
+                    return Err(invalid_data("bad RGBE run length"));
+                }
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                for byte in &mut channel[x..x + count] {
+                    *byte = value[0];
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid_data("bad RGBE run length"));
+                }
+                reader.read_exact(&mut channel[x..x + count])?;
+                x += count;
+            }
+        }
+
+        for x in 0..width {
+            scanline[x * 4 + c] = channel[x];
+        }
+    }
+
+    Ok(scanline)
+}
+
+/// Read a Radiance RGBE image.
+///
+/// Only the common orientation "-Y height +X width" (top to bottom, left to right) is supported.
+pub fn read_rgbe<R: BufRead>(reader: &mut R) -> io::Result<Image<Color>> {
+    let magic = read_line(reader)?;
+    if !magic.starts_with("#?") {
+        return Err(invalid_data("missing Radiance header"));
+    }
+
+    // header variables, terminated by an empty line
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data("unsupported Radiance pixel format"));
+            }
+        }
+    }
+
+    let resolution = read_line(reader)?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    if fields.len() != 4 || fields[0] != "-Y" || fields[2] != "+X" {
+        return Err(invalid_data("unsupported Radiance image orientation"));
+    }
+    let height: usize = fields[1]
+        .parse()
+        .map_err(|_| invalid_data("bad image height"))?;
+    let width: usize = fields[3]
+        .parse()
+        .map_err(|_| invalid_data("bad image width"))?;
+    check_dimensions(width, height)?;
+
+    let mut img = Image::new(width, height, Color::new3(0.0, 0.0, 0.0));
+    for j in 0..height {
+        let scanline = read_rgbe_scanline(reader, width)?;
+        for i in 0..width {
+            img[j][i] = rgbe_to_color(&scanline[i * 4..i * 4 + 4]);
+        }
+    }
+
+    Ok(img)
+}
+
+/// Read a portable float map (PFM) image.
+///
+/// The header consists of the type ("PF" for color, "Pf" for grayscale), the dimensions and a
+/// scale factor whose sign encodes the byte order (negative means little endian). The rows are
+/// stored bottom to top.
+pub fn read_pfm<R: BufRead>(reader: &mut R) -> io::Result<Image<Color>> {
+    let channels = match read_line(reader)?.trim() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid_data("missing PFM header")),
+    };
+
+    let dimensions = read_line(reader)?;
+    let fields: Vec<&str> = dimensions.split_whitespace().collect();
+    if fields.len() != 2 {
+        return Err(invalid_data("bad PFM dimensions"));
+    }
+    let width: usize = fields[0]
+        .parse()
+        .map_err(|_| invalid_data("bad image width"))?;
+    let height: usize = fields[1]
+        .parse()
+        .map_err(|_| invalid_data("bad image height"))?;
+    check_dimensions(width, height)?;
+
+    let scale: f64 = read_line(reader)?
+        .trim()
+        .parse()
+        .map_err(|_| invalid_data("bad PFM scale"))?;
+    let little_endian = scale < 0.0;
+
+    let mut data = vec![0u8; width * height * channels * 4];
+    reader.read_exact(&mut data)?;
+    let value = |index: usize| -> f64 {
+        let bytes = [
+            data[index * 4],
+            data[index * 4 + 1],
+            data[index * 4 + 2],
+            data[index * 4 + 3],
+        ];
+        if little_endian {
+            f32::from_le_bytes(bytes) as f64
+        } else {
+            f32::from_be_bytes(bytes) as f64
+        }
+    };
+
+    let mut img = Image::new(width, height, Color::new3(0.0, 0.0, 0.0));
+    for j in 0..height {
+        // flip the rows so the first one is the top row
+        let row = height - 1 - j;
+        for i in 0..width {
+            let index = (row * width + i) * channels;
+            img[j][i] = if channels == 3 {
+                Color::new3(value(index), value(index + 1), value(index + 2))
+            } else {
+                Color::new3(value(index), value(index), value(index))
+            };
+        }
+    }
+
+    Ok(img)
+}
+
+/// Write a portable float map (PFM) image in little endian byte order.
+///
+/// Unlike the 8 bit formats, this keeps the values as they are: no gamma correction and no
+/// clamping, so it also suits data like positions or depths.
+///
+/// * `writer` - Destination of the image data.
+/// * `img` - Image to write, the first row is the bottom row.
+/// * `channels` - 3 for color, 1 for grayscale (the red channel is written).
+pub fn write_pfm<W: Write>(writer: &mut W, img: &Image<Color>, channels: usize) -> io::Result<()> {
+    let channels = if channels == 1 { 1 } else { 3 };
+    writeln!(writer, "{}", if channels == 1 { "Pf" } else { "PF" })?;
+    writeln!(writer, "{} {}", img.width(), img.height())?;
+    writeln!(writer, "-1.0")?;
+    for j in 0..img.height() {
+        for color in &img[j] {
+            for value in &[color.x(), color.y(), color.z()][..channels] {
+                writer.write_all(&(*value as f32).to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn rejects_empty_images() {
+        let pfm = b"PF\n0 4\n-1.0\n";
+        let error = read_pfm(&mut Cursor::new(&pfm[..]))
+            .err()
+            .expect("malformed image accepted");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let rgbe = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 4 +X 0\n";
+        let error = read_rgbe(&mut Cursor::new(&rgbe[..]))
+            .err()
+            .expect("malformed image accepted");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_huge_images_before_allocating() {
+        let pfm = b"PF\n4611686018427387904 2\n-1.0\n";
+        let error = read_pfm(&mut Cursor::new(&pfm[..]))
+            .err()
+            .expect("malformed image accepted");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let rgbe = b"#?RADIANCE\n\n-Y 100000 +X 100000\n";
+        let error = read_rgbe(&mut Cursor::new(&rgbe[..]))
+            .err()
+            .expect("malformed image accepted");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reads_a_small_pfm() {
+        let mut pfm = b"PF\n1 1\n-1.0\n".to_vec();
+        for value in &[0.25f32, 0.5, 1.0] {
+            pfm.extend_from_slice(&value.to_le_bytes());
+        }
+        let img = read_pfm(&mut Cursor::new(pfm)).unwrap();
+        assert_eq!((img.width(), img.height()), (1, 1));
+        assert_eq!(img[0][0].y(), 0.5);
+    }
+}
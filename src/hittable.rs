@@ -1,6 +1,7 @@
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
 use crate::ray::{Point3, Ray};
+use crate::spectrum::Wavelengths;
 use crate::vec::Vec3;
 
 pub trait Hittable<T: Copy> {
@@ -10,6 +11,27 @@ pub trait Hittable<T: Copy> {
     /// * `t_min`: Minimum depth of the ray.
     /// * `t_max`: Maxmimum depth of the ray.
     fn is_hit(&self, ray: &Ray<T>, t_min: T, t_max: T) -> Option<HitRecord<T>>;
+
+    /// Sample a direction from a given point towards the object.
+    ///
+    /// Returns the unit direction and its probability density with respect to solid angle, or
+    /// none if the object does not support sampling (it cannot act as a light source then).
+    ///
+    /// * `origin` - Point from which the object is seen, e.g. a point on a diffuse surface.
+    fn sample(&self, _origin: &Point3<T>) -> Option<(Vec3<T>, f64)> {
+        None
+    }
+
+    /// Returns the probability density (with respect to solid angle) of sampling a direction.
+    ///
+    /// This is the density that `sample` would produce for the given direction, so it is zero for
+    /// all directions that miss the object.
+    ///
+    /// * `origin` - Point from which the object is seen.
+    /// * `direction` - Direction from the origin, does not need to be normalized.
+    fn pdf(&self, _origin: &Point3<T>, _direction: &Vec3<T>) -> f64 {
+        0.0
+    }
 }
 
 pub struct HitRecord<T: Copy> {
@@ -17,6 +39,12 @@ pub struct HitRecord<T: Copy> {
     pub normal: Vec3<T>,
     pub t: T,
     pub front_face: bool,
+    /// Index of refraction of the medium around the object, on the other side of the surface
+    /// than the object's own medium. Air unless the renderer finds the object nested inside
+    /// another dielectric, see `MediumStack`.
+    pub exterior_refraction: f64,
+    /// Wavelengths of the path when rendering spectrally, see `Wavelengths`.
+    pub wavelengths: Option<Wavelengths>,
 }
 
 impl<T: Copy> HitRecord<T>
@@ -57,6 +85,8 @@ where
             normal,
             t,
             front_face,
+            exterior_refraction: 1.0,
+            wavelengths: None,
         }
     }
 }
@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once the render should stop, e.g. because the user pressed Ctrl-C.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Signal handler, only does async-signal-safe work.
+#[cfg(unix)]
+extern "C" fn handle_signal(signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    // Restore the default action, so a second signal terminates the process right away if
+    // saving the partial result takes too long.
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+/// Install handlers for SIGINT (Ctrl-C) and SIGTERM which request an interruption.
+///
+/// On other platforms, the signals keep their default behavior.
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Request an interruption, e.g. because the preview window was closed.
+#[cfg(feature = "minifb")]
+pub fn request() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether an interruption was requested.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,280 @@
+use std::f64::consts::PI;
+
+use crate::color::Color;
+use crate::onb::Onb;
+use crate::ray::Point3;
+use crate::rtweekend;
+use crate::vec::Vec3;
+
+/// A sample of incident light at some point in the scene.
+///
+/// Produced by the world when sampling its light sources for next-event estimation.
+pub struct LightSample {
+    /// Unit direction from the shaded point towards the light.
+    pub direction: Vec3<f64>,
+    /// Distance to the sampled point on the light.
+    pub distance: f64,
+    /// Radiance arriving from the light, not yet considering occlusion.
+    pub radiance: Color,
+    /// Probability density of the sample with respect to solid angle.
+    ///
+    /// For delta lights, this is the discrete probability of picking the light instead.
+    pub pdf: f64,
+    /// Whether the light is described by a delta distribution (e.g. an infinitely small point).
+    ///
+    /// Such lights can only be found by light sampling, never by randomly scattered rays.
+    pub delta: bool,
+}
+
+/// Light sources which are not part of the scene geometry.
+///
+/// Emissive objects act as lights too, but are sampled through the `Hittable` trait instead.
+pub trait Light {
+    /// Sample the incident light at a given point.
+    ///
+    /// Occlusion is not tested, so the caller has to trace a shadow ray.
+    ///
+    /// * `point` - Point which receives the light.
+    fn sample(&self, point: &Point3<f64>) -> Option<LightSample>;
+
+    /// Returns the probability density with which `sample` picks a given direction.
+    ///
+    /// Delta lights cannot be hit by chance, so the default is zero.
+    ///
+    /// * `point` - Point which receives the light.
+    /// * `direction` - Unit direction from the point.
+    fn pdf(&self, _point: &Point3<f64>, _direction: &Vec3<f64>) -> f64 {
+        0.0
+    }
+
+    /// Returns the radiance seen by a ray which leaves the scene into a given direction.
+    ///
+    /// Only infinitely distant lights with a non-zero extent contribute here.
+    ///
+    /// * `direction` - Unit direction of the ray.
+    fn background(&self, _direction: &Vec3<f64>) -> Color {
+        Color::new3(0.0, 0.0, 0.0)
+    }
+}
+
+/// Point light.
+///
+/// An infinitely small light bulb which emits light uniformly into all directions. The received
+/// light falls off with the inverse square of the distance:
+///
+///     E = I / d²
+///
+/// where I is the radiant intensity and d the distance to the light.
+pub struct PointLight {
+    position: Point3<f64>,
+    intensity: Color,
+}
+
+impl PointLight {
+    /// Create a new point light.
+    ///
+    /// * `position` - Position in 3D space.
+    /// * `intensity` - Radiant intensity, i.e. the received light at a distance of 1.0.
+    pub fn new(position: Point3<f64>, intensity: Color) -> Self {
+        PointLight {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Point3<f64>) -> Option<LightSample> {
+        let direction = self.position - *point;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction: direction.normalized(),
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity / distance_squared,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+}
+
+/// Spot light.
+///
+/// A point light which only emits into a cone around its direction:
+///
+///           P
+///          /|\
+///         / | \
+///        /  |  \
+///       /   |   \
+///      /    v    \
+///
+/// Inside the inner cone (falloff start), the full intensity is emitted. Between the inner and
+/// the outer cone, the intensity smoothly falls off to zero. Outside, nothing is emitted.
+pub struct SpotLight {
+    position: Point3<f64>,
+    direction: Vec3<f64>,
+    intensity: Color,
+    // cosine of the outer half angle
+    cos_total: f64,
+    // cosine of the inner half angle
+    cos_falloff: f64,
+}
+
+impl SpotLight {
+    /// Create a new spot light.
+    ///
+    /// * `position` - Position in 3D space.
+    /// * `target` - Point the spot light is aimed at.
+    /// * `intensity` - Radiant intensity along the center of the cone.
+    /// * `total_angle` - Full opening angle of the outer cone in degrees.
+    /// * `falloff_angle` - Full opening angle of the inner cone (no falloff) in degrees.
+    pub fn new(
+        position: Point3<f64>,
+        target: Point3<f64>,
+        intensity: Color,
+        total_angle: f64,
+        falloff_angle: f64,
+    ) -> Self {
+        let total = rtweekend::degrees_to_radians(total_angle / 2.0);
+        let falloff = rtweekend::degrees_to_radians(falloff_angle.min(total_angle) / 2.0);
+
+        SpotLight {
+            position,
+            direction: (target - position).normalized(),
+            intensity,
+            cos_total: total.cos(),
+            cos_falloff: falloff.cos(),
+        }
+    }
+
+    /// Returns the intensity scale for light leaving the spot in a given direction.
+    ///
+    /// We use the smoothstep function on the cosine of the angle to the spot direction:
+    ///
+    ///     s(x) = 3x² - 2x³ with x = (cosθ - cosθtotal) / (cosθfalloff - cosθtotal)
+    ///
+    /// * `direction` - Unit direction from the light.
+    fn falloff(&self, direction: &Vec3<f64>) -> f64 {
+        let cos_theta = Vec3::dot(&self.direction, direction);
+        if cos_theta >= self.cos_falloff {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_total {
+            return 0.0;
+        }
+
+        let x = (cos_theta - self.cos_total) / (self.cos_falloff - self.cos_total);
+        x * x * (3.0 - 2.0 * x)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Point3<f64>) -> Option<LightSample> {
+        let direction = self.position - *point;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+
+        let direction = direction.normalized();
+        let scale = self.falloff(&(-direction));
+        if scale <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance: distance_squared.sqrt(),
+            radiance: self.intensity * (scale / distance_squared),
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+}
+
+/// Directional (sun) light.
+///
+/// An infinitely distant light, so all its light arrives from the same direction everywhere in
+/// the scene. Optionally, the light covers a small disk in the sky (the sun has an angular radius
+/// of about 0.27°), which softens the shadows. In that case the light is no longer a delta light:
+/// it is visible to rays leaving the scene and directions are sampled uniformly inside the cone
+/// it covers.
+pub struct DirectionalLight {
+    direction: Vec3<f64>,
+    irradiance: Color,
+    // cosine of the angular radius, 1.0 for a delta light
+    cos_theta_max: f64,
+}
+
+impl DirectionalLight {
+    /// Create a new directional light.
+    ///
+    /// * `direction` - Direction pointing towards the light (not the direction light travels).
+    /// * `irradiance` - Light received by a surface facing the light.
+    /// * `angular_radius` - Angular radius of the light disk in degrees, 0.0 for a delta light.
+    pub fn new(direction: Vec3<f64>, irradiance: Color, angular_radius: f64) -> Self {
+        let angular_radius = rtweekend::clamp(angular_radius, 0.0, 90.0);
+
+        DirectionalLight {
+            direction: direction.normalized(),
+            irradiance,
+            cos_theta_max: rtweekend::degrees_to_radians(angular_radius).cos(),
+        }
+    }
+
+    /// Returns the solid angle covered by the light disk.
+    fn solid_angle(&self) -> f64 {
+        2.0 * PI * (1.0 - self.cos_theta_max)
+    }
+
+    /// Whether the light has a finite extent (it is not a delta light).
+    fn is_disk(&self) -> bool {
+        self.solid_angle() > 0.0
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Point3<f64>) -> Option<LightSample> {
+        if !self.is_disk() {
+            return Some(LightSample {
+                direction: self.direction,
+                distance: f64::INFINITY,
+                radiance: self.irradiance,
+                pdf: 1.0,
+                delta: true,
+            });
+        }
+
+        // The irradiance is spread over the disk, so each direction inside it carries the
+        // radiance E / Ω.
+        let onb = Onb::new(&self.direction);
+        let direction = onb.local(&rtweekend::random_vec_in_cone(self.cos_theta_max));
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.irradiance / self.solid_angle(),
+            pdf: 1.0 / self.solid_angle(),
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, _point: &Point3<f64>, direction: &Vec3<f64>) -> f64 {
+        if self.is_disk() && Vec3::dot(&self.direction, direction) >= self.cos_theta_max {
+            1.0 / self.solid_angle()
+        } else {
+            0.0
+        }
+    }
+
+    fn background(&self, direction: &Vec3<f64>) -> Color {
+        if self.is_disk() && Vec3::dot(&self.direction, direction) >= self.cos_theta_max {
+            self.irradiance / self.solid_angle()
+        } else {
+            Color::new3(0.0, 0.0, 0.0)
+        }
+    }
+}
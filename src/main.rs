@@ -1,263 +1,617 @@
-use std::io;
-use std::io::Write;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
 
 use rayon::prelude::*;
 
-use minifb::{Window, WindowOptions};
+mod cli;
 
 mod ppm;
-use ppm::Image;
+
+mod png;
 
 mod vec;
-use vec::Vec3;
 
 mod ray;
-use ray::{Point3, Ray};
 
 mod hittable;
 
 mod sphere;
-use sphere::Sphere;
 
 mod rtweekend;
 
+mod aperture;
+
 mod camera;
-use camera::Camera;
 
 mod color;
-use color::Color;
 
 mod world;
-use world::World;
 
 mod material;
 
-/// Post processing to transform RGB channels into PPM RGB color values.
-///
-/// We perform two steps:
-///     1. Gamma correction using gamma=2
-///     2. Color value mapping from [0.0, 1.0] to [0, 255]
-fn color_to_rgb8(color: &Color) -> [u8; 3] {
-    let mut r = color.x();
-    let mut g = color.y();
-    let mut b = color.z();
-
-    // gamma correction: raise color to the power of 1/gamma
-    // here: use gamma=2 as first approximation
-    r = r.sqrt();
-    g = g.sqrt();
-    b = b.sqrt();
-
-    // clamp to [0.0, 1.0] range
-    r = rtweekend::clamp(r, 0.0, 0.999);
-    g = rtweekend::clamp(g, 0.0, 0.999);
-    b = rtweekend::clamp(b, 0.0, 0.999);
-
-    // map to [0, 255] range
-    r = 256.0 * r;
-    g = 256.0 * g;
-    b = 256.0 * b;
-
-    [r as u8, g as u8, b as u8]
-}
+mod microfacet;
 
-/// Compute the color of pixel hit by a ray.
-fn ray_color(ray: &Ray<f64>, world: &World<f64>, depth: usize) -> Color {
-    if depth == 0 {
-        // ray bounce limit exceeded, no more light is reflected
-        return Color::new3(0.0, 0.0, 0.0);
-    }
+mod conductor;
 
-    // Fix shadow acne: due to floating point approximation, some of the reflected rays hit the
-    // object they are reflecting off of not at exactly t = 0, but e.g. t = -0.000001 or
-    // t = 0.000001. Ignore hits near zero to work around this.
-    let t_min = 0.001;
-    let t_max = std::f64::MAX;
-
-    if let Some((rec, material)) = world.trace(ray, t_min, t_max) {
-        // DEBUG: surface normal shading
-        //return Color::new3(
-        //    rec.normal.x() + 1.0,
-        //    rec.normal.y() + 1.0,
-        //    rec.normal.z() + 1.0,
-        //) * 0.5;
-
-        // scatter the light ray
-        if let Some((scatter, attenuation)) = material.scatter(ray, &rec) {
-            let mut scatter_color = ray_color(&scatter, world, depth - 1);
-            // consider attenuation of the object
-            scatter_color[0] = scatter_color[0] * attenuation[0];
-            scatter_color[1] = scatter_color[1] * attenuation[1];
-            scatter_color[2] = scatter_color[2] * attenuation[2];
-            return scatter_color;
-        } else {
-            // no light is reflected
-            return Color::new3(0.0, 0.0, 0.0);
-        }
-    }
+mod dielectric;
 
-    // scale the ray direction to unit length (so -1.0 < y < 1.0)
-    let unit_direction = ray.direction().normalized();
-    // scale t so 0.0 <= t <= 1.0
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    // linear blend aka interpolation between white and blue
-    let white = Color::new3(1.0, 1.0, 1.0);
-    let blue = Color::new3(0.5, 0.7, 1.0);
-    white * (1.0 - t) + blue * t
-}
+mod medium;
 
-/// Setup a random scene.
-fn random_scene() -> World<f64> {
-    let mut world = World::new();
-
-    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
-    let sphere_ground_mat = material::Lambertian::new(Color::new3(0.5, 0.5, 0.5));
-    world.add(sphere_ground, sphere_ground_mat);
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let random = rtweekend::random(0.0..1.0);
-            let center = Point3::new3(a as f64 + 0.9 * random, 0.2, b as f64 + 0.9 * random);
-
-            if (center - Point3::new3(4.0, 0.2, 0.0)).length() > 0.9 {
-                if random < 0.8 {
-                    // diffuse
-                    let albedo = Color::new3(
-                        rtweekend::random(0.0..1.0) * rtweekend::random(0.0..1.0),
-                        rtweekend::random(0.0..1.0) * rtweekend::random(0.0..1.0),
-                        rtweekend::random(0.0..1.0) * rtweekend::random(0.0..1.0),
-                    );
-                    let material = material::Lambertian::new(albedo);
-                    let sphere = Sphere::new(center, 0.2);
-                    world.add(sphere, material);
-                } else if random < 0.95 {
-                    // metal
-                    let albedo = Color::new3(
-                        rtweekend::random(0.5..1.0),
-                        rtweekend::random(0.5..1.0),
-                        rtweekend::random(0.5..1.0),
-                    );
-                    let fuzz = rtweekend::random(0.0..0.5);
-                    let material = material::Metal::new(albedo, fuzz);
-                    let sphere = Sphere::new(center, 0.2);
-                    world.add(sphere, material);
-                } else {
-                    // glass
-                    let material = material::Dielectric::new(1.5);
-                    let sphere = Sphere::new(center, 0.2);
-                    world.add(sphere, material);
-                }
-            }
-        }
-    }
+mod spectrum;
 
-    let material = material::Dielectric::new(1.5);
-    let sphere = Sphere::new(Point3::new3(0.0, 1.0, 0.0), 1.0);
-    world.add(sphere, material);
+mod texture;
 
-    let material = material::Lambertian::new(Color::new3(0.4, 0.2, 0.1));
-    let sphere = Sphere::new(Point3::new3(-4.0, 1.0, 0.0), 1.0);
-    world.add(sphere, material);
+mod principled;
 
-    let material = material::Metal::new(Color::new3(0.7, 0.6, 0.5), 0.0);
-    let sphere = Sphere::new(Point3::new3(4.0, 1.0, 0.0), 1.0);
-    world.add(sphere, material);
+mod onb;
 
-    world
-}
+mod quad;
+
+mod distribution;
+
+mod hdr;
+
+mod film;
+use film::Film;
+
+mod aov;
+use aov::{Aov, AovFilm};
+
+mod denoise;
+use denoise::{Denoiser, Features};
+
+mod checkpoint;
+
+mod interrupt;
+
+mod render;
+use render::Renderer;
+
+mod preview;
+use preview::Preview;
+
+#[cfg(feature = "minifb")]
+mod navigation;
+
+mod transform;
+
+mod animation;
+
+mod distributed;
+use distributed::{Coordinator, Worker};
+
+mod scene;
+use scene::Scene;
+
+mod server;
+use server::{Limits, Server};
+
+mod tile;
+use tile::TileOrder;
+
+mod environment;
+
+mod sky;
+
+mod light;
+
+/// Exit status after an interruption (signal or closed window), once the partial image and a
+/// checkpoint have been written.
+const EXIT_INTERRUPTED: i32 = 3;
 
 fn main() -> io::Result<()> {
-    // Image settings
-    const ASPECT_RATIO: f64 = 3.0 / 2.0;
-    const IMAGE_WIDTH: usize = 1200;
-    const IMAGE_HEIGHT: usize = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as usize;
-    const RAY_SAMPLES_PER_PIXEL: usize = 500;
-    const RAY_MAX_DEPTH: usize = 50;
-    eprintln!(">> Image: {} (W) x {} (H)", IMAGE_WIDTH, IMAGE_HEIGHT);
-
-    // minifb setup
-    #[cfg(feature = "minifb")]
-    let mut window =
-        Window::new("Scene", IMAGE_WIDTH, IMAGE_HEIGHT, WindowOptions::default()).unwrap();
-    #[cfg(feature = "minifb")]
-    let mut buffer = vec![0u32; IMAGE_WIDTH * IMAGE_HEIGHT];
+    let args = cli::Args::parse();
+    interrupt::install();
+
+    // Worker: --worker=<address> connects to a coordinator, which sends the arguments describing
+    // the scene
+    let (args, worker) = match args.value::<String>("worker") {
+        Some(address) => {
+            let worker = Worker::connect(address)?;
+            (cli::Args::from(worker.args().iter().cloned()), Some(worker))
+        }
+        None => (args, None),
+    };
+
+    // Tiles: --tile-size=<pixels> and --tile-order=<scanline|morton|hilbert|spiral>
+    let tile_size = args.value("tile-size").unwrap_or(32);
+    let tile_order = match args.value::<String>("tile-order").as_deref() {
+        Some("scanline") => TileOrder::Scanline,
+        Some("morton") => TileOrder::Morton,
+        Some("hilbert") => TileOrder::Hilbert,
+        _ => TileOrder::Spiral,
+    };
+    let pass_samples = args.value("pass-samples").unwrap_or(16);
+
+    // HTTP service: --serve[=<address>] renders the scenes submitted to it, see Server for the
+    // API. --queue=<n> limits the number of jobs waiting to be rendered, --max-width=<pixels>,
+    // --max-height=<pixels>, --max-samples=<n> and --max-depth=<n> the scenes it accepts.
+    if args.flag("serve") {
+        let address = args
+            .value::<String>("serve")
+            .unwrap_or_else(|| "127.0.0.1:8080".to_string());
+        let listener = TcpListener::bind(address)?;
+        eprintln!(">> Serving on http://{}", listener.local_addr()?);
+        let defaults = Limits::default();
+        let limits = Limits {
+            width: args.value("max-width").unwrap_or(defaults.width),
+            height: args.value("max-height").unwrap_or(defaults.height),
+            samples: args.value("max-samples").unwrap_or(defaults.samples),
+            max_depth: args.value("max-depth").unwrap_or(defaults.max_depth),
+        };
+        let server = Server::new(
+            args.value("queue").unwrap_or(8),
+            pass_samples,
+            tile_size,
+            tile_order,
+        )
+        .limits(limits);
+        return server.run(listener);
+    }
 
-    // Camera settings
-    const VIEWPORT_HEIGHT: f64 = 2.0;
-    const VIEWPORT_WIDTH: f64 = ASPECT_RATIO * VIEWPORT_HEIGHT;
+    // Scene selection: the first argument names the scene, options adjust the image and camera
+    let scene = Scene::new(&args)?;
+    let (width, height) = (scene.width, scene.height);
+    eprintln!(">> Image: {} (W) x {} (H)", width, height);
     eprintln!(
         ">> Viewport: {} (W) x {} (H)",
-        VIEWPORT_WIDTH, VIEWPORT_HEIGHT
+        scene.viewport_width,
+        scene.viewport_height()
     );
-    let camera = Camera::new(VIEWPORT_WIDTH, VIEWPORT_HEIGHT)
-        .lookfrom(Vec3::new3(13.0, 2.0, 3.0))
-        .lookat(Vec3::new3(0.0, 0.0, 0.0))
-        .up(Vec3::new3(0.0, 1.0, 0.0))
-        .vfov(20.0)
-        .lens(0.1, 10.0);
-
-    // World
-    let world = random_scene();
-
-    // create the image buffer
-    let mut img = Image::new(IMAGE_WIDTH, IMAGE_HEIGHT, Color::new3(0.0, 0.0, 0.0));
-
-    // fill image with test data
-    for j in (0..img.height()).rev() {
-        eprint!("\r>> Scanlines remaining: {:width$}", j, width = 5);
-        io::stdout().flush()?;
-
-        let scanline: Vec<Color> = (0..img.width())
-            .into_par_iter()
-            .map(|i| {
-                let mut color = Color::new3(0.0, 0.0, 0.0);
-
-                // For each pixel, we send RAY_SAMPLES_PER_PIXEL number of rays and essentially average
-                // their color values to get a final pixel color.
-                for _ in 0..RAY_SAMPLES_PER_PIXEL {
-                    let u = (i as f64 + rtweekend::random(0.0..1.0)) / ((img.width() - 1) as f64);
-                    let v = (j as f64 + rtweekend::random(0.0..1.0)) / ((img.height() - 1) as f64);
-                    let ray = camera.ray(u, v);
-                    color = color + ray_color(&ray, &world, RAY_MAX_DEPTH);
-                }
+    eprintln!(">> Scene: {}", scene.name);
+    eprintln!(
+        ">> Filter: {} ({} px)",
+        scene.filter_name,
+        scene.filter.radius()
+    );
+
+    let tiles = tile::tiles(width, height, tile_size, tile_order);
+
+    // AOVs: --aovs[=<list>] renders feature images like albedo, normal or depth alongside the
+    // image, see Aov for the names. Without a list, all of them are rendered.
+    let aovs = if args.flag("aovs") {
+        let list = args
+            .value::<String>("aovs")
+            .unwrap_or_else(|| "all".to_string());
+        Aov::parse_list(&list)?
+    } else {
+        Vec::new()
+    };
+
+    // Denoising: --denoise[=<iterations>] filters the image, guided by the albedo, normal, depth
+    // and variance AOVs. --denoise-sigma=<tolerance> trades smoothness for shading details.
+    let denoiser = if args.flag("denoise") {
+        let mut denoiser = Denoiser::new();
+        if let Some(iterations) = args.value("denoise") {
+            denoiser = denoiser.iterations(iterations);
+        }
+        if let Some(sigma) = args.value("denoise-sigma") {
+            denoiser = denoiser.luminance_sigma(sigma);
+        }
+        Some(denoiser)
+    } else {
+        None
+    };
+    if (!aovs.is_empty() || denoiser.is_some()) && (args.flag("frames") || args.flag("coordinator"))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--aovs and --denoise are only supported for single images rendered locally",
+        ));
+    }
+
+    // Benchmark: --benchmark[=<samples per pixel>] compares rendering scanline by scanline (the
+    // pixels of a row in parallel, one row after another) with the tiled renderer
+    if args.flag("benchmark") {
+        let samples = args.value("benchmark").unwrap_or(4);
+        let (world, camera) = scene.build(scene.time)?;
+        let renderer = Renderer {
+            world: &world,
+            camera: &camera,
+            width,
+            height,
+            max_depth: scene.max_depth,
+            seed: scene.seed,
+            spectral: scene.spectral,
+            cancel: None,
+            aovs: None,
+        };
+        eprintln!(
+            ">> Benchmark: {} samples per pixel, {} threads",
+            samples,
+            rayon::current_num_threads()
+        );
+
+        let film = Film::new(width, height, scene.filter);
+        let start = Instant::now();
+        for j in (0..height).rev() {
+            (0..width)
+                .into_par_iter()
+                .for_each(|i| renderer.render_pixel(&film, i, j, samples));
+        }
+        let scanlines = start.elapsed();
+        eprintln!(">> Scanlines: {:.3} s", scanlines.as_secs_f64());
+
+        let film = Film::new(width, height, scene.filter);
+        let start = Instant::now();
+        tile::render(
+            &tiles,
+            |tile| renderer.render_tile(&film, tile, samples),
+            |_| {},
+        );
+        let tiled = start.elapsed();
+        eprintln!(
+            ">> Tiles ({} px, {:?}): {:.3} s",
+            tile_size,
+            tile_order,
+            tiled.as_secs_f64()
+        );
+        eprintln!(
+            ">> Speedup: {:.2}x",
+            scanlines.as_secs_f64() / tiled.as_secs_f64()
+        );
+        return Ok(());
+    }
+
+    // Checkpoints: --checkpoint[=<path>] saves the progress every --checkpoint-interval=<seconds>
+    // and at the end, --resume continues from the checkpoint (and may raise the sample count)
+    let checkpoint_path = args
+        .value::<String>("checkpoint")
+        .unwrap_or_else(|| "render.checkpoint".to_string());
+    let checkpointing = args.flag("checkpoint") || args.flag("resume");
+    let checkpoint_interval =
+        Duration::from_secs_f64(args.value("checkpoint-interval").unwrap_or(300.0));
+    let settings = checkpoint::Settings {
+        width,
+        height,
+        max_depth: scene.max_depth,
+        filter: scene.filter,
+        seed: scene.seed,
+        scene_hash: checkpoint::scene_hash(
+            &args,
+            &[
+                "checkpoint",
+                "checkpoint-interval",
+                "resume",
+                "samples",
+                "pass-samples",
+                "tile-size",
+                "tile-order",
+                "benchmark",
+                "seed",
+                "output",
+                "aovs",
+                "denoise",
+                "denoise-sigma",
+                "coordinator",
+                "spawn-workers",
+                "worker-timeout",
+                "worker",
+            ],
+        ),
+    };
+
+    // Worker: renders tiles for a coordinator, see below
+    if let Some(worker) = worker {
+        let (world, camera) = scene.build(scene.time)?;
+        let renderer = Renderer {
+            world: &world,
+            camera: &camera,
+            width,
+            height,
+            max_depth: scene.max_depth,
+            seed: scene.seed,
+            spectral: scene.spectral,
+            cancel: None,
+            aovs: None,
+        };
+        let film = Film::new(width, height, scene.filter);
+        worker.run(&renderer, &settings, &film)?;
+        if interrupt::interrupted() {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        return Ok(());
+    }
+
+    let mut preview = Preview::new(width, height);
+
+    // Interactive: --interactive frames the shot in the preview window, see Preview for the
+    // controls. The render restarts whenever the view changes. Saved cameras are appended as keys
+    // to --camera-out=<file>, ready for --camera-path.
+    #[cfg(not(feature = "minifb"))]
+    if args.flag("interactive") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--interactive needs the preview window (minifb feature)",
+        ));
+    }
+    #[cfg(feature = "minifb")]
+    if args.flag("interactive") {
+        let camera_out = args
+            .value::<String>("camera-out")
+            .unwrap_or_else(|| "camera.path".to_string());
+        return navigation::explore(&scene, &tiles, &mut preview, pass_samples, &camera_out);
+    }
+
+    // Animation: --frames=<first>-<last> renders a sequence at --fps=<n> frames per second into
+    // --frame-dir=<dir>, --skip-existing keeps frames rendered earlier
+    if let Some(range) = args.value::<String>("frames") {
+        let (first, last) = match range.split_once('-') {
+            Some((first, last)) => (first.parse(), last.parse()),
+            None => (range.parse(), range.parse()),
+        };
+        let (first, last): (usize, usize) = match (first, last) {
+            (Ok(first), Ok(last)) if first <= last => (first, last),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--frames expects a range like 1-48",
+                ))
+            }
+        };
+        let fps = args.value("fps").unwrap_or(24.0);
+        let frame_dir = PathBuf::from(
+            args.value::<String>("frame-dir")
+                .unwrap_or_else(|| "frames".to_string()),
+        );
+        fs::create_dir_all(&frame_dir)?;
+
+        for frame in first..=last {
+            let path = frame_dir.join(format!("frame_{:04}.png", frame));
+            if args.flag("skip-existing") && path.exists() {
+                eprintln!(">> Frame {}: skipping existing {}", frame, path.display());
+                continue;
+            }
+
+            // frame 1 shows the scene at time zero
+            let time = (frame as f64 - 1.0) / fps;
+            let (world, camera) = scene.build(time)?;
+            let renderer = Renderer {
+                world: &world,
+                camera: &camera,
+                width,
+                height,
+                max_depth: scene.max_depth,
+                seed: scene.seed,
+                spectral: scene.spectral,
+                cancel: None,
+                aovs: None,
+            };
+            let film = Film::new(width, height, scene.filter);
+            let mut remaining = tiles.len();
+            renderer.render(
+                &film,
+                &tiles,
+                scene.samples,
+                pass_samples,
+                |tile, samples| {
+                    remaining = if remaining == 0 {
+                        tiles.len()
+                    } else {
+                        remaining
+                    } - 1;
+                    eprint!(
+                        "\r>> Frame {} ({:.2} s), samples: {:4}/{}, tiles remaining: {:width$}",
+                        frame,
+                        time,
+                        samples,
+                        scene.samples,
+                        remaining,
+                        width = 5
+                    );
+                    preview.update(&film, tile);
+                },
+                |_| Ok(()),
+            )?;
+
+            if interrupt::interrupted() {
+                // partial frames are not written, so --skip-existing renders them again
+                eprintln!("\n>> Render interrupted, frame {} was not written", frame);
+                std::process::exit(EXIT_INTERRUPTED);
+            }
 
-                // divide the color by the number of samples
-                color / RAY_SAMPLES_PER_PIXEL as f64
-            })
-            .collect();
+            // write to a temporary file first, so a frame file is either complete or missing
+            let temp = path.with_extension("png.tmp");
+            png::write(&mut BufWriter::new(File::create(&temp)?), &film.image())?;
+            fs::rename(&temp, &path)?;
+            eprintln!("\n>> Frame {}: wrote {}", frame, path.display());
+        }
+
+        return Ok(());
+    }
 
-        for i in 0..scanline.len() {
-            img[j][i] = scanline[i];
+    // Still image at the point in time given by --time
+    let (world, camera) = scene.build(scene.time)?;
+    let mut collected = aovs.clone();
+    if denoiser.is_some() {
+        for aov in &[Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Variance] {
+            if !collected.contains(aov) {
+                collected.push(*aov);
+            }
+        }
+    }
+    let mut aov_film = if collected.is_empty() {
+        None
+    } else {
+        Some(AovFilm::new(width, height, &collected))
+    };
+
+    // create the film which collects the samples, checkpoints hold the AOVs as well so they
+    // cover all samples after resuming, e.g. the variance the denoiser relies on
+    let mut film = Film::new(width, height, scene.filter);
+    if args.flag("resume") {
+        checkpoint::load(&checkpoint_path, &settings, &mut film, aov_film.as_mut())?;
+        eprintln!(">> Resuming from {}", checkpoint_path);
+    }
+    let film = film;
+    let aov_film = aov_film;
+
+    let renderer = Renderer {
+        world: &world,
+        camera: &camera,
+        width,
+        height,
+        max_depth: scene.max_depth,
+        seed: scene.seed,
+        spectral: scene.spectral,
+        cancel: None,
+        aovs: aov_film.as_ref(),
+    };
+
+    // Distributed rendering: --coordinator=<address> hands the tiles out to worker processes,
+    // started with --worker=<address> on any machine or with --spawn-workers=<n> on this one.
+    // --worker-timeout=<seconds> gives up on workers which take longer for a tile.
+    if let Some(address) = args.value::<String>("coordinator") {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        eprintln!(">> Waiting for workers on {}", address);
+        let mut workers = Vec::new();
+        for _ in 0..args.value("spawn-workers").unwrap_or(0) {
+            workers.push(
+                Command::new(std::env::current_exe()?)
+                    .arg(format!("--worker={}", address))
+                    .spawn()?,
+            );
         }
 
-        #[cfg(feature = "minifb")]
-        {
-            // update minifb buffer and render it
-            let buffer_offset = (IMAGE_HEIGHT - 1 - j) * IMAGE_WIDTH;
-            let buffer_row = &mut buffer[buffer_offset..buffer_offset + IMAGE_WIDTH];
-            for i in 0..scanline.len() {
-                let rgb8 = color_to_rgb8(&scanline[i]);
-                let (r, g, b) = (rgb8[0] as u32, rgb8[1] as u32, rgb8[2] as u32);
-                buffer_row[i] = (r << 16) | (g << 8) | b
+        // the workers get the scene, but none of the options about this process
+        let job = args.to_vec(&[
+            "coordinator",
+            "spawn-workers",
+            "worker-timeout",
+            "checkpoint",
+            "checkpoint-interval",
+            "resume",
+            "benchmark",
+            "output",
+        ]);
+        let coordinator = Coordinator {
+            args: &job,
+            settings: &settings,
+            film: &film,
+            samples: scene.samples,
+            timeout: args.value("worker-timeout").map(Duration::from_secs_f64),
+        };
+        let mut merged = 0;
+        coordinator.run(listener, &tiles, |tile| {
+            merged += 1;
+            eprint!("\r>> Tiles merged: {:5}/{}", merged, tiles.len());
+            preview.update(&film, tile);
+        })?;
+
+        for mut worker in workers {
+            // interrupted workers may still be busy with a tile nobody waits for
+            if interrupt::interrupted() {
+                let _ = worker.kill();
             }
-            window
-                .update_with_buffer(&buffer, IMAGE_WIDTH, IMAGE_HEIGHT)
-                .unwrap();
+            worker.wait()?;
+        }
+        if checkpointing && !interrupt::interrupted() {
+            checkpoint::save(&checkpoint_path, &settings, &film, aov_film.as_ref())?;
         }
+    } else {
+        // Render in passes of --pass-samples=<n> samples per pixel, checkpoints are written in
+        // between.
+        let mut last_checkpoint = Instant::now();
+        let mut remaining = tiles.len();
+        renderer.render(
+            &film,
+            &tiles,
+            scene.samples,
+            pass_samples,
+            |tile, samples| {
+                remaining = if remaining == 0 {
+                    tiles.len()
+                } else {
+                    remaining
+                } - 1;
+                eprint!(
+                    "\r>> Samples: {:4}/{}, tiles remaining: {:width$}",
+                    samples,
+                    scene.samples,
+                    remaining,
+                    width = 5
+                );
+                preview.update(&film, tile);
+            },
+            |samples| {
+                let finished = samples == scene.samples;
+                if checkpointing && (finished || last_checkpoint.elapsed() >= checkpoint_interval) {
+                    checkpoint::save(&checkpoint_path, &settings, &film, aov_film.as_ref())?;
+                    last_checkpoint = Instant::now();
+                }
+                Ok(())
+            },
+        )?;
+    }
+    let interrupted = interrupt::interrupted();
+    if interrupted {
+        eprintln!("\n>> Render interrupted");
+    } else {
+        eprintln!("\n>> Render done");
+    }
+
+    // Output: --output=<path> writes the image to a file instead of stdout, as PNG if the name
+    // ends with .png and as PPM otherwise. An interrupted render produces the image of the
+    // samples taken so far.
+    let mut img = film.image();
+    if let (Some(denoiser), Some(aov_film)) = (&denoiser, &aov_film) {
+        let albedo = aov_film.image(Aov::Albedo).unwrap();
+        let normal = aov_film.image(Aov::Normal).unwrap();
+        let depth = aov_film.image(Aov::Depth).unwrap();
+        let variance = aov_film.image(Aov::Variance).unwrap();
+        let start = Instant::now();
+        img = denoiser.denoise(
+            &img,
+            &Features {
+                albedo: &albedo,
+                normal: &normal,
+                depth: &depth,
+                variance: &variance,
+            },
+        );
+        eprintln!(">> Denoised in {:.3} s", start.elapsed().as_secs_f64());
     }
-    eprintln!("\n>> Render done");
-
-    // print PPM header
-    println!("P3");
-    println!("{} {}", img.width(), img.height());
-    println!("255");
-    // print PPM data
-    for j in (0..img.height()).rev() {
-        for i in 0..img.width() {
-            let color = img[j][i];
-            let rgb8 = color_to_rgb8(&color);
-            println!("{} {} {}", rgb8[0], rgb8[1], rgb8[2]);
+    match args.value::<String>("output") {
+        Some(path) if path.ends_with(".png") => {
+            png::write(&mut BufWriter::new(File::create(path)?), &img)?
         }
+        Some(path) => ppm::write(&mut BufWriter::new(File::create(path)?), &img)?,
+        None => ppm::write(&mut BufWriter::new(io::stdout().lock()), &img)?,
+    }
+
+    // AOVs are written as PFM images named after the output, e.g. render.png gives
+    // render.albedo.pfm, render.depth.pfm and so on
+    if let Some(aov_film) = &aov_film {
+        let base = match args.value::<String>("output") {
+            Some(path) => Path::new(&path).with_extension(""),
+            None => PathBuf::from("render"),
+        };
+        for aov in &aovs {
+            let mut path = base.clone().into_os_string();
+            path.push(format!(".{}.pfm", aov.name()));
+            let img = aov_film.image(*aov).unwrap();
+            hdr::write_pfm(
+                &mut BufWriter::new(File::create(&path)?),
+                &img,
+                aov.channels(),
+            )?;
+            eprintln!(">> Wrote {} to {}", aov.name(), Path::new(&path).display());
+        }
+    }
+
+    if interrupted {
+        // no thread renders anymore, so the film is consistent
+        checkpoint::save(&checkpoint_path, &settings, &film, aov_film.as_ref())?;
+        eprintln!(
+            ">> Saved checkpoint to {}, continue with --resume",
+            checkpoint_path
+        );
+        std::process::exit(EXIT_INTERRUPTED);
     }
 
     Ok(())
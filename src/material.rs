@@ -1,21 +1,115 @@
+use std::f64::consts::PI;
 use std::ops::{Add, Div, Mul, Sub};
 
 use crate::color::Color;
 use crate::hittable::HitRecord;
+use crate::medium::Interior;
+use crate::onb::Onb;
 use crate::ray::Ray;
 use crate::rtweekend;
+use crate::spectrum::Dispersion;
 use crate::vec::Vec3;
 
+/// Result of scattering a light ray on a material.
+pub struct ScatterRecord<T: Copy> {
+    /// Scattered ray.
+    pub ray: Ray<T>,
+    /// Attenuation of the light carried by the scattered ray.
+    ///
+    /// This is the BSDF value times the cosine term, divided by the probability density of the
+    /// scattered direction.
+    pub attenuation: Color,
+    /// Probability density of the scattered direction with respect to solid angle.
+    ///
+    /// Perfectly specular materials scatter into exactly one direction, which is not described by
+    /// a density. They return none here.
+    pub pdf: Option<f64>,
+}
+
 /// Generic material trait.
 pub trait Material<T: Copy> {
     /// Scatter an incoming light ray on a material.
     ///
-    /// Returns a scattered ray and the attenuation color if there is reflection.
-    /// Otherwise, none is returned.
+    /// Returns a scattered ray sampled from the BSDF along with its attenuation and probability
+    /// density if there is reflection. Otherwise, none is returned.
     ///
     /// * `ray` - Incoming light ray.
     /// * `rec` - Previous hit record of the ray on some object.
-    fn scatter(&self, ray: &Ray<T>, rec: &HitRecord<T>) -> Option<(Ray<T>, Color)>;
+    fn scatter(&self, ray: &Ray<T>, rec: &HitRecord<T>) -> Option<ScatterRecord<T>>;
+
+    /// Evaluate the BSDF for an incoming ray and an arbitrary outgoing direction.
+    ///
+    /// Returns the BSDF value multiplied by the cosine of the angle between the direction and the
+    /// surface normal. Perfectly specular materials always return black, since the chance of the
+    /// direction matching their single reflection direction is zero.
+    ///
+    /// * `ray` - Incoming light ray.
+    /// * `rec` - Hit record of the ray on some object.
+    /// * `direction` - Unit direction of the scattered light, e.g. towards a light source.
+    fn eval(&self, _ray: &Ray<T>, _rec: &HitRecord<T>, _direction: &Vec3<T>) -> Color {
+        Color::new3(0.0, 0.0, 0.0)
+    }
+
+    /// Returns the probability density with which `scatter` picks a given direction.
+    ///
+    /// * `ray` - Incoming light ray.
+    /// * `rec` - Hit record of the ray on some object.
+    /// * `direction` - Unit direction of the scattered light.
+    fn pdf(&self, _ray: &Ray<T>, _rec: &HitRecord<T>, _direction: &Vec3<T>) -> f64 {
+        0.0
+    }
+
+    /// Returns the light emitted by the material towards the incoming ray.
+    ///
+    /// Most materials do not emit any light, so the default is black.
+    ///
+    /// * `ray` - Incoming light ray.
+    /// * `rec` - Hit record of the ray on the emitting object.
+    fn emitted(&self, _ray: &Ray<T>, _rec: &HitRecord<T>) -> Color {
+        Color::new3(0.0, 0.0, 0.0)
+    }
+
+    /// Whether the material emits light.
+    ///
+    /// Objects with emissive materials are sampled as light sources by the world.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Returns the medium inside objects made of this material, if light can travel through it.
+    ///
+    /// The renderer keeps track of the media along a path, see `MediumStack`. It absorbs light
+    /// inside them and tells dielectrics the index of refraction on the other side of their
+    /// surface in `HitRecord::exterior_refraction`.
+    fn interior(&self) -> Option<Interior> {
+        None
+    }
+
+    /// Whether the material scatters each wavelength differently, see `Wavelengths`.
+    ///
+    /// Only the hero wavelength of a spectral path is followed after such a material.
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    /// Whether the colors returned by `scatter` and `eval` are values at the wavelengths in
+    /// `HitRecord::wavelengths` when rendering spectrally.
+    ///
+    /// Other materials return RGB colors, which the renderer converts into spectral values, see
+    /// `Wavelengths::spectrum`.
+    fn is_spectral(&self) -> bool {
+        false
+    }
+
+    /// Returns the albedo, the fraction of incoming light the surface scatters in total.
+    ///
+    /// This is not needed for rendering, it describes the surface in feature images, e.g. for
+    /// denoising. Materials which do not scatter any light return black.
+    ///
+    /// * `rec` - Hit record of a ray on the object.
+    fn albedo(&self, _rec: &HitRecord<T>) -> Color {
+        Color::new3(0.0, 0.0, 0.0)
+    }
 }
 
 /// Lambertian (diffuse) material.
@@ -23,7 +117,7 @@ pub trait Material<T: Copy> {
 /// In our diffuse reflection model, a lambertian material will always both scatter and attenuate
 /// by its own reflectance (albedo).
 ///
-/// Should only be used for smooth matte surfaces, not rough matte ones.
+/// Should only be used for smooth matte surfaces, not rough matte ones, see `OrenNayar`.
 /// See https://www.cs.cmu.edu/afs/cs/academic/class/15462-f09/www/lec/lec8.pdf for explanation.
 pub struct Lambertian {
     /// Color of the object.
@@ -38,22 +132,148 @@ impl Lambertian {
 }
 
 impl Material<f64> for Lambertian {
-    fn scatter(&self, _ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<(Ray<f64>, Color)> {
+    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<ScatterRecord<f64>> {
         // Diffuse reflection: True Lambertian reflection.
         // We aim for a Lambertian distribution of the reflected rays, which has a distribution of
         // cos(phi) instead of cos³(phi) for random vectors inside the unit sphere.
         // To achieve this, we pick a random point on the surface of the unit sphere, which is done
         // by picking a random point inside the sphere and then normalizing that point.
-        let random_unit_vec = rtweekend::random_vec_in_unit_sphere().normalized();
+        let random_unit_vec = rtweekend::random_unit_vec();
 
         // Diffuse reflection: send out a new ray from the hit position point pointing towards a
         // random point on the surface of the sphere tangent to that hit point.
         // Possible problem: the recursion depth may be too deep, so we blow up the stack. Avoid
         // this by limiting the number of child rays.
-        let scatter_direction = rec.normal + random_unit_vec;
+        let mut scatter_direction = rec.normal + random_unit_vec;
+        // the random vector may be (almost) exactly opposite to the normal
+        if scatter_direction.length_squared() < 1e-12 {
+            scatter_direction = rec.normal;
+        }
+        let scatter_direction = scatter_direction.normalized();
         let scatter = Ray::new(rec.point, scatter_direction);
 
-        Some((scatter, self.albedo))
+        // The BSDF is albedo / π and the directions are distributed with density cosθ / π, so
+        // the cosine terms cancel out and the attenuation is just the albedo.
+        Some(ScatterRecord {
+            ray: scatter,
+            attenuation: self.albedo,
+            pdf: Some(self.pdf(ray, rec, &scatter_direction)),
+        })
+    }
+
+    fn eval(&self, _ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> Color {
+        // The Lambertian BSDF is constant: albedo / π. Light arriving from below the surface is
+        // not reflected at all.
+        let cosine = Vec3::dot(&rec.normal, direction).max(0.0);
+        self.albedo * (cosine / PI)
+    }
+
+    fn pdf(&self, _ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> f64 {
+        Vec3::dot(&rec.normal, direction).max(0.0) / PI
+    }
+
+    fn albedo(&self, _rec: &HitRecord<f64>) -> Color {
+        self.albedo
+    }
+}
+
+/// Rough diffuse material, e.g. clay, concrete or fabric.
+///
+/// Following Oren and Nayar ("Generalization of Lambert's Reflectance Model", 1994), the surface
+/// is made of tiny V-shaped grooves whose sides are Lambertian. The grooves shadow and light each
+/// other, so the surface reflects more light back towards the light source and looks flatter than
+/// a Lambertian one, like the full moon. With θ and φ the polar and azimuthal angles of the
+/// directions in the local frame, α = max(θi, θo) and β = min(θi, θo), the qualitative model is:
+///
+///     f(ωo, ωi) = albedo / π ⋅ (A + B⋅max(0, cos(φi - φo))⋅sinα⋅tanβ)
+///
+///     A = 1 - σ² / (2⋅(σ² + 0.33)),  B = 0.45⋅σ² / (σ² + 0.09)
+///
+/// where σ is the standard deviation of the groove slopes in radians. Zero is Lambertian.
+///
+/// Directions are sampled like for `Lambertian` with the density cosθi / π, which is exact for
+/// the sampled direction, so light sampling and BSDF sampling weight each other correctly. The
+/// cosine terms cancel, and the weight of a sample is the albedo times the factor in parentheses.
+pub struct OrenNayar {
+    /// Color of the object.
+    albedo: Color,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    /// Create a new rough diffuse material.
+    ///
+    /// * `albedo`: Color of the object.
+    /// * `sigma`: Roughness, the standard deviation of the groove slopes in degrees, e.g. 20° for
+    ///   clay or 40° for rough concrete. Zero is Lambertian.
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        let sigma = rtweekend::degrees_to_radians(sigma.max(0.0));
+        let sigma2 = sigma * sigma;
+        OrenNayar {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    /// Returns the factor A + B⋅max(0, cos(φi - φo))⋅sinα⋅tanβ of the albedo.
+    ///
+    /// * `wo`: Unit direction towards the viewer in the local frame.
+    /// * `wi`: Unit direction towards the light in the local frame, above the surface.
+    fn factor(&self, wo: &Vec3<f64>, wi: &Vec3<f64>) -> f64 {
+        let sin_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+
+        // cos(φi - φo) from the projections of the directions onto the surface
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.x() * wi.x() + wo.y() * wi.y()) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+        // the larger angle has the smaller cosine
+        let (sin_alpha, tan_beta) = if wi.z() > wo.z() {
+            (sin_o, sin_i / wi.z())
+        } else {
+            (sin_i, sin_o / wo.z())
+        };
+        self.a + self.b * cos_phi * sin_alpha * tan_beta
+    }
+}
+
+impl Material<f64> for OrenNayar {
+    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<ScatterRecord<f64>> {
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.project(&-ray.direction().normalized());
+        let wi = rtweekend::random_cosine_direction();
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return None;
+        }
+
+        // the cosine terms cancel out like for the Lambertian material
+        Some(ScatterRecord {
+            ray: Ray::new(rec.point, frame.local(&wi)),
+            attenuation: self.albedo * self.factor(&wo, &wi),
+            pdf: Some(wi.z() / PI),
+        })
+    }
+
+    fn eval(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> Color {
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.project(&-ray.direction().normalized());
+        let wi = frame.project(direction);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::new3(0.0, 0.0, 0.0);
+        }
+        self.albedo * (self.factor(&wo, &wi) * wi.z() / PI)
+    }
+
+    fn pdf(&self, _ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> f64 {
+        Vec3::dot(&rec.normal, direction).max(0.0) / PI
+    }
+
+    fn albedo(&self, _rec: &HitRecord<f64>) -> Color {
+        self.albedo
     }
 }
 
@@ -121,19 +341,86 @@ impl Metal {
     }
 }
 
+impl Metal {
+    /// Returns the probability density of the fuzzed reflection for a given direction.
+    ///
+    /// The scattered direction is d = R + f⋅s, where R is the unit reflection direction, f the
+    /// fuzz factor and s a uniformly distributed point inside the unit sphere. In other words,
+    /// the tip of d is uniformly distributed inside a ball with radius f around R. The ray
+    /// t⋅ω (t >= 0) enters this ball at t0 and leaves it at t1, with
+    ///
+    ///     t0,1 = (ω⋅R) ∓ sqrt((ω⋅R)² - (1 - f²))
+    ///
+    /// Integrating the uniform density over the part of the ray inside the ball (the volume
+    /// element in spherical coordinates is t²⋅dt⋅dω) yields:
+    ///
+    ///     p(ω) = ∫ t² dt / V = (t1³ - t0³) / (4π⋅f³)
+    ///
+    /// * `reflected` - Unit reflection direction R.
+    /// * `direction` - Unit direction ω.
+    fn fuzz_pdf(&self, reflected: &Vec3<f64>, direction: &Vec3<f64>) -> f64 {
+        let b = Vec3::dot(direction, reflected);
+        let discriminant = b * b - (1.0 - self.fuzz * self.fuzz);
+        if discriminant < 0.0 {
+            return 0.0;
+        }
+
+        let d_sqrt = discriminant.sqrt();
+        let t0 = (b - d_sqrt).max(0.0);
+        let t1 = b + d_sqrt;
+        if t1 <= 0.0 {
+            return 0.0;
+        }
+
+        (t1 * t1 * t1 - t0 * t0 * t0) / (4.0 * PI * self.fuzz * self.fuzz * self.fuzz)
+    }
+}
+
 impl Material<f64> for Metal {
-    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<(Ray<f64>, Color)> {
+    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<ScatterRecord<f64>> {
         // specular reflection
-        let direction = Metal::reflect(&ray.direction().normalized(), &rec.normal);
+        let reflected = Metal::reflect(&ray.direction().normalized(), &rec.normal);
         // apply fuzzing
-        let direction = direction + rtweekend::random_vec_in_unit_sphere() * self.fuzz;
+        let direction = reflected + rtweekend::random_vec_in_unit_sphere() * self.fuzz;
         let scatter = Ray::new(rec.point, direction);
 
         if Vec3::dot(&scatter.direction(), &rec.normal) <= 0.0 {
-            None
+            return None;
+        }
+
+        // A perfect mirror has no density, otherwise the fuzzing distribution is the BSDF (up to
+        // the albedo), so the attenuation is the albedo in both cases.
+        let pdf = if self.fuzz > 0.0 {
+            Some(self.fuzz_pdf(&reflected, &direction.normalized()))
         } else {
-            Some((scatter, self.albedo))
+            None
+        };
+
+        Some(ScatterRecord {
+            ray: scatter,
+            attenuation: self.albedo,
+            pdf,
+        })
+    }
+
+    fn eval(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> Color {
+        // Directions below the surface are absorbed, see scatter().
+        if Vec3::dot(direction, &rec.normal) <= 0.0 {
+            return Color::new3(0.0, 0.0, 0.0);
         }
+        self.albedo * self.pdf(ray, rec, direction)
+    }
+
+    fn pdf(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> f64 {
+        if self.fuzz <= 0.0 {
+            return 0.0;
+        }
+        let reflected = Metal::reflect(&ray.direction().normalized(), &rec.normal);
+        self.fuzz_pdf(&reflected, direction)
+    }
+
+    fn albedo(&self, _rec: &HitRecord<f64>) -> Color {
+        self.albedo
     }
 }
 
@@ -214,9 +501,33 @@ impl Material<f64> for Metal {
 ///
 /// In case of sinθ > (1.0 / refraction ratio), we cannot refract and thus must reflect. This is
 /// called "total internal reflection".
+///
+/// Radiance changes when it crosses the interface: the light is squeezed into a narrower cone
+/// when it enters a denser medium, which makes it brighter by (η′ / η)², and spreads out again on
+/// the way out. The transmitted ray is therefore weighted with
+///
+///     (η / η′)²
+///
+/// where η is the side the ray comes from. Going in and out of an object cancels, but light
+/// which is scattered inside, e.g. by an object under water, keeps the factor. `RoughDielectric`
+/// and the glass of `Principled` follow the same convention, so smooth and rough glass are
+/// equally bright.
+///
+/// Colored glass and liquids absorb light on its way through them, see `beer_lambert`. The
+/// outside is air, unless the object is nested inside another dielectric, see `MediumStack`.
+///
+/// In reality, the refraction index depends on the wavelength: blue light is bent more than red
+/// light, which splits white light into a rainbow (dispersion). This is only visible when
+/// rendering spectrally, see `Wavelengths`.
 pub struct Dielectric {
     /// Refraction index.
     refraction: f64,
+    /// Wavelength dependence of the refraction index, if any.
+    dispersion: Option<Dispersion>,
+    /// Absorption coefficient of the inside per channel.
+    absorption: Color,
+    /// Priority of the inside where objects overlap.
+    priority: u32,
 }
 
 impl Dielectric {
@@ -224,7 +535,40 @@ impl Dielectric {
     ///
     /// * `refraction`: Refraction index.
     pub fn new(refraction: f64) -> Self {
-        Dielectric { refraction }
+        Dielectric {
+            refraction,
+            dispersion: None,
+            absorption: Color::new3(0.0, 0.0, 0.0),
+            priority: 0,
+        }
+    }
+
+    /// Sets the color of the inside, see `absorption_coefficient`.
+    ///
+    /// * `transmittance`: Color of white light after traveling the distance inside.
+    /// * `distance`: Distance in scene units.
+    pub fn absorption(mut self, transmittance: Color, distance: f64) -> Self {
+        self.absorption = absorption_coefficient(&transmittance, distance);
+        self
+    }
+
+    /// Sets the priority of the inside where it overlaps other dielectrics, see `MediumStack`.
+    ///
+    /// * `priority`: Priority, e.g. higher for a glass than for the liquid in it.
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Makes the refraction index depend on the wavelength.
+    ///
+    /// Renders in RGB use the index at the yellow d line, see `Dispersion::nominal`.
+    ///
+    /// * `dispersion`: Refraction index by wavelength, e.g. `Dispersion::BK7`.
+    pub fn dispersion(mut self, dispersion: Dispersion) -> Self {
+        self.refraction = dispersion.nominal();
+        self.dispersion = Some(dispersion);
+        self
     }
 
     /// Returns the refracted (trasmitted) ray.
@@ -261,14 +605,17 @@ impl Dielectric {
 }
 
 impl Material<f64> for Dielectric {
-    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<(Ray<f64>, Color)> {
-        // assume the material where the ray originates from is air
-        let eta = 1.0;
-        let eta_prime = self.refraction;
+    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<ScatterRecord<f64>> {
+        // the medium outside, usually air
+        let eta = rec.exterior_refraction;
+        let eta_prime = match (self.dispersion, rec.wavelengths) {
+            (Some(dispersion), Some(wavelengths)) => dispersion.refraction(wavelengths.hero()),
+            _ => self.refraction,
+        };
         let refraction_ratio = if rec.front_face {
             eta / eta_prime
         } else {
-            eta_prime
+            eta_prime / eta
         };
 
         // Total internal reflection: if
@@ -291,18 +638,123 @@ impl Material<f64> for Dielectric {
         let can_refract = can_refract
             && (Dielectric::reflectance(cos_theta, refraction_ratio)) < rtweekend::random(0.0..1.0);
 
-        // direction of the scattered ray
-        let direction = if !can_refract {
+        // direction of the scattered ray, and the change of radiance across the interface
+        let (direction, scale) = if !can_refract {
             // must reflect
-            Metal::reflect(&r, &rec.normal)
+            (Metal::reflect(&r, &rec.normal), 1.0)
         } else {
             // can refract
-            Dielectric::refract(&r, &rec.normal, refraction_ratio)
+            (
+                Dielectric::refract(&r, &rec.normal, refraction_ratio),
+                refraction_ratio * refraction_ratio,
+            )
         };
         let scatter = Ray::new(rec.point, direction);
-        // attenuation is always 1 since air/glass/diamond do not absorb
-        let attenuation = Color::new3(1.0, 1.0, 1.0);
+        // the interface itself does not absorb, the renderer takes care of the absorption inside
+        let attenuation = Color::new3(scale, scale, scale);
+
+        Some(ScatterRecord {
+            ray: scatter,
+            attenuation,
+            pdf: None,
+        })
+    }
+
+    fn albedo(&self, _rec: &HitRecord<f64>) -> Color {
+        // all light is either reflected or transmitted
+        Color::new3(1.0, 1.0, 1.0)
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        Some(Interior {
+            refraction: self.refraction,
+            absorption: self.absorption,
+            priority: self.priority,
+        })
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+}
+
+/// Returns the fraction of light which passes through an absorbing medium (Beer–Lambert law).
+///
+/// Every bit of the way, the medium absorbs the same fraction of the light that is left, so the
+/// light falls off exponentially with the distance d:
+///
+///     T = exp(-σa⋅d)
+///
+/// where σa is the absorption coefficient. Each channel is absorbed separately, which gives thick
+/// glass, liquids and gemstones their color: the deeper, the more saturated.
+///
+/// * `absorption` - Absorption coefficient σa per channel, in inverse scene units.
+/// * `distance` - Distance traveled through the medium, may be infinite.
+pub fn beer_lambert(absorption: &Color, distance: f64) -> Color {
+    // clear channels stay clear even at an infinite distance
+    let channel = |sigma: f64| {
+        if sigma > 0.0 {
+            (-sigma * distance).exp()
+        } else {
+            1.0
+        }
+    };
+    Color::new3(
+        channel(absorption.x()),
+        channel(absorption.y()),
+        channel(absorption.z()),
+    )
+}
+
+/// Returns the absorption coefficient which leaves a given color after a given distance.
+///
+/// Picking a color is easier than picking a coefficient, this inverts `beer_lambert`:
+///
+///     σa = -ln(T) / d
+///
+/// * `transmittance` - Color of white light after traveling the distance, in (0, 1].
+/// * `distance` - Distance in scene units.
+pub fn absorption_coefficient(transmittance: &Color, distance: f64) -> Color {
+    let channel = |value: f64| -value.clamp(1e-6, 1.0).ln() / distance.max(1e-6);
+    Color::new3(
+        channel(transmittance.x()),
+        channel(transmittance.y()),
+        channel(transmittance.z()),
+    )
+}
+
+/// Diffuse light (emissive) material.
+///
+/// Emits light uniformly into all directions of the hemisphere around the front face of the
+/// surface. The back face does not emit. Light sources do not reflect any incoming light.
+pub struct DiffuseLight {
+    /// Emitted radiance.
+    emit: Color,
+}
+
+impl DiffuseLight {
+    /// Create a new light emitting material.
+    ///
+    /// * `emit`: Emitted radiance, may exceed 1.0 for bright lights.
+    pub fn new(emit: Color) -> Self {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material<f64> for DiffuseLight {
+    fn scatter(&self, _ray: &Ray<f64>, _rec: &HitRecord<f64>) -> Option<ScatterRecord<f64>> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray<f64>, rec: &HitRecord<f64>) -> Color {
+        if rec.front_face {
+            self.emit
+        } else {
+            Color::new3(0.0, 0.0, 0.0)
+        }
+    }
 
-        Some((scatter, attenuation))
+    fn is_emissive(&self) -> bool {
+        true
     }
 }
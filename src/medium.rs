@@ -0,0 +1,118 @@
+use crate::color::Color;
+use crate::material;
+
+/// Medium filling the inside of a dielectric object, e.g. glass, water or air in a bubble.
+#[derive(Debug, Clone, Copy)]
+pub struct Interior {
+    /// Index of refraction.
+    pub refraction: f64,
+    /// Absorption coefficient per channel, see `material::beer_lambert`.
+    pub absorption: Color,
+    /// Which medium fills the space where objects overlap, the highest priority wins.
+    pub priority: u32,
+}
+
+/// Media a ray is currently inside, for nested dielectrics.
+///
+/// Refraction depends on the media on both sides of a surface, which is not always air on the
+/// outside: think of a glass filled with water, ice floating in it, or a bubble inside a gem.
+/// Following Schmidt and Budge ("Simple Nested Dielectrics in Ray Traced Images", 2002), every
+/// path keeps a stack of the media it entered. Touching surfaces are hard to model without gaps,
+/// so objects are allowed to overlap instead, and the medium with the highest priority fills the
+/// overlap. Surfaces of lower priority media inside it do not exist for the ray (false hits):
+///
+///      |  :         :  |
+///      |  :  water  :  |    the boundary of the water (:) lies inside the glass walls and is
+///      |  :.........:  |    skipped, the ray refracts from glass to water at the inner
+///      |_______________|    surface of the glass instead
+///             glass
+///
+/// Media are identified by their material index, so a hollow object made of several surfaces
+/// sharing a material is one medium. Where no medium is left, the ray travels through air.
+#[derive(Debug, Clone, Default)]
+pub struct MediumStack {
+    /// Material index and medium, in the order they were entered.
+    entries: Vec<(usize, Interior)>,
+}
+
+impl MediumStack {
+    /// Create an empty stack, for rays starting in air.
+    pub fn new() -> Self {
+        MediumStack {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the medium with the highest priority, the latest one entered if there is a tie.
+    ///
+    /// * `except` - Material index of a medium to ignore once, e.g. the one about to be left.
+    fn highest(&self, except: Option<usize>) -> Option<&Interior> {
+        let skip = except.and_then(|material| self.position(material));
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| Some(*index) != skip)
+            .max_by_key(|(_, (_, interior))| interior.priority)
+            .map(|(_, (_, interior))| interior)
+    }
+
+    /// Returns the position of the latest entry of a medium.
+    fn position(&self, material: usize) -> Option<usize> {
+        self.entries.iter().rposition(|(id, _)| *id == material)
+    }
+
+    /// Returns the medium the ray is currently traveling through, none for air.
+    pub fn current(&self) -> Option<&Interior> {
+        self.highest(None)
+    }
+
+    /// Whether a surface should be ignored, because a medium of higher priority fills both sides.
+    ///
+    /// * `material` - Material index of the surface.
+    /// * `interior` - Medium inside the surface.
+    /// * `front_face` - Whether the ray enters (true) or leaves (false) the medium.
+    pub fn is_false_hit(&self, material: usize, interior: &Interior, front_face: bool) -> bool {
+        let highest = if front_face {
+            self.highest(None)
+        } else {
+            self.highest(Some(material))
+        };
+        highest.is_some_and(|other| other.priority > interior.priority)
+    }
+
+    /// Returns the index of refraction on the other side of a surface than its own medium.
+    ///
+    /// * `material` - Material index of the surface.
+    /// * `front_face` - Whether the ray enters (true) or leaves (false) the medium.
+    pub fn exterior_refraction(&self, material: usize, front_face: bool) -> f64 {
+        let exterior = if front_face {
+            self.highest(None)
+        } else {
+            self.highest(Some(material))
+        };
+        exterior.map_or(1.0, |interior| interior.refraction)
+    }
+
+    /// Update the stack for a ray passing through a surface.
+    ///
+    /// * `material` - Material index of the surface.
+    /// * `interior` - Medium inside the surface.
+    /// * `front_face` - Whether the ray enters (true) or leaves (false) the medium.
+    pub fn cross(&mut self, material: usize, interior: &Interior, front_face: bool) {
+        if front_face {
+            self.entries.push((material, *interior));
+        } else if let Some(index) = self.position(material) {
+            self.entries.remove(index);
+        }
+    }
+
+    /// Returns the fraction of light passing through the current medium over a distance.
+    ///
+    /// * `distance` - Distance traveled, may be infinite.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        match self.current() {
+            Some(interior) => material::beer_lambert(&interior.absorption, distance),
+            None => Color::new3(1.0, 1.0, 1.0),
+        }
+    }
+}
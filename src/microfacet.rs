@@ -0,0 +1,276 @@
+use std::f64::consts::PI;
+
+use crate::vec::Vec3;
+
+/// GGX (Trowbridge-Reitz) distribution of microfacet normals.
+///
+/// Rough surfaces are modeled as many tiny perfect mirrors (microfacets) whose normals m spread
+/// around the macro surface normal. All directions are given in a local frame with the surface
+/// normal along z and the tangent along x. The distribution is stretched by the roughness αx
+/// along the tangent and αy along the bitangent:
+///
+///     D(m) = 1 / (π⋅αx⋅αy⋅(mx²/αx² + my²/αy² + mz²)²)
+///
+/// Microfacets hide each other from the viewer (masking) and from the light (shadowing). Smith's
+/// model gives the visible fraction in terms of
+///
+///     Λ(ω) = (-1 + sqrt(1 + (αx²⋅ωx² + αy²⋅ωy²) / ωz²)) / 2
+///
+/// as G1(ω) = 1 / (1 + Λ(ω)) for one direction and, taking into account that high facets are
+/// likely visible from both directions, G2(ωo, ωi) = 1 / (1 + Λ(ωo) + Λ(ωi)) for both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    /// Create a new distribution.
+    ///
+    /// * `alpha_x` - Roughness along the tangent.
+    /// * `alpha_y` - Roughness along the bitangent.
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Ggx {
+            alpha_x: alpha_x.max(1e-4),
+            alpha_y: alpha_y.max(1e-4),
+        }
+    }
+
+    /// Create a distribution from perceptual roughness and anisotropy, as used by Disney.
+    ///
+    /// The roughness is squared, which spreads the visual change evenly over [0, 1]. Anisotropy
+    /// stretches the highlight along the tangent:
+    ///
+    ///     aspect = sqrt(1 - 0.9⋅anisotropy)
+    ///     αx = roughness² / aspect,  αy = roughness²⋅aspect
+    ///
+    /// * `roughness` - Perceptual roughness in [0, 1], zero is a perfect mirror.
+    /// * `anisotropy` - Anisotropy in [0, 1], zero is isotropic.
+    pub fn from_roughness(roughness: f64, anisotropy: f64) -> Self {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+        Ggx::new(alpha / aspect, alpha * aspect)
+    }
+
+    /// Whether the surface is so smooth that it is better treated as a perfect mirror.
+    ///
+    /// The distribution becomes a Dirac delta, which cannot be evaluated numerically.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    /// Returns the density of microfacet normals D(m).
+    ///
+    /// The projected areas of the microfacets add up to the macro surface: ∫ D(m)⋅cosθm dm = 1.
+    ///
+    /// * `m` - Unit microfacet normal in the local frame.
+    pub fn d(&self, m: &Vec3<f64>) -> f64 {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = m.x() / self.alpha_x;
+        let y = m.y() / self.alpha_y;
+        let e = x * x + y * y + m.z() * m.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Returns Smith's auxiliary function Λ(ω).
+    ///
+    /// * `w` - Unit direction in the local frame.
+    pub fn lambda(&self, w: &Vec3<f64>) -> f64 {
+        let z2 = w.z() * w.z();
+        if z2 <= 0.0 {
+            return f64::INFINITY;
+        }
+        let x = self.alpha_x * w.x();
+        let y = self.alpha_y * w.y();
+        ((1.0 + (x * x + y * y) / z2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Returns the fraction of microfacets visible from a direction, G1(ω).
+    ///
+    /// * `w` - Unit direction in the local frame.
+    pub fn g1(&self, w: &Vec3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Returns the fraction of microfacets visible from both directions, G2(ωo, ωi).
+    ///
+    /// * `wo` - Unit direction towards the viewer in the local frame.
+    /// * `wi` - Unit direction towards the light in the local frame.
+    pub fn g(&self, wo: &Vec3<f64>, wi: &Vec3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Sample a microfacet normal from the distribution of normals visible from a direction.
+    ///
+    /// Sampling only the visible normals avoids wasting samples on facets that face away from
+    /// the viewer. Following Heitz ("Sampling the GGX Distribution of Visible Normals", 2018),
+    /// the view direction is stretched into the configuration with α = 1, where the visible
+    /// normals project to a disk whose lower half is squeezed by the view angle:
+    ///
+    ///         . - - .
+    ///       /    |    \       upper half: uniform
+    ///      |-----+-----|
+    ///       \ _ _|_ _ /       lower half: scaled by (1 + cosθo) / 2
+    ///
+    /// A uniform point on that disk is lifted onto the hemisphere, and the normal is unstretched.
+    ///
+    /// * `wo` - Unit direction towards the viewer in the local frame, above the surface.
+    /// * `u1` - Uniform random number in [0, 1).
+    /// * `u2` - Uniform random number in [0, 1).
+    pub fn sample_visible(&self, wo: &Vec3<f64>, u1: f64, u2: f64) -> Vec3<f64> {
+        // stretch the view direction
+        let vh = Vec3::new3(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).normalized();
+
+        // orthonormal basis around it
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0.0 {
+            Vec3::new3(-vh.y(), vh.x(), 0.0) / length_squared.sqrt()
+        } else {
+            Vec3::new3(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(&vh, &t1);
+
+        // uniform point on the disk, with the lower half squeezed
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let p2 = r * phi.sin();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * p2;
+
+        // lift onto the hemisphere and unstretch
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        Vec3::new3(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        )
+        .normalized()
+    }
+
+    /// Returns the density with which `sample_visible` picks a microfacet normal.
+    ///
+    ///     D_ωo(m) = G1(ωo)⋅max(0, ωo⋅m)⋅D(m) / cosθo
+    ///
+    /// * `wo` - Unit direction towards the viewer in the local frame.
+    /// * `m` - Unit microfacet normal in the local frame.
+    pub fn pdf_visible(&self, wo: &Vec3<f64>, m: &Vec3<f64>) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * Vec3::dot(wo, m).max(0.0) * self.d(m) / wo.z()
+    }
+}
+
+/// Returns the Fresnel reflectance of a conductor for unpolarized light.
+///
+/// Metals absorb the light that enters them, which their complex index of refraction η + i⋅k
+/// describes with the extinction coefficient k. The exact reflectance is the average of the
+/// reflectances for light polarized perpendicular (s) and parallel (p) to the plane of incidence:
+///
+///     a² + b² = sqrt((η² - k² - sin²θ)² + 4η²k²)
+///     a       = sqrt((a² + b² + η² - k² - sin²θ) / 2)
+///
+///     Rs = (a² + b² - 2a⋅cosθ + cos²θ) / (a² + b² + 2a⋅cosθ + cos²θ)
+///     Rp = Rs⋅(cos²θ⋅(a² + b²) - 2a⋅cosθ⋅sin²θ + sin⁴θ)
+///            / (cos²θ⋅(a² + b²) + 2a⋅cosθ⋅sin²θ + sin⁴θ)
+///
+/// * `cos_theta` - Cosine of the angle between the incident direction and the normal.
+/// * `eta` - Real part of the index of refraction, relative to the outside medium.
+/// * `k` - Extinction coefficient, relative to the outside medium.
+pub fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos_theta = cos_theta.clamp(0.0, 1.0);
+    let cos2 = cos_theta * cos_theta;
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * a * cos_theta;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rs + rp)
+}
+
+/// Reflect a direction about a normal, both pointing away from the surface.
+///
+/// * `w` - Unit direction.
+/// * `m` - Unit normal.
+pub fn reflect(w: &Vec3<f64>, m: &Vec3<f64>) -> Vec3<f64> {
+    *m * (2.0 * Vec3::dot(w, m)) - *w
+}
+
+/// Returns the Fresnel reflectance of a dielectric interface for unpolarized light.
+///
+/// Light arrives from the medium with index η_i and is partly transmitted into the one with
+/// index η_t. With the relative index η = η_t / η_i and the angle θt given by Snell's law, the
+/// reflectances for both polarizations are:
+///
+///     Rs = (cosθ - η⋅cosθt) / (cosθ + η⋅cosθt)
+///     Rp = (η⋅cosθ - cosθt) / (η⋅cosθ + cosθt)
+///
+/// and the result is (Rs² + Rp²) / 2. Beyond the critical angle, there is no transmitted
+/// direction and all light is reflected (total internal reflection).
+///
+/// * `cos_theta` - Cosine of the angle between the incident direction and the normal.
+/// * `eta` - Relative index of refraction η_t / η_i.
+pub fn fresnel_dielectric(cos_theta: f64, eta: f64) -> f64 {
+    let cos_theta = cos_theta.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_theta * cos_theta) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let rs = (cos_theta - eta * cos_t) / (cos_theta + eta * cos_t);
+    let rp = (eta * cos_theta - cos_t) / (eta * cos_theta + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+/// Refract a direction through a dielectric interface, see `fresnel_dielectric`.
+///
+/// Returns none in case of total internal reflection. Otherwise, the transmitted direction
+/// points away from the surface on the other side:
+///
+///     ωt = -ω / η + (cosθ / η - cosθt)⋅m
+///
+/// * `w` - Unit direction, on the same side as the normal.
+/// * `m` - Unit normal.
+/// * `eta` - Relative index of refraction η_t / η_i.
+pub fn refract(w: &Vec3<f64>, m: &Vec3<f64>, eta: f64) -> Option<Vec3<f64>> {
+    let cos_theta = Vec3::dot(w, m);
+    let sin2_t = (1.0 - cos_theta * cos_theta).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-*w / eta + *m * (cos_theta / eta - cos_t))
+}
+
+/// Returns the microfacet normal which refracts ω into ωt, see `refract`, facing up.
+///
+/// This is the generalized half vector m ∝ ω + η⋅ωt. None is returned if no microfacet refracts
+/// ω into ωt, i.e. if both directions end up on the same side of it.
+///
+/// * `w` - Unit direction above the surface in the local frame.
+/// * `wt` - Unit direction below the surface in the local frame.
+/// * `eta` - Relative index of refraction η_t / η_i.
+pub fn transmission_normal(w: &Vec3<f64>, wt: &Vec3<f64>, eta: f64) -> Option<Vec3<f64>> {
+    let m = *w + *wt * eta;
+    if m.length_squared() < 1e-12 {
+        return None;
+    }
+    let m = m.normalized();
+    let m = if m.z() < 0.0 { -m } else { m };
+    if Vec3::dot(w, &m) <= 0.0 || Vec3::dot(wt, &m) >= 0.0 {
+        return None;
+    }
+    Some(m)
+}
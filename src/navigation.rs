@@ -0,0 +1,306 @@
+use std::fs;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::interrupt;
+use crate::preview::Preview;
+use crate::ray::Point3;
+use crate::render::Renderer;
+use crate::scene::Scene;
+use crate::tile::Tile;
+use crate::vec::Vec3;
+
+/// Change of the view requested in the preview window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Circle around the target, by angles in degrees to the right and upwards.
+    Orbit(f64, f64),
+    /// Move camera and target sideways and upwards, in fractions of the view height.
+    Pan(f64, f64),
+    /// Scale the distance to the target.
+    Dolly(f64),
+    /// Scale the field of view.
+    Zoom(f64),
+    /// Scale the aperture.
+    Aperture(f64),
+    /// Scale the focus distance.
+    Focus(f64),
+    /// Focus on whatever is visible at an image position, given like the camera's u and v.
+    FocusAt(f64, f64),
+    /// Go back to the initial view.
+    Reset,
+    /// Print the camera parameters.
+    Print,
+    /// Save the camera parameters.
+    Save,
+}
+
+/// Orbit camera controller for framing shots interactively.
+///
+/// The camera circles around its target, its position is kept in spherical coordinates:
+///
+///     lookfrom = lookat + distance⋅(cos(pitch)⋅sin(yaw), sin(pitch), cos(pitch)⋅cos(yaw))
+///
+/// The y axis points up, like in all our scenes. Pitch stays below ±90°, so the camera never
+/// flips over the pole.
+#[derive(Debug, Clone, Copy)]
+pub struct Navigator {
+    target: Point3<f64>,
+    distance: f64,
+    // angles in radians
+    yaw: f64,
+    pitch: f64,
+    // vertical field of view in degrees
+    vfov: f64,
+    aperture: f64,
+    focus_distance: f64,
+}
+
+impl Navigator {
+    /// Create a navigator starting at the view of a camera.
+    ///
+    /// * `camera` - Initial camera.
+    pub fn new(camera: &Camera) -> Self {
+        let (lookfrom, lookat) = camera.placement();
+        let offset = lookfrom - lookat;
+        let distance = offset.length().max(1e-6);
+        let (aperture, focus_distance) = camera.lens_settings();
+
+        Navigator {
+            target: lookat,
+            distance,
+            yaw: offset.x().atan2(offset.z()),
+            pitch: (offset.y() / distance).clamp(-1.0, 1.0).asin(),
+            vfov: camera.field_of_view(),
+            aperture,
+            focus_distance,
+        }
+    }
+
+    /// Returns the camera position.
+    pub fn position(&self) -> Point3<f64> {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        self.target
+            + Vec3::new3(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw) * self.distance
+    }
+
+    /// Returns the unit vector in viewing direction.
+    pub fn forward(&self) -> Vec3<f64> {
+        (self.target - self.position()) / self.distance
+    }
+
+    /// Sets the focus distance.
+    ///
+    /// * `distance` - Distance from the camera to the focus plane.
+    pub fn set_focus(&mut self, distance: f64) {
+        self.focus_distance = distance.max(1e-3);
+    }
+
+    /// Apply an action which changes the view.
+    ///
+    /// Returns whether the view changed. Actions which need more than the camera parameters,
+    /// e.g. focusing on an object, are left to the caller.
+    ///
+    /// * `action` - Requested change.
+    /// * `initial` - Navigator to go back to on reset.
+    pub fn act(&mut self, action: Action, initial: &Navigator) -> bool {
+        match action {
+            Action::Orbit(right, up) => {
+                self.yaw += right.to_radians();
+                let limit = 89.0f64.to_radians();
+                self.pitch = (self.pitch + up.to_radians()).clamp(-limit, limit);
+            }
+            Action::Pan(right, up) => {
+                // the view height at the target distance
+                let height = 2.0 * self.distance * (self.vfov.to_radians() / 2.0).tan();
+                let forward = self.forward();
+                let side = Vec3::cross(&forward, &Vec3::new3(0.0, 1.0, 0.0)).normalized();
+                let above = Vec3::cross(&side, &forward);
+                self.target = self.target + (side * right + above * up) * height;
+            }
+            Action::Dolly(factor) => {
+                // keep the target in focus if it was before
+                let focused = (self.focus_distance - self.distance).abs() < 1e-6 * self.distance;
+                self.distance = (self.distance * factor).max(1e-3);
+                if focused {
+                    self.focus_distance = self.distance;
+                }
+            }
+            Action::Zoom(factor) => self.vfov = (self.vfov * factor).clamp(1.0, 170.0),
+            Action::Aperture(factor) => {
+                // opening up from a pinhole starts at a small fraction of the focus distance
+                let smallest = 1e-3 * self.focus_distance;
+                self.aperture = if self.aperture < smallest {
+                    if factor > 1.0 {
+                        smallest
+                    } else {
+                        0.0
+                    }
+                } else {
+                    self.aperture * factor
+                };
+            }
+            Action::Focus(factor) => self.set_focus(self.focus_distance * factor),
+            Action::Reset => *self = *initial,
+            Action::FocusAt(_, _) | Action::Print | Action::Save => return false,
+        }
+        true
+    }
+
+    /// Returns a camera with the current view.
+    ///
+    /// * `camera` - Camera with all other settings.
+    pub fn apply(&self, camera: Camera) -> Camera {
+        camera
+            .lookfrom(self.position())
+            .lookat(self.target)
+            .vfov(self.vfov)
+            .lens(self.aperture, self.focus_distance)
+    }
+
+    /// Returns the view as a key of a camera path file, see `CameraPath::load`.
+    ///
+    /// * `time` - Time of the key in seconds.
+    pub fn key(&self, time: f64) -> String {
+        let position = self.position();
+        format!(
+            "{} {:.4} {:.4} {:.4} {:.4} {:.4} {:.4} {:.3} {:.4}",
+            time,
+            position.x(),
+            position.y(),
+            position.z(),
+            self.target.x(),
+            self.target.y(),
+            self.target.z(),
+            self.vfov,
+            self.focus_distance
+        )
+    }
+
+    /// Returns the aperture diameter, which camera path files do not hold.
+    pub fn aperture(&self) -> f64 {
+        self.aperture
+    }
+}
+
+/// Let the user frame a shot in the preview window, until the window is closed.
+///
+/// The scene is rendered progressively from the current view, see `Preview` for the controls.
+/// Whenever the view changes, the render starts over.
+///
+/// * `scene` - Scene to explore, starting with its camera.
+/// * `tiles` - Tiles covering the image, in the order they should be rendered.
+/// * `preview` - Window showing the image and taking the input.
+/// * `pass_samples` - Largest number of samples per pixel added in one pass.
+/// * `camera_out` - File the saved cameras are appended to, as keys of a camera path.
+pub fn explore(
+    scene: &Scene,
+    tiles: &[Tile],
+    preview: &mut Preview,
+    pass_samples: usize,
+    camera_out: &str,
+) -> io::Result<()> {
+    let (world, initial_camera) = scene.build(scene.time)?;
+    let initial = Navigator::new(&initial_camera);
+    let mut navigator = initial;
+    let mut saved = 0;
+    let mut film = Film::new(scene.width, scene.height, scene.filter);
+    let mut done = 0;
+    while !interrupt::interrupted() {
+        let camera = navigator.apply(initial_camera.clone());
+
+        // Render passes of growing size, so the image shows up quickly after every change.
+        // Any input stops the pass, an unchanged view continues where the pass stopped.
+        let mut actions = Vec::new();
+        if done < scene.samples {
+            let target = (done + done.clamp(1, pass_samples.max(1))).min(scene.samples);
+            let cancel = AtomicBool::new(false);
+            let renderer = Renderer {
+                world: &world,
+                camera: &camera,
+                width: scene.width,
+                height: scene.height,
+                max_depth: scene.max_depth,
+                seed: scene.seed,
+                spectral: scene.spectral,
+                cancel: Some(&cancel),
+                aovs: None,
+            };
+            renderer.render(
+                &film,
+                tiles,
+                target,
+                target,
+                |tile, _| {
+                    preview.update(&film, tile);
+                    actions.extend(preview.actions());
+                    if !actions.is_empty() {
+                        cancel.store(true, Ordering::Relaxed);
+                    }
+                },
+                |samples| {
+                    done = samples;
+                    Ok(())
+                },
+            )?;
+        } else {
+            thread::sleep(Duration::from_millis(15));
+            preview.refresh();
+            actions = preview.actions();
+        }
+
+        let mut changed = false;
+        for action in actions {
+            match action {
+                Action::FocusAt(u, v) => {
+                    // trace the chief ray through that point, the focus distance is measured
+                    // along the viewing direction
+                    let (aperture, focus) = camera.lens_settings();
+                    let pinhole = camera.clone().lens(0.0, focus);
+                    let hit = pinhole
+                        .ray(u, v)
+                        .and_then(|ray| world.trace(&ray, 0.001, f64::INFINITY));
+                    if let Some((rec, _)) = hit {
+                        let offset = rec.point - navigator.position();
+                        navigator.set_focus(Vec3::dot(&offset, &navigator.forward()));
+                        changed = aperture > 0.0;
+                    }
+                }
+                Action::Print => {
+                    eprintln!(
+                        "\n>> Camera: {}  # aperture {}",
+                        navigator.key(0.0),
+                        navigator.aperture()
+                    );
+                }
+                Action::Save => {
+                    let mut file = fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(camera_out)?;
+                    // one second between the keys
+                    writeln!(
+                        file,
+                        "{}  # aperture {}",
+                        navigator.key(saved as f64),
+                        navigator.aperture()
+                    )?;
+                    eprintln!("\n>> Saved camera key {} to {}", saved, camera_out);
+                    saved += 1;
+                }
+                action => changed |= navigator.act(action, &initial),
+            }
+        }
+        if changed {
+            film = Film::new(scene.width, scene.height, scene.filter);
+            done = 0;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::vec::Vec3;
+
+/// Orthonormal basis.
+///
+/// Three mutually orthogonal unit vectors u, v and w. We use this to express directions sampled
+/// around the z axis (e.g. inside a cone) relative to an arbitrary axis w in world space.
+pub struct Onb {
+    u: Vec3<f64>,
+    v: Vec3<f64>,
+    w: Vec3<f64>,
+}
+
+impl Onb {
+    /// Build an orthonormal basis around a given axis.
+    ///
+    /// * `w` - Axis which becomes the local z axis, does not need to be normalized.
+    pub fn new(w: &Vec3<f64>) -> Self {
+        let w = w.normalized();
+        // pick a helper vector which is guaranteed not to be parallel to w
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new3(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new3(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::cross(&w, &a).normalized();
+        let u = Vec3::cross(&w, &v);
+
+        Onb { u, v, w }
+    }
+
+    /// Build an orthonormal basis around an axis, with u pointing along a tangent direction.
+    ///
+    /// The tangent is projected onto the plane perpendicular to w. If it is (almost) parallel to
+    /// w, an arbitrary u is picked like in `new`.
+    ///
+    /// * `w` - Axis which becomes the local z axis, does not need to be normalized.
+    /// * `tangent` - Direction which becomes the local x axis, does not need to be normalized.
+    pub fn from_tangent(w: &Vec3<f64>, tangent: &Vec3<f64>) -> Self {
+        let w = w.normalized();
+        let u = *tangent - w * Vec3::dot(tangent, &w);
+        if u.length_squared() < 1e-12 {
+            return Onb::new(&w);
+        }
+        let u = u.normalized();
+        let v = Vec3::cross(&w, &u);
+
+        Onb { u, v, w }
+    }
+
+    /// Transform a vector from local coordinates into world space.
+    ///
+    /// * `a` - Vector in local coordinates (x/y/z along u/v/w).
+    pub fn local(&self, a: &Vec3<f64>) -> Vec3<f64> {
+        self.u * a.x() + self.v * a.y() + self.w * a.z()
+    }
+
+    /// Transform a vector from world space into local coordinates, the inverse of `local`.
+    ///
+    /// * `a` - Vector in world space.
+    pub fn project(&self, a: &Vec3<f64>) -> Vec3<f64> {
+        Vec3::new3(
+            Vec3::dot(a, &self.u),
+            Vec3::dot(a, &self.v),
+            Vec3::dot(a, &self.w),
+        )
+    }
+}
@@ -0,0 +1,96 @@
+use std::io::{self, Write};
+
+use crate::color::{self, Color};
+use crate::ppm::Image;
+
+/// Returns the CRC-32 (ISO 3309) of some bytes, as used for PNG chunks.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Returns the Adler-32 checksum of some bytes, as used by zlib.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        // 5552 is the largest block size for which the sums cannot overflow
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Write a PNG chunk: length, type, data and the CRC of type and data.
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut crc_data = Vec::with_capacity(4 + data.len());
+    crc_data.extend_from_slice(kind);
+    crc_data.extend_from_slice(data);
+    writer.write_all(&crc_data)?;
+    writer.write_all(&crc32(&crc_data).to_be_bytes())
+}
+
+/// Wrap data into a zlib stream without compression.
+///
+/// Deflate allows "stored" blocks of up to 65535 bytes, which are copied verbatim. The files are
+/// larger than necessary, but the encoder stays trivial.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = Vec::with_capacity(data.len() + data.len() / 65535 * 5 + 16);
+    // CMF/FLG: deflate with a 32K window, no preset dictionary, fastest "compression"
+    stream.extend_from_slice(&[0x78, 0x01]);
+
+    let mut blocks = data.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        // an empty final block
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// Write an image in PNG format (8 bit RGB).
+///
+/// * `writer` - Destination of the image data.
+/// * `img` - Image to write, the first row is the bottom row.
+pub fn write<W: Write>(writer: &mut W, img: &Image<Color>) -> io::Result<()> {
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(img.width() as u32).to_be_bytes());
+    header.extend_from_slice(&(img.height() as u32).to_be_bytes());
+    // bit depth 8, color type 2 (RGB), deflate, adaptive filtering, no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+
+    // every scanline starts with its filter type, we always use 0 (none)
+    let mut data = Vec::with_capacity(img.height() * (1 + img.width() * 3));
+    for j in (0..img.height()).rev() {
+        data.push(0);
+        for i in 0..img.width() {
+            data.extend_from_slice(&color::color_to_rgb8(&img[j][i]));
+        }
+    }
+    write_chunk(writer, b"IDAT", &zlib_stored(&data))?;
+    write_chunk(writer, b"IEND", &[])?;
+    writer.flush()
+}
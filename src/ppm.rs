@@ -1,4 +1,9 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
 use std::ops::{Index, IndexMut};
+use std::path::Path;
+
+use crate::color::{self, Color};
 
 /// A simple PPM image struct.
 ///
@@ -51,3 +56,187 @@ impl<T> IndexMut<usize> for Image<T> {
         &mut self.pixels[offset..offset + self.width]
     }
 }
+
+/// Largest width or height of an image.
+const MAX_DIMENSION: usize = 1 << 15;
+
+/// Largest number of pixels of an image, e.g. a 16k × 8k environment map.
+const MAX_PIXELS: usize = 1 << 27;
+
+/// Check the dimensions declared in an image header, before allocating anything for the pixels.
+///
+/// Malformed or hostile files may declare an empty image, which leaves nothing to sample, or a
+/// huge one, which would exhaust the memory. All image loaders check their headers with this.
+///
+/// * `width` - Declared width in pixels.
+/// * `height` - Declared height in pixels.
+pub fn check_dimensions(width: usize, height: usize) -> io::Result<()> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    if width == 0 || height == 0 {
+        return Err(invalid("empty image"));
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION || width * height > MAX_PIXELS {
+        return Err(invalid("image too large"));
+    }
+    Ok(())
+}
+
+/// Read the next whitespace separated header token, skipping comments.
+fn read_token<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut token = String::new();
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' => {
+                // comment until the end of the line
+                let mut comment = Vec::new();
+                reader.read_until(b'\n', &mut comment)?;
+            }
+            c if c.is_ascii_whitespace() => {
+                if !token.is_empty() {
+                    return Ok(token);
+                }
+            }
+            c => token.push(c as char),
+        }
+    }
+}
+
+/// Load a PNM (PPM or PGM) image, both ASCII (P2/P3) and binary (P5/P6) encodings.
+///
+/// The channels are converted to linear colors in [0.0, 1.0] by undoing gamma 2, the inverse of
+/// what we do when writing images. The first row of the returned image is the top row of the
+/// picture.
+///
+/// * `path` - Path to the image file.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Image<Color>> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+/// Read a PNM image, see `load`.
+///
+/// * `reader` - Source of the image data.
+pub fn read<R: BufRead>(reader: &mut R) -> io::Result<Image<Color>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let magic = read_token(reader)?;
+    let (channels, binary) = match magic.as_str() {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        _ => return Err(invalid("unsupported PNM format")),
+    };
+    let mut number = || -> io::Result<usize> {
+        read_token(reader)?
+            .parse()
+            .map_err(|_| invalid("bad PNM header"))
+    };
+    let width = number()?;
+    let height = number()?;
+    let max = number()?;
+    if max == 0 || max > 65535 {
+        return Err(invalid("bad PNM maximum value"));
+    }
+    check_dimensions(width, height)?;
+
+    let count = width * height * channels;
+    let values: Vec<usize> = if binary {
+        // samples are one byte wide, or two bytes (big endian) for maximum values above 255
+        let size = if max > 255 { 2 } else { 1 };
+        let mut data = vec![0u8; count * size];
+        reader.read_exact(&mut data)?;
+        data.chunks(size)
+            .map(|chunk| chunk.iter().fold(0, |acc, &b| acc << 8 | b as usize))
+            .collect()
+    } else {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        text.split_whitespace()
+            .take(count)
+            .map(|token| token.parse().map_err(|_| invalid("bad PNM data")))
+            .collect::<io::Result<_>>()?
+    };
+    if values.len() < count {
+        return Err(invalid("truncated PNM data"));
+    }
+
+    let linear = |value: usize| {
+        let x = value as f64 / max as f64;
+        x * x
+    };
+    let mut img = Image::new(width, height, Color::new3(0.0, 0.0, 0.0));
+    for j in 0..height {
+        for i in 0..width {
+            let index = (j * width + i) * channels;
+            img[j][i] = if channels == 3 {
+                Color::new3(
+                    linear(values[index]),
+                    linear(values[index + 1]),
+                    linear(values[index + 2]),
+                )
+            } else {
+                let value = linear(values[index]);
+                Color::new3(value, value, value)
+            };
+        }
+    }
+
+    Ok(img)
+}
+
+/// Write an image in ASCII PPM (P3) format.
+///
+/// * `writer` - Destination of the image data.
+/// * `img` - Image to write, the first row is the bottom row.
+pub fn write<W: Write>(writer: &mut W, img: &Image<Color>) -> io::Result<()> {
+    // print PPM header
+    writeln!(writer, "P3")?;
+    writeln!(writer, "{} {}", img.width(), img.height())?;
+    writeln!(writer, "255")?;
+    // print PPM data
+    for j in (0..img.height()).rev() {
+        for i in 0..img.width() {
+            let color = img[j][i];
+            let rgb8 = color::color_to_rgb8(&color);
+            writeln!(writer, "{} {} {}", rgb8[0], rgb8[1], rgb8[2])?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn rejects_empty_images() {
+        for pnm in &[&b"P5 0 4 255\n"[..], b"P3 4 0 255\n"] {
+            let error = read(&mut Cursor::new(pnm))
+                .err()
+                .expect("malformed image accepted");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn rejects_huge_images_before_allocating() {
+        for pnm in &[&b"P5 4000000000 4000000000 255\n"[..], b"P6 100000 2 255\n"] {
+            let error = read(&mut Cursor::new(pnm))
+                .err()
+                .expect("malformed image accepted");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn reads_a_small_pgm() {
+        let img = read(&mut Cursor::new(&b"P5 2 1 255\n\x00\xff"[..])).unwrap();
+        assert_eq!((img.width(), img.height()), (2, 1));
+        assert_eq!(img[0][0].x(), 0.0);
+        assert_eq!(img[0][1].y(), 1.0);
+    }
+}
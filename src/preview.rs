@@ -0,0 +1,201 @@
+#[cfg(feature = "minifb")]
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+
+#[cfg(feature = "minifb")]
+use crate::color;
+use crate::film::Film;
+#[cfg(feature = "minifb")]
+use crate::interrupt;
+#[cfg(feature = "minifb")]
+use crate::navigation::Action;
+use crate::tile::Tile;
+
+/// Window showing the image while it is being rendered.
+///
+/// The window also takes the input for framing shots interactively:
+///
+///     left drag            orbit            arrow keys   orbit
+///     right/middle drag    pan              A, D, Q, E   pan left, right, down, up
+///     mouse wheel          dolly            W, S         dolly in, out
+///     left click           focus there      +, -         zoom in, out
+///     [, ]                 close/open       ",", "."     focus nearer, farther
+///                          the aperture     R            reset the view
+///     P                    print camera     K            save camera
+///
+/// Without the minifb feature, there is no window and updates do nothing.
+pub struct Preview {
+    #[cfg(feature = "minifb")]
+    window: Window,
+    #[cfg(feature = "minifb")]
+    buffer: Vec<u32>,
+    // mouse position when the button went down and at the last update
+    #[cfg(feature = "minifb")]
+    press: Option<(f32, f32)>,
+    #[cfg(feature = "minifb")]
+    last: Option<(f32, f32)>,
+    // whether the mouse moved since the button went down, so it is no click
+    #[cfg(feature = "minifb")]
+    dragged: bool,
+    width: usize,
+    height: usize,
+}
+
+impl Preview {
+    /// Open the preview window.
+    ///
+    /// * `width` - Image width in pixels.
+    /// * `height` - Image height in pixels.
+    pub fn new(width: usize, height: usize) -> Self {
+        Preview {
+            #[cfg(feature = "minifb")]
+            window: Window::new("Scene", width, height, WindowOptions::default()).unwrap(),
+            #[cfg(feature = "minifb")]
+            buffer: vec![0u32; width * height],
+            #[cfg(feature = "minifb")]
+            press: None,
+            #[cfg(feature = "minifb")]
+            last: None,
+            #[cfg(feature = "minifb")]
+            dragged: false,
+            width,
+            height,
+        }
+    }
+
+    /// Show the current state of a tile.
+    ///
+    /// Closing the window requests an interruption of the render.
+    ///
+    /// * `film` - Film that is being rendered.
+    /// * `tile` - Tile that changed.
+    pub fn update(&mut self, film: &Film, tile: &Tile) {
+        debug_assert!(tile.x + tile.width <= self.width && tile.y + tile.height <= self.height);
+
+        #[cfg(feature = "minifb")]
+        {
+            // update minifb buffer and render it
+            for j in tile.y..tile.y + tile.height {
+                let buffer_offset = (self.height - 1 - j) * self.width;
+                let buffer_row = &mut self.buffer[buffer_offset..buffer_offset + self.width];
+                let scanline = film.row(j);
+                for i in tile.x..tile.x + tile.width {
+                    let rgb8 = color::color_to_rgb8(&scanline[i]);
+                    let (r, g, b) = (rgb8[0] as u32, rgb8[1] as u32, rgb8[2] as u32);
+                    buffer_row[i] = (r << 16) | (g << 8) | b
+                }
+            }
+            self.window
+                .update_with_buffer(&self.buffer, self.width, self.height)
+                .unwrap();
+            if !self.window.is_open() {
+                interrupt::request();
+            }
+        }
+
+        #[cfg(not(feature = "minifb"))]
+        let _ = film;
+    }
+
+    /// Show the window again, which also processes its input events.
+    ///
+    /// Keeps the window responsive while nothing is rendered.
+    #[cfg(feature = "minifb")]
+    pub fn refresh(&mut self) {
+        self.window
+            .update_with_buffer(&self.buffer, self.width, self.height)
+            .unwrap();
+        if !self.window.is_open() {
+            interrupt::request();
+        }
+    }
+
+    /// Returns the actions requested since the last call, see above for the controls.
+    ///
+    /// Input events are processed when the window is updated, so call `update` or `refresh`
+    /// first. Escape closes the window.
+    #[cfg(feature = "minifb")]
+    pub fn actions(&mut self) -> Vec<Action> {
+        let mut actions = Vec::new();
+
+        // Mouse: dragging by the whole window height orbits by 180° or pans by the view
+        // height, a click without moving the mouse focuses
+        let (width, height) = (self.width as f64, self.height as f64);
+        let position = self.window.get_mouse_pos(MouseMode::Clamp);
+        let left = self.window.get_mouse_down(MouseButton::Left);
+        let other = self.window.get_mouse_down(MouseButton::Right)
+            || self.window.get_mouse_down(MouseButton::Middle);
+        match (position, left || other) {
+            (Some((x, y)), true) => {
+                if let (Some(press), Some(last)) = (self.press, self.last) {
+                    let (dx, dy) = ((x - last.0) as f64, (y - last.1) as f64);
+                    if (x - press.0).abs() + (y - press.1).abs() > 3.0 {
+                        self.dragged = true;
+                    }
+                    if self.dragged && (dx != 0.0 || dy != 0.0) {
+                        actions.push(if left {
+                            Action::Orbit(-180.0 * dx / height, 180.0 * dy / height)
+                        } else {
+                            Action::Pan(-dx / height, dy / height)
+                        });
+                    }
+                } else {
+                    self.press = Some((x, y));
+                    self.dragged = false;
+                }
+                self.last = Some((x, y));
+            }
+            (_, false) => {
+                if let (Some((x, y)), false) = (self.press, self.dragged) {
+                    if self.last.is_some() {
+                        // the first row of the window is the top of the image
+                        let u = x as f64 / (width - 1.0);
+                        let v = 1.0 - y as f64 / (height - 1.0);
+                        actions.push(Action::FocusAt(u, v));
+                    }
+                }
+                self.press = None;
+                self.last = None;
+            }
+            (None, true) => {}
+        }
+        if let Some((_, scroll)) = self.window.get_scroll_wheel() {
+            actions.push(Action::Dolly(0.9f64.powf(scroll as f64)));
+        }
+
+        // Keyboard
+        for key in self
+            .window
+            .get_keys_pressed(KeyRepeat::Yes)
+            .unwrap_or_default()
+        {
+            actions.push(match key {
+                Key::Left => Action::Orbit(-5.0, 0.0),
+                Key::Right => Action::Orbit(5.0, 0.0),
+                Key::Up => Action::Orbit(0.0, 5.0),
+                Key::Down => Action::Orbit(0.0, -5.0),
+                Key::A => Action::Pan(-0.05, 0.0),
+                Key::D => Action::Pan(0.05, 0.0),
+                Key::Q => Action::Pan(0.0, -0.05),
+                Key::E => Action::Pan(0.0, 0.05),
+                Key::W => Action::Dolly(0.9),
+                Key::S => Action::Dolly(1.0 / 0.9),
+                Key::Equal | Key::NumPadPlus => Action::Zoom(0.9),
+                Key::Minus | Key::NumPadMinus => Action::Zoom(1.0 / 0.9),
+                Key::LeftBracket => Action::Aperture(1.0 / 1.25),
+                Key::RightBracket => Action::Aperture(1.25),
+                Key::Comma => Action::Focus(0.95),
+                Key::Period => Action::Focus(1.0 / 0.95),
+                Key::R => Action::Reset,
+                Key::P => Action::Print,
+                Key::K => Action::Save,
+                Key::Escape => {
+                    interrupt::request();
+                    continue;
+                }
+                _ => continue,
+            });
+        }
+
+        actions
+    }
+}
@@ -0,0 +1,541 @@
+use std::f64::consts::PI;
+
+use crate::color::{self, Color};
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::medium::Interior;
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend;
+use crate::texture::Texture;
+use crate::vec::Vec3;
+
+/// Smallest perceptual roughness, smoother lobes are too spiky to be evaluated reliably.
+const MIN_ROUGHNESS: f64 = 0.05;
+
+/// Principled material, with the parameters artists know from the Disney BSDF.
+///
+/// Following Burley ("Physically Based Shading at Disney", 2012 and "Extending the Disney BRDF
+/// to a BSDF with Integrated Subsurface Scattering", 2015), a handful of intuitive parameters in
+/// [0, 1] blend between plastic, metal, cloth and glass. The BSDF is a weighted sum of lobes:
+///
+///     f = (1 - metallic)⋅(1 - transmission)⋅(diffuse + sheen)
+///       + (1 - (1 - metallic)⋅transmission)⋅specular
+///       + (1 - metallic)⋅transmission⋅glass
+///       + clearcoat / 4⋅coat
+///
+/// * diffuse: Lambertian with a retro-reflection towards grazing angles on rough surfaces,
+///   sheen adds a tinted rim, e.g. for cloth.
+/// * specular: GGX microfacets with the Schlick Fresnel term. Its color at normal incidence
+///   blends from a tinted 8% ⋅ specular for dielectrics to the base color for metals.
+/// * glass: rough refraction like `RoughDielectric`. Light passing through the surface is
+///   tinted by the square root of the base color, so it is the base color after going in and
+///   out again.
+/// * coat: a colorless layer of lacquer with a long-tailed GTR1 highlight, whose width is set
+///   by the clearcoat gloss.
+///
+/// Every parameter is a `Texture`. Scattered directions are sampled from the lobes as a whole:
+/// a lobe is picked with a probability following its estimated contribution, and the weight of
+/// the sample uses the combined density of all lobes, so no lobe adds noise where another one
+/// is a better fit.
+///
+/// Roughness has a lower limit, perfect mirrors and clear glass are `Metal` and `Dielectric`.
+/// Objects only become media, which light can enter, if a transmission texture is set.
+pub struct Principled {
+    base_color: Box<dyn Texture<Color> + Send + Sync>,
+    metallic: Box<dyn Texture<f64> + Send + Sync>,
+    roughness: Box<dyn Texture<f64> + Send + Sync>,
+    specular: Box<dyn Texture<f64> + Send + Sync>,
+    specular_tint: Box<dyn Texture<f64> + Send + Sync>,
+    sheen: Box<dyn Texture<f64> + Send + Sync>,
+    sheen_tint: Box<dyn Texture<f64> + Send + Sync>,
+    clearcoat: Box<dyn Texture<f64> + Send + Sync>,
+    clearcoat_gloss: Box<dyn Texture<f64> + Send + Sync>,
+    transmission: Box<dyn Texture<f64> + Send + Sync>,
+    anisotropy: Box<dyn Texture<f64> + Send + Sync>,
+    /// Whether light can enter the object, i.e. whether a transmission texture is set.
+    transmissive: bool,
+    /// Index of refraction of the inside.
+    refraction: f64,
+    /// Direction the specular highlight is stretched along.
+    tangent: Vec3<f64>,
+}
+
+impl Principled {
+    /// Create a new principled material, a rough plastic of the given color.
+    ///
+    /// The other parameters start at the Disney defaults: roughness and specular 0.5, sheen tint
+    /// 0.5, clearcoat gloss 1 and everything else zero.
+    ///
+    /// * `base_color` - Diffuse color, or the color of reflections for metals.
+    pub fn new<C>(base_color: C) -> Self
+    where
+        C: Texture<Color> + Send + Sync + 'static,
+    {
+        Principled {
+            base_color: Box::new(base_color),
+            metallic: Box::new(0.0),
+            roughness: Box::new(0.5),
+            specular: Box::new(0.5),
+            specular_tint: Box::new(0.0),
+            sheen: Box::new(0.0),
+            sheen_tint: Box::new(0.5),
+            clearcoat: Box::new(0.0),
+            clearcoat_gloss: Box::new(1.0),
+            transmission: Box::new(0.0),
+            anisotropy: Box::new(0.0),
+            transmissive: false,
+            refraction: 1.5,
+            tangent: Vec3::new3(0.0, 1.0, 0.0),
+        }
+    }
+
+    /// Sets how metallic the surface is, from dielectric (0) to metal (1).
+    pub fn metallic<T>(mut self, metallic: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.metallic = Box::new(metallic);
+        self
+    }
+
+    /// Sets the perceptual roughness of the specular, glass and diffuse lobes.
+    pub fn roughness<T>(mut self, roughness: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.roughness = Box::new(roughness);
+        self
+    }
+
+    /// Sets the strength of the specular reflection of dielectrics, 0.5 is 4% like glass.
+    pub fn specular<T>(mut self, specular: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.specular = Box::new(specular);
+        self
+    }
+
+    /// Sets how much the specular reflection of dielectrics is tinted by the base color.
+    pub fn specular_tint<T>(mut self, specular_tint: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.specular_tint = Box::new(specular_tint);
+        self
+    }
+
+    /// Sets the strength of the sheen, a soft rim of light as seen on cloth.
+    pub fn sheen<T>(mut self, sheen: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.sheen = Box::new(sheen);
+        self
+    }
+
+    /// Sets how much the sheen is tinted by the base color.
+    pub fn sheen_tint<T>(mut self, sheen_tint: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.sheen_tint = Box::new(sheen_tint);
+        self
+    }
+
+    /// Sets the strength of the clearcoat, a second specular layer like car paint.
+    pub fn clearcoat<T>(mut self, clearcoat: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.clearcoat = Box::new(clearcoat);
+        self
+    }
+
+    /// Sets the glossiness of the clearcoat, from satin (0) to gloss (1).
+    pub fn clearcoat_gloss<T>(mut self, clearcoat_gloss: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.clearcoat_gloss = Box::new(clearcoat_gloss);
+        self
+    }
+
+    /// Sets how much light passes through the surface, from opaque (0) to glass (1).
+    ///
+    /// This makes the object a medium, see `Material::interior`.
+    pub fn transmission<T>(mut self, transmission: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.transmission = Box::new(transmission);
+        self.transmissive = true;
+        self
+    }
+
+    /// Sets the anisotropy of the specular and glass lobes, see `Ggx::from_roughness`.
+    pub fn anisotropy<T>(mut self, anisotropy: T) -> Self
+    where
+        T: Texture<f64> + Send + Sync + 'static,
+    {
+        self.anisotropy = Box::new(anisotropy);
+        self
+    }
+
+    /// Sets the index of refraction of the inside, for transmission.
+    ///
+    /// * `refraction` - Index of refraction, e.g. 1.5 for glass.
+    pub fn refraction(mut self, refraction: f64) -> Self {
+        self.refraction = refraction;
+        self
+    }
+
+    /// Sets the axis which gives the direction of anisotropic highlights, see `Conductor`.
+    ///
+    /// * `tangent` - Axis in world space, does not need to be normalized.
+    pub fn tangent(mut self, tangent: Vec3<f64>) -> Self {
+        self.tangent = tangent;
+        self
+    }
+
+    /// Evaluate the textures at a hit point and set up the lobes for a ray.
+    ///
+    /// Returns none if the ray arrives from below the shading frame. Inside a transmissive
+    /// object, only the glass lobe is left: the other ones describe its outside.
+    fn lobes(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<Lobes> {
+        let frame = Onb::from_tangent(&rec.normal, &self.tangent);
+        let wo = frame.project(&-ray.direction().normalized());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let parameter =
+            |texture: &(dyn Texture<f64> + Send + Sync)| texture.value(rec).clamp(0.0, 1.0);
+        let base_color = self.base_color.value(rec);
+        let metallic = parameter(self.metallic.as_ref());
+        let roughness = parameter(self.roughness.as_ref()).max(MIN_ROUGHNESS);
+        let transmission = parameter(self.transmission.as_ref());
+        let white = Color::new3(1.0, 1.0, 1.0);
+
+        // hue and saturation of the base color, without its brightness
+        let luminance = color::luminance(&base_color);
+        let tint = if luminance > 0.0 {
+            base_color / luminance
+        } else {
+            white
+        };
+        let specular_tint = lerp(&white, &tint, parameter(self.specular_tint.as_ref()));
+        let dielectric_specular = specular_tint * (0.08 * parameter(self.specular.as_ref()));
+        let sheen_tint = lerp(&white, &tint, parameter(self.sheen_tint.as_ref()));
+
+        let eta = if rec.front_face {
+            self.refraction / rec.exterior_refraction
+        } else {
+            rec.exterior_refraction / self.refraction
+        };
+        let inside = self.transmissive && !rec.front_face;
+        let (diffuse, specular, glass, clearcoat) = if inside {
+            (0.0, 0.0, 1.0, 0.0)
+        } else {
+            let dielectric = 1.0 - metallic;
+            (
+                dielectric * (1.0 - transmission),
+                1.0 - dielectric * transmission,
+                dielectric * transmission,
+                0.25 * parameter(self.clearcoat.as_ref()),
+            )
+        };
+
+        let mut lobes = Lobes {
+            frame,
+            wo,
+            eta,
+            base_color,
+            roughness,
+            distribution: Ggx::from_roughness(roughness, parameter(self.anisotropy.as_ref())),
+            diffuse,
+            sheen: sheen_tint * parameter(self.sheen.as_ref()),
+            specular,
+            specular_color: lerp(&dielectric_specular, &base_color, metallic),
+            glass,
+            glass_color: Color::new3(
+                base_color.x().max(0.0).sqrt(),
+                base_color.y().max(0.0).sqrt(),
+                base_color.z().max(0.0).sqrt(),
+            ),
+            clearcoat,
+            clearcoat_alpha: 0.1 + (0.001 - 0.1) * parameter(self.clearcoat_gloss.as_ref()),
+            probabilities: [0.0; 4],
+        };
+
+        // pick the lobes by how much light they reflect towards the viewer
+        let weights = [
+            diffuse * (color::luminance(&base_color) + color::luminance(&lobes.sheen)),
+            specular * color::luminance(&schlick(&lobes.specular_color, wo.z())),
+            glass,
+            clearcoat * (0.04 + 0.96 * schlick_weight(wo.z())),
+        ];
+        let total: f64 = weights.iter().sum();
+        if total > 0.0 {
+            lobes.probabilities = weights.map(|weight| weight / total);
+        }
+        Some(lobes)
+    }
+}
+
+/// Material parameters and lobe weights at a hit point, see `Principled`.
+struct Lobes {
+    /// Local frame at the hit point: normal along z, tangent along x.
+    frame: Onb,
+    /// Direction towards the viewer in the local frame.
+    wo: Vec3<f64>,
+    /// Index of refraction below the surface relative to above.
+    eta: f64,
+    base_color: Color,
+    roughness: f64,
+    distribution: Ggx,
+    /// Weight of the diffuse and sheen lobes.
+    diffuse: f64,
+    /// Color of the sheen.
+    sheen: Color,
+    /// Weight of the specular lobe.
+    specular: f64,
+    /// Specular reflectance at normal incidence.
+    specular_color: Color,
+    /// Weight of the glass lobe.
+    glass: f64,
+    /// Tint of light passing through the surface once.
+    glass_color: Color,
+    /// Weight of the clearcoat lobe.
+    clearcoat: f64,
+    /// Roughness of the GTR1 distribution of the clearcoat.
+    clearcoat_alpha: f64,
+    /// Probabilities of sampling the diffuse, specular, glass and clearcoat lobe.
+    probabilities: [f64; 4],
+}
+
+impl Lobes {
+    /// Returns the BSDF value multiplied by the cosine of the light direction.
+    ///
+    /// * `wi` - Unit direction towards the light in the local frame.
+    fn eval(&self, wi: &Vec3<f64>) -> Color {
+        let wo = &self.wo;
+        let black = Color::new3(0.0, 0.0, 0.0);
+        if wi.z() == 0.0 {
+            return black;
+        }
+
+        // only the glass lobe lets light through
+        if wi.z() < 0.0 {
+            if self.glass <= 0.0 {
+                return black;
+            }
+            let m = match microfacet::transmission_normal(wo, wi, self.eta) {
+                Some(m) => m,
+                None => return black,
+            };
+            let (cos_om, cos_im) = (Vec3::dot(wo, &m), Vec3::dot(wi, &m));
+            let denominator = cos_im + cos_om / self.eta;
+            let value = (1.0 - microfacet::fresnel_dielectric(cos_om, self.eta))
+                * self.distribution.d(&m)
+                * self.distribution.g(wo, wi)
+                * (cos_im.abs() * cos_om)
+                / (wo.z() * denominator * denominator * self.eta * self.eta);
+            return self.glass_color * (self.glass * value);
+        }
+
+        let h = (*wo + *wi).normalized();
+        let cos_d = Vec3::dot(wi, &h);
+        let mut f = black;
+        if self.diffuse > 0.0 {
+            // rough surfaces reflect more light back towards grazing angles, smooth ones less
+            let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+            let light = 1.0 + (fd90 - 1.0) * schlick_weight(wi.z());
+            let view = 1.0 + (fd90 - 1.0) * schlick_weight(wo.z());
+            let diffuse =
+                self.base_color * (light * view / PI) + self.sheen * schlick_weight(cos_d);
+            f = f + diffuse * (self.diffuse * wi.z());
+        }
+
+        // the cosine of the light direction cancels out of the microfacet lobes
+        let microfacet = self.distribution.d(&h) * self.distribution.g(wo, wi) / (4.0 * wo.z());
+        if self.specular > 0.0 {
+            f = f + schlick(&self.specular_color, cos_d) * (self.specular * microfacet);
+        }
+        if self.glass > 0.0 {
+            let reflectance = microfacet::fresnel_dielectric(cos_d, self.eta);
+            f = f + Color::new3(1.0, 1.0, 1.0) * (self.glass * reflectance * microfacet);
+        }
+        if self.clearcoat > 0.0 {
+            let coat = gtr1(h.z(), self.clearcoat_alpha)
+                * (0.04 + 0.96 * schlick_weight(cos_d))
+                * Ggx::new(0.25, 0.25).g(wo, wi)
+                / (4.0 * wo.z());
+            f = f + Color::new3(1.0, 1.0, 1.0) * (self.clearcoat * coat);
+        }
+        f
+    }
+
+    /// Returns the density with which `sample` picks a direction, over all lobes.
+    ///
+    /// * `wi` - Unit direction towards the light in the local frame.
+    fn pdf(&self, wi: &Vec3<f64>) -> f64 {
+        let wo = &self.wo;
+        let [diffuse, specular, glass, clearcoat] = self.probabilities;
+
+        if wi.z() < 0.0 {
+            if glass <= 0.0 {
+                return 0.0;
+            }
+            let m = match microfacet::transmission_normal(wo, wi, self.eta) {
+                Some(m) => m,
+                None => return 0.0,
+            };
+            let (cos_om, cos_im) = (Vec3::dot(wo, &m), Vec3::dot(wi, &m));
+            let denominator = cos_im + cos_om / self.eta;
+            return glass
+                * (1.0 - microfacet::fresnel_dielectric(cos_om, self.eta))
+                * self.distribution.pdf_visible(wo, &m)
+                * cos_im.abs()
+                / (denominator * denominator);
+        }
+        if wi.z() == 0.0 {
+            return 0.0;
+        }
+
+        // the reflection maps solid angles around h to four times as large ones around ωi
+        let h = (*wo + *wi).normalized();
+        let cos_oh = Vec3::dot(wo, &h);
+        let reflection = self.distribution.pdf_visible(wo, &h) / (4.0 * cos_oh);
+        diffuse * wi.z() / PI
+            + specular * reflection
+            + glass * microfacet::fresnel_dielectric(cos_oh, self.eta) * reflection
+            + clearcoat * gtr1(h.z(), self.clearcoat_alpha) * h.z() / (4.0 * cos_oh)
+    }
+
+    /// Sample a direction towards the light in the local frame from one of the lobes.
+    ///
+    /// Returns none if the direction ends up on the wrong side of the surface, e.g. a reflection
+    /// off a microfacet pointing below the horizon.
+    fn sample(&self) -> Option<Vec3<f64>> {
+        let wo = &self.wo;
+        let u = rtweekend::random(0.0..1.0);
+        let u1 = rtweekend::random(0.0..1.0);
+        let u2 = rtweekend::random(0.0..1.0);
+        let [diffuse, specular, glass, _] = self.probabilities;
+
+        let wi = if u < diffuse {
+            rtweekend::random_cosine_direction()
+        } else if u < diffuse + specular {
+            microfacet::reflect(wo, &self.distribution.sample_visible(wo, u1, u2))
+        } else if u < diffuse + specular + glass {
+            let m = self.distribution.sample_visible(wo, u1, u2);
+            let reflectance = microfacet::fresnel_dielectric(Vec3::dot(wo, &m), self.eta);
+            match microfacet::refract(wo, &m, self.eta) {
+                // refracted back above the surface: the light would hit another microfacet
+                Some(wi) if rtweekend::random(0.0..1.0) >= reflectance => {
+                    return (wi.z() < 0.0).then_some(wi);
+                }
+                _ => microfacet::reflect(wo, &m),
+            }
+        } else {
+            microfacet::reflect(wo, &sample_gtr1(self.clearcoat_alpha, u1, u2))
+        };
+        (wi.z() > 0.0).then_some(wi)
+    }
+}
+
+impl Material<f64> for Principled {
+    fn scatter(&self, ray: &Ray<f64>, rec: &HitRecord<f64>) -> Option<ScatterRecord<f64>> {
+        let lobes = self.lobes(ray, rec)?;
+        let wi = lobes.sample()?;
+        let pdf = lobes.pdf(&wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(ScatterRecord {
+            ray: Ray::new(rec.point, lobes.frame.local(&wi)),
+            attenuation: lobes.eval(&wi) / pdf,
+            pdf: Some(pdf),
+        })
+    }
+
+    fn eval(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> Color {
+        match self.lobes(ray, rec) {
+            Some(lobes) => lobes.eval(&lobes.frame.project(direction)),
+            None => Color::new3(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn pdf(&self, ray: &Ray<f64>, rec: &HitRecord<f64>, direction: &Vec3<f64>) -> f64 {
+        match self.lobes(ray, rec) {
+            Some(lobes) => lobes.pdf(&lobes.frame.project(direction)),
+            None => 0.0,
+        }
+    }
+
+    fn albedo(&self, rec: &HitRecord<f64>) -> Color {
+        self.base_color.value(rec)
+    }
+
+    fn interior(&self) -> Option<Interior> {
+        self.transmissive.then_some(Interior {
+            refraction: self.refraction,
+            absorption: Color::new3(0.0, 0.0, 0.0),
+            priority: 0,
+        })
+    }
+}
+
+/// Linear interpolation between two colors.
+fn lerp(a: &Color, b: &Color, t: f64) -> Color {
+    *a * (1.0 - t) + *b * t
+}
+
+/// Returns Schlick's Fresnel weight (1 - cosθ)⁵.
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+/// Returns Schlick's approximation of the Fresnel reflectance, F0 + (1 - F0)⋅(1 - cosθ)⁵.
+///
+/// * `f0` - Reflectance at normal incidence.
+/// * `cos_theta` - Cosine of the angle between the direction and the microfacet normal.
+fn schlick(f0: &Color, cos_theta: f64) -> Color {
+    lerp(f0, &Color::new3(1.0, 1.0, 1.0), schlick_weight(cos_theta))
+}
+
+/// Returns the GTR1 (Berry) distribution of microfacet normals used for the clearcoat.
+///
+///     D(m) = (α² - 1) / (π⋅ln(α²)⋅(1 + (α² - 1)⋅cos²θm))
+///
+/// Its tails are longer than the ones of GGX, which gives the hazy glow around the highlight.
+///
+/// * `cos_theta` - Cosine of the angle between the microfacet normal and the surface normal.
+/// * `alpha` - Roughness in (0, 1).
+fn gtr1(cos_theta: f64, alpha: f64) -> f64 {
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_theta * cos_theta))
+}
+
+/// Sample a microfacet normal from the GTR1 distribution with density D(m)⋅cosθm.
+///
+///     cos²θm = (1 - α²^(1 - ξ1)) / (1 - α²)
+///     φ = 2π⋅ξ2
+///
+/// * `alpha` - Roughness in (0, 1).
+/// * `u1` - Uniform random number in [0, 1).
+/// * `u2` - Uniform random number in [0, 1).
+fn sample_gtr1(alpha: f64, u1: f64, u2: f64) -> Vec3<f64> {
+    let a2 = alpha * alpha;
+    let cos2 = ((1.0 - a2.powf(1.0 - u1)) / (1.0 - a2)).clamp(0.0, 1.0);
+    let sin = (1.0 - cos2).sqrt();
+    let phi = 2.0 * PI * u2;
+    Vec3::new3(sin * phi.cos(), sin * phi.sin(), cos2.sqrt())
+}
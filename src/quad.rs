@@ -0,0 +1,116 @@
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::{Point3, Ray};
+use crate::rtweekend;
+use crate::vec::Vec3;
+
+/// A planar quadrilateral (parallelogram).
+///
+/// The quad is spanned by a corner point Q and two edge vectors u and v:
+///
+///       Q + v ---------- Q + u + v
+///         /             /
+///        /             /
+///       Q ---------- Q + u
+///
+/// Every point P on the quad can be written as P = Q + α⋅u + β⋅v with 0 <= α, β <= 1.
+/// The outward normal is given by the right hand rule, i.e. it points into the direction of u x v.
+pub struct Quad<T: Copy> {
+    q: Point3<T>,
+    u: Vec3<T>,
+    v: Vec3<T>,
+    // unit normal of the plane containing the quad
+    normal: Vec3<T>,
+    // constant D of the plane equation n⋅P = D
+    d: T,
+    // n / (n⋅n) with n = u x v, used to compute the planar coordinates of a hit point
+    w: Vec3<T>,
+    // surface area
+    area: T,
+}
+
+impl Quad<f64> {
+    /// Create a new quad from a corner and two edges.
+    ///
+    /// * `q` - Corner point.
+    /// * `u` - First edge, starting at the corner.
+    /// * `v` - Second edge, starting at the corner.
+    pub fn new(q: Point3<f64>, u: Vec3<f64>, v: Vec3<f64>) -> Self {
+        let n = Vec3::cross(&u, &v);
+        let normal = n.normalized();
+        let d = Vec3::dot(&normal, &q);
+        let w = n / Vec3::dot(&n, &n);
+        let area = n.length();
+
+        Quad {
+            q,
+            u,
+            v,
+            normal,
+            d,
+            w,
+            area,
+        }
+    }
+}
+
+impl Hittable<f64> for Quad<f64> {
+    fn is_hit(&self, ray: &Ray<f64>, t_min: f64, t_max: f64) -> Option<HitRecord<f64>> {
+        // Plugging the ray equation P(t) = A + t*b into the plane equation n⋅P = D yields:
+        //
+        //      t = (D - n⋅A) / (n⋅b)
+        //
+        // If n⋅b is zero, the ray is parallel to the plane and never hits it.
+        let denom = Vec3::dot(&self.normal, &ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - Vec3::dot(&self.normal, &ray.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // check whether the hit point on the plane lies inside the quad by computing its planar
+        // coordinates α and β
+        let point = ray.at(t);
+        let planar = point - self.q;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&planar, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(HitRecord::new(point, self.normal, t, ray))
+    }
+
+    fn sample(&self, origin: &Point3<f64>) -> Option<(Vec3<f64>, f64)> {
+        // pick a uniformly distributed point on the quad
+        let point =
+            self.q + self.u * rtweekend::random(0.0..1.0) + self.v * rtweekend::random(0.0..1.0);
+        let direction = point - *origin;
+        let distance_squared = direction.length_squared();
+        let direction = direction.normalized();
+
+        // Convert the density from area measure (1 / A) to solid angle measure. A small patch dA
+        // at distance d whose normal is tilted by the angle θ against the direction covers the
+        // solid angle dω = dA⋅cosθ / d².
+        let cosine = Vec3::dot(&direction, &self.normal).abs();
+        if cosine < 1e-8 {
+            return None;
+        }
+
+        Some((direction, distance_squared / (cosine * self.area)))
+    }
+
+    fn pdf(&self, origin: &Point3<f64>, direction: &Vec3<f64>) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        let rec = match self.is_hit(&ray, 0.001, f64::MAX) {
+            Some(rec) => rec,
+            None => return 0.0,
+        };
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (Vec3::dot(direction, &self.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+}
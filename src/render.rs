@@ -0,0 +1,416 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::aov::{AovFilm, AovSample};
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::film::Film;
+use crate::interrupt;
+use crate::medium::MediumStack;
+use crate::ray::Ray;
+use crate::rtweekend;
+use crate::spectrum::Wavelengths;
+use crate::tile::{self, Tile};
+use crate::vec::Vec3;
+use crate::world::World;
+
+/// Power heuristic for multiple importance sampling (MIS) with one sample per strategy.
+///
+/// When a light contribution can be found by two sampling strategies (sampling the BSDF or
+/// sampling the light), we add both estimates but weight each by:
+///
+///     w = p_f² / (p_f² + p_g²)
+///
+/// where p_f is the density of the strategy that produced the sample and p_g the density the other
+/// strategy would have had for the same direction. The weights of both strategies sum up to one,
+/// so the result stays unbiased, and each strategy dominates where it has low variance: BSDF
+/// sampling for glossy surfaces and large lights, light sampling for diffuse surfaces and small
+/// lights.
+///
+/// * `pdf_f` - Density of the strategy that was used.
+/// * `pdf_g` - Density of the other strategy.
+pub fn power_heuristic(pdf_f: f64, pdf_g: f64) -> f64 {
+    let f = pdf_f * pdf_f;
+    let g = pdf_g * pdf_g;
+    if f + g <= 0.0 {
+        return 0.0;
+    }
+    f / (f + g)
+}
+
+/// Light arriving along a ray, split by how often it was scattered on its way.
+#[derive(Debug, Clone, Copy)]
+pub struct PathLight {
+    /// Light emitted by the surface the ray hits, or by the background if the ray escapes.
+    pub emitted: Color,
+    /// Light coming straight from a light source, scattered once by the surface.
+    pub direct: Color,
+    /// Light scattered more than once.
+    pub indirect: Color,
+}
+
+impl PathLight {
+    /// Returns all light arriving along the ray.
+    pub fn total(&self) -> Color {
+        self.emitted + self.direct + self.indirect
+    }
+
+    /// Convert light carried at the wavelengths of a path into RGB.
+    ///
+    /// * `wavelengths` - Wavelengths of the path, see `Wavelengths::to_rgb`.
+    pub fn to_rgb(self, wavelengths: &Wavelengths) -> PathLight {
+        PathLight {
+            emitted: wavelengths.to_rgb(&self.emitted),
+            direct: wavelengths.to_rgb(&self.direct),
+            indirect: wavelengths.to_rgb(&self.indirect),
+        }
+    }
+}
+
+/// Compute the light arriving along a ray, split like `PathLight`.
+///
+/// The light of a path is added up from the end: whatever a scattered ray finds is emitted light
+/// from the point of view of that ray, direct light for the surface it was scattered by, and
+/// indirect light for all surfaces before.
+///
+/// Rays inside dielectrics travel through their media, which absorb light on the way. Surfaces
+/// of media with a lower priority than the current one are passed through without a bounce.
+///
+/// Spectral paths carry the light at their wavelengths instead of RGB. The RGB colors of the
+/// scene are converted to spectra where they meet the path.
+///
+/// * `ray` - Ray to trace.
+/// * `world` - Scene to trace the ray in.
+/// * `depth` - Remaining number of bounces.
+/// * `pdf` - Density with which the ray direction was sampled from the previous BSDF, none for
+///   camera rays and specular reflections.
+/// * `media` - Media the ray starts in.
+/// * `wavelengths` - Wavelengths of a spectral path, none for RGB.
+pub fn path_light(
+    ray: &Ray<f64>,
+    world: &World<f64>,
+    depth: usize,
+    pdf: Option<f64>,
+    media: &MediumStack,
+    wavelengths: Option<&Wavelengths>,
+) -> PathLight {
+    let black = Color::new3(0.0, 0.0, 0.0);
+    if depth == 0 {
+        // ray bounce limit exceeded, no more light is reflected
+        return PathLight {
+            emitted: black,
+            direct: black,
+            indirect: black,
+        };
+    }
+
+    // Fix shadow acne: due to floating point approximation, some of the reflected rays hit the
+    // object they are reflecting off of not at exactly t = 0, but e.g. t = -0.000001 or
+    // t = 0.000001. Ignore hits near zero to work around this.
+    let t_min = 0.001;
+    let t_max = std::f64::MAX;
+
+    let mut wavelengths = wavelengths.copied();
+    let spectrum = move |color: Color| match &wavelengths {
+        Some(wavelengths) => wavelengths.spectrum(&color),
+        None => color,
+    };
+
+    // Skip the false hits, the ray keeps its origin so the light densities stay the same. Each
+    // stretch of the ray is absorbed by the medium it passes through.
+    let mut media = media.clone();
+    let mut transmittance = Color::new3(1.0, 1.0, 1.0);
+    let mut t_start = 0.0;
+    let mut hit = world.hit(ray, t_min, t_max);
+    while let Some((rec, object)) = &hit {
+        let id = world.material_id(*object);
+        match world.material(*object).interior() {
+            Some(interior) if media.is_false_hit(id, &interior, rec.front_face) => {
+                let t = rec.t;
+                transmittance = transmittance
+                    * spectrum(media.transmittance((t - t_start) * ray.direction().length()));
+                media.cross(id, &interior, rec.front_face);
+                t_start = t;
+                hit = world.hit(ray, t + t_min, t_max);
+            }
+            _ => break,
+        }
+    }
+
+    if let Some((mut rec, object)) = hit {
+        let material = world.material(object);
+        let id = world.material_id(object);
+        let interior = material.interior();
+        rec.wavelengths = wavelengths;
+        // the BSDF of spectral materials is already given at the wavelengths
+        let reflectance = |color: Color| {
+            if material.is_spectral() {
+                color
+            } else {
+                spectrum(color)
+            }
+        };
+        // light dispersed here only follows the path at the hero wavelength
+        let dispersed = material.is_dispersive()
+            && wavelengths
+                .as_mut()
+                .is_some_and(|wavelengths| wavelengths.terminate_secondary());
+        // the medium on the far side of the surface, for rays passing through it
+        let mut crossed = media.clone();
+        if let Some(interior) = &interior {
+            rec.exterior_refraction = media.exterior_refraction(id, rec.front_face);
+            crossed.cross(id, interior, rec.front_face);
+        }
+        let transmitted =
+            |direction: &Vec3<f64>| interior.is_some() && Vec3::dot(direction, &rec.normal) < 0.0;
+
+        // Emitted light found by BSDF sampling. If the direction could also have been produced by
+        // light sampling at the previous bounce, weight it accordingly. Specular bounces and
+        // camera rays cannot sample lights, so they see the full emission.
+        let mut emitted = spectrum(material.emitted(ray, &rec));
+        if let Some(pdf) = pdf {
+            let light_pdf = world.light_pdf(&ray.origin(), &ray.direction(), Some(object));
+            emitted = emitted * power_heuristic(pdf, light_pdf);
+        }
+
+        // Next-event estimation: small light sources are rarely hit by randomly scattered rays,
+        // so we additionally sample a point on a light and trace a shadow ray towards it. This
+        // does not help perfect mirrors: they reflect light from exactly one direction, which a
+        // light sample will never match, so their BSDF evaluates to black.
+        let mut direct = black;
+        if let Some(sample) = world.sample_light(&rec.point) {
+            let f = material.eval(ray, &rec, &sample.direction);
+            if sample.pdf > 0.0 && (f.x() > 0.0 || f.y() > 0.0 || f.z() > 0.0) {
+                let shadow = Ray::new(rec.point, sample.direction);
+                let occluded = world
+                    .trace(&shadow, t_min, sample.distance - t_min)
+                    .is_some();
+                if !occluded {
+                    // delta lights cannot be found by BSDF sampling, so they get the full weight
+                    let weight = if sample.delta {
+                        1.0
+                    } else {
+                        power_heuristic(sample.pdf, material.pdf(ray, &rec, &sample.direction))
+                    };
+                    let shadow_media = if transmitted(&sample.direction) {
+                        &crossed
+                    } else {
+                        &media
+                    };
+                    direct = reflectance(f)
+                        * spectrum(sample.radiance)
+                        * spectrum(shadow_media.transmittance(sample.distance))
+                        * (weight / sample.pdf);
+                }
+            }
+        }
+
+        // scatter the light ray, consider attenuation of the object
+        let mut indirect = black;
+        if let Some(scatter) = material.scatter(ray, &rec) {
+            let scatter_media = if transmitted(&scatter.ray.direction()) {
+                &crossed
+            } else {
+                &media
+            };
+            let light = path_light(
+                &scatter.ray,
+                world,
+                depth - 1,
+                scatter.pdf,
+                scatter_media,
+                wavelengths.as_ref(),
+            );
+            let attenuation = reflectance(scatter.attenuation);
+            direct = direct + light.emitted * attenuation;
+            indirect = (light.direct + light.indirect) * attenuation;
+        }
+        if dispersed {
+            direct = Wavelengths::keep_hero(&direct);
+            indirect = Wavelengths::keep_hero(&indirect);
+        }
+
+        // absorption on the way to the surface
+        let transmittance = transmittance
+            * spectrum(media.transmittance((rec.t - t_start) * ray.direction().length()));
+        return PathLight {
+            emitted: emitted * transmittance,
+            direct: direct * transmittance,
+            indirect: indirect * transmittance,
+        };
+    }
+
+    // light from infinitely distant sources such as the sun, weighted like emissive objects
+    let mut escaped = world.escaped(&ray.direction());
+    if let Some(pdf) = pdf {
+        let light_pdf = world.light_pdf(&ray.origin(), &ray.direction(), None);
+        escaped = escaped * power_heuristic(pdf, light_pdf);
+    }
+
+    let emitted = match world.background() {
+        Some(background) => escaped + background,
+        None => {
+            // scale the ray direction to unit length (so -1.0 < y < 1.0)
+            let unit_direction = ray.direction().normalized();
+            // scale t so 0.0 <= t <= 1.0
+            let t = 0.5 * (unit_direction.y() + 1.0);
+            // linear blend aka interpolation between white and blue
+            let white = Color::new3(1.0, 1.0, 1.0);
+            let blue = Color::new3(0.5, 0.7, 1.0);
+            escaped + white * (1.0 - t) + blue * t
+        }
+    };
+    // a ray only escapes from inside a medium if it is open, e.g. a plane of water
+    PathLight {
+        emitted: spectrum(emitted) * transmittance * spectrum(media.transmittance(f64::INFINITY)),
+        direct: black,
+        indirect: black,
+    }
+}
+
+/// Renders a scene as seen by a camera onto a film.
+pub struct Renderer<'a> {
+    /// Scene to render.
+    pub world: &'a World<f64>,
+    /// Camera looking at the scene.
+    pub camera: &'a Camera,
+    /// Image width in pixels.
+    pub width: usize,
+    /// Image height in pixels.
+    pub height: usize,
+    /// Maximum number of bounces per path.
+    pub max_depth: usize,
+    /// Base seed of the random sequences.
+    pub seed: u64,
+    /// Stops this render when set, in addition to interruptions of the whole process.
+    pub cancel: Option<&'a AtomicBool>,
+    /// Collects the AOVs of the camera rays, if any are wanted.
+    pub aovs: Option<&'a AovFilm>,
+    /// Whether paths carry light at sampled wavelengths instead of RGB.
+    pub spectral: bool,
+}
+
+impl Renderer<'_> {
+    /// Whether the render should stop, because of an interruption or a cancellation.
+    pub fn stopped(&self) -> bool {
+        interrupt::interrupted()
+            || self
+                .cancel
+                .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Render the samples of a pixel.
+    ///
+    /// For each pixel, we send a number of rays and splat their color values onto the film, which
+    /// weights them by the reconstruction filter. The film knows how many samples a pixel already
+    /// has, so we only render the missing ones up to the given total.
+    ///
+    /// * `film` - Film to splat the samples onto.
+    /// * `i` - Column index.
+    /// * `j` - Row index, zero is the bottom row.
+    /// * `samples` - Total number of samples the pixel should have afterwards.
+    pub fn render_pixel(&self, film: &Film, i: usize, j: usize, samples: usize) {
+        let done = film.samples(i, j);
+        if done >= samples || self.stopped() {
+            return;
+        }
+
+        let pixel = (j * self.width + i) as u64;
+        for sample in done..samples {
+            // every sample has its own random sequence, independent of the thread that renders it
+            // and of any earlier interruptions
+            rtweekend::seed(rtweekend::mix_seed(self.seed, pixel, sample as u64));
+            let x = i as f64 + rtweekend::random(0.0..1.0);
+            let y = j as f64 + rtweekend::random(0.0..1.0);
+            let u = x / ((self.width - 1) as f64);
+            let v = y / ((self.height - 1) as f64);
+            let color = match self.camera.ray(u, v) {
+                Some(ray) => {
+                    // RGB renders do not draw the extra random number, so they stay the same
+                    let wavelengths = if self.spectral {
+                        Some(Wavelengths::sample(rtweekend::random(0.0..1.0)))
+                    } else {
+                        None
+                    };
+                    let media = MediumStack::new();
+                    let light = path_light(
+                        &ray,
+                        self.world,
+                        self.max_depth,
+                        None,
+                        &media,
+                        wavelengths.as_ref(),
+                    );
+                    let light = match &wavelengths {
+                        Some(wavelengths) => light.to_rgb(wavelengths),
+                        None => light,
+                    };
+                    if let Some(aovs) = self.aovs {
+                        aovs.add_sample(i, j, &AovSample::new(&ray, self.world, &light));
+                    }
+                    light.total()
+                }
+                None => Color::new3(0.0, 0.0, 0.0),
+            };
+            film.add_sample(x, y, color);
+        }
+        film.add_samples(i, j, samples - done);
+    }
+
+    /// Render the samples of all pixels in a tile.
+    ///
+    /// * `film` - Film to splat the samples onto.
+    /// * `tile` - Pixels to render.
+    /// * `samples` - Total number of samples each pixel should have afterwards.
+    pub fn render_tile(&self, film: &Film, tile: &Tile, samples: usize) {
+        for j in tile.y..tile.y + tile.height {
+            for i in tile.x..tile.x + tile.width {
+                self.render_pixel(film, i, j, samples);
+            }
+        }
+    }
+
+    /// Render the whole image in passes.
+    ///
+    /// Each pass adds a number of samples to every pixel, so the whole image takes shape early and
+    /// there are points in time where no thread touches the film, e.g. to write a checkpoint.
+    /// Stops early if an interruption or a cancellation is requested.
+    ///
+    /// * `film` - Film to splat the samples onto.
+    /// * `tiles` - Tiles covering the image, in the order they should be rendered.
+    /// * `samples_per_pixel` - Total number of samples per pixel.
+    /// * `pass_samples` - Number of samples per pixel added in each pass.
+    /// * `tile_done` - Called on the current thread for every finished tile, along with the
+    ///   sample count of the pass.
+    /// * `pass_done` - Called after every pass with the sample count reached so far.
+    pub fn render<T, P>(
+        &self,
+        film: &Film,
+        tiles: &[Tile],
+        samples_per_pixel: usize,
+        pass_samples: usize,
+        mut tile_done: T,
+        mut pass_done: P,
+    ) -> io::Result<()>
+    where
+        T: FnMut(&Tile, usize),
+        P: FnMut(usize) -> io::Result<()>,
+    {
+        let mut samples = 0;
+        while samples < samples_per_pixel {
+            samples = (samples + pass_samples.max(1)).min(samples_per_pixel);
+
+            tile::render(
+                tiles,
+                |tile| self.render_tile(film, tile, samples),
+                |tile| tile_done(tile, samples),
+            );
+            if self.stopped() {
+                break;
+            }
+
+            pass_done(samples)?;
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
+use std::cell::RefCell;
 use std::cmp::PartialOrd;
 
 use rand::distributions::uniform::{SampleRange, SampleUniform};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use crate::vec::Vec3;
 
@@ -10,14 +12,39 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * std::f64::consts::PI / 180.0
 }
 
+thread_local! {
+    // random number generator of the current thread, seeded from the OS until reseeded
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
 /// Generate a random number in the range [0,1)
 pub fn random<T, R>(range: R) -> T
 where
     T: SampleUniform,
     R: SampleRange<T>,
 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(range)
+    RNG.with(|rng| rng.borrow_mut().gen_range(range))
+}
+
+/// Reseed the random number generator of the current thread.
+///
+/// All following random numbers on this thread are determined by the seed, no matter which
+/// thread ran before.
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Combine a base seed with two indices (e.g. pixel and sample) into a new seed.
+///
+/// Uses the SplitMix64 finalizer, so seeds for neighboring indices are unrelated.
+pub fn mix_seed(seed: u64, a: u64, b: u64) -> u64 {
+    let mix = |mut z: u64| {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let golden = 0x9e37_79b9_7f4a_7c15u64;
+    mix(mix(mix(seed.wrapping_add(golden)) ^ a.wrapping_add(golden)) ^ b.wrapping_add(golden))
 }
 
 /// Clamp a value so it falls inside the given range
@@ -48,3 +75,37 @@ pub fn random_vec_in_unit_sphere() -> Vec3<f64> {
         return vec;
     }
 }
+
+/// Find a random unit vector, uniformly distributed on the surface of the unit sphere.
+pub fn random_unit_vec() -> Vec3<f64> {
+    random_vec_in_unit_sphere().normalized()
+}
+
+/// Find a random unit vector inside a cone around the z axis.
+///
+/// The directions are uniformly distributed with respect to solid angle. Since the solid angle of
+/// a cone with half opening angle θmax is 2π⋅(1 - cosθmax), cosθ has to be uniformly distributed
+/// in [cosθmax, 1]:
+///
+///     cosθ = 1 - ξ1⋅(1 - cosθmax)
+///     φ = 2π⋅ξ2
+///
+/// * `cos_theta_max` - Cosine of the half opening angle of the cone.
+pub fn random_vec_in_cone(cos_theta_max: f64) -> Vec3<f64> {
+    let cos_theta = 1.0 - random(0.0..1.0) * (1.0 - cos_theta_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * random(0.0..1.0);
+    Vec3::new3(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+}
+
+/// Find a random unit vector on the hemisphere around the z axis, with cosine weighted density.
+///
+/// A uniform point on the unit disk is lifted onto the hemisphere (Malley's method), which gives
+/// the density p(ω) = cosθ / π, the shape of the light a diffuse surface scatters.
+pub fn random_cosine_direction() -> Vec3<f64> {
+    let u: f64 = random(0.0..1.0);
+    let r = u.sqrt();
+    let phi = 2.0 * std::f64::consts::PI * random(0.0..1.0);
+    let z = (1.0 - r * r).max(0.0).sqrt();
+    Vec3::new3(r * phi.cos(), r * phi.sin(), z)
+}
@@ -0,0 +1,864 @@
+use std::io;
+use std::sync::Arc;
+
+use crate::animation::{CameraPath, Interpolation, TransformTrack};
+use crate::aperture::{ApertureMask, ApertureShape};
+use crate::camera::{Camera, FisheyeMapping, Projection};
+use crate::cli::Args;
+use crate::color::Color;
+use crate::conductor::{ComplexIor, Conductor};
+use crate::dielectric::RoughDielectric;
+use crate::environment::EnvironmentMap;
+use crate::film::{Filter, FilterKind};
+use crate::hdr;
+use crate::light::{DirectionalLight, PointLight, SpotLight};
+use crate::material;
+use crate::ppm;
+use crate::principled::Principled;
+use crate::quad::Quad;
+use crate::ray::Point3;
+use crate::rtweekend;
+use crate::sky::Sky;
+use crate::spectrum::Dispersion;
+use crate::sphere::Sphere;
+use crate::texture::Checker;
+use crate::transform::Transformed;
+use crate::vec::Vec3;
+use crate::world::World;
+
+/// Default image width in pixels.
+const IMAGE_WIDTH: usize = 1200;
+/// Default number of samples per pixel.
+const RAY_SAMPLES_PER_PIXEL: usize = 500;
+/// Maximum number of bounces per path.
+const RAY_MAX_DEPTH: usize = 50;
+/// Height of the viewport, the width follows from the aspect ratio.
+const VIEWPORT_HEIGHT: f64 = 2.0;
+
+/// Scene and image settings described by command line arguments.
+///
+/// The description is parsed once, the scene itself can be built for any point in time. Building
+/// is deterministic, so the same arguments always give the same scene.
+pub struct Scene {
+    args: Args,
+    /// Name of the scene, the first positional argument.
+    pub name: String,
+    /// Image width in pixels.
+    pub width: usize,
+    /// Image height in pixels.
+    pub height: usize,
+    /// Total number of samples per pixel.
+    pub samples: usize,
+    /// Maximum number of bounces per path.
+    pub max_depth: usize,
+    /// Base seed of the random sequences.
+    pub seed: u64,
+    /// Reconstruction filter.
+    pub filter: Filter,
+    /// Name of the reconstruction filter.
+    pub filter_name: String,
+    /// Width of the viewport.
+    pub viewport_width: f64,
+    /// Point in time of still images in seconds.
+    pub time: f64,
+    /// Whether light is rendered by wavelength instead of RGB.
+    pub spectral: bool,
+    projection: Projection,
+    eye_separation: f64,
+    aperture_shape: ApertureShape,
+    camera_path: Option<CameraPath>,
+    turntable: Option<f64>,
+}
+
+impl Scene {
+    /// Parse a scene description.
+    ///
+    /// Fails for invalid options and if files referenced by them, e.g. an aperture mask, cannot
+    /// be loaded.
+    ///
+    /// * `args` - Command line arguments describing the scene.
+    pub fn new(args: &Args) -> io::Result<Self> {
+        // Camera projection:
+        // --projection=<perspective|orthographic|fisheye|equisolid|equirectangular> and
+        // --stereo[=<eye separation>] for an over-under stereo pair
+        let projection = match args.value::<String>("projection").as_deref() {
+            Some("orthographic") => Projection::Orthographic,
+            Some("fisheye") => Projection::Fisheye(FisheyeMapping::Equidistant),
+            Some("equisolid") => Projection::Fisheye(FisheyeMapping::Equisolid),
+            Some("equirectangular") => Projection::Equirectangular,
+            _ => Projection::Perspective,
+        };
+        let eye_separation = if args.flag("stereo") {
+            args.value("stereo").unwrap_or(0.064)
+        } else {
+            0.0
+        };
+
+        // Image settings: --width=<pixels> and --samples=<samples per pixel>
+        let aspect_ratio = match projection {
+            // panoramas cover 360° horizontally and 180° vertically
+            Projection::Equirectangular => 2.0,
+            _ => 3.0 / 2.0,
+        };
+        let width = args.value("width").unwrap_or(IMAGE_WIDTH).max(1);
+        let eye_height = ((width as f64 / aspect_ratio) as usize).max(1);
+        // stereo pairs are stacked on top of each other
+        let height = if eye_separation > 0.0 {
+            2 * eye_height
+        } else {
+            eye_height
+        };
+
+        // Lens aperture: --aperture-blades=<n> with --aperture-rotation=<degrees> for a polygonal
+        // aperture or --aperture-mask=<image.ppm> for an arbitrary one
+        let aperture_shape = if let Some(path) = args.value::<String>("aperture-mask") {
+            let mask = ApertureMask::new(&ppm::load(path)?);
+            ApertureShape::Mask(Arc::new(mask))
+        } else if let Some(blades) = args.value("aperture-blades") {
+            let rotation = args.value("aperture-rotation").unwrap_or(0.0);
+            ApertureShape::Polygon {
+                blades,
+                rotation: rtweekend::degrees_to_radians(rotation),
+            }
+        } else {
+            ApertureShape::Circle
+        };
+
+        // Camera animation: --camera-path=<file> with keys as described in CameraPath::load and
+        // --interpolation=<linear|catmull-rom>, or --turntable[=<seconds per revolution>]
+        let interpolation = match args.value::<String>("interpolation").as_deref() {
+            Some("linear") => Interpolation::Linear,
+            _ => Interpolation::CatmullRom,
+        };
+        let camera_path = match args.value::<String>("camera-path") {
+            Some(path) => Some(CameraPath::load(path, interpolation)?),
+            None => None,
+        };
+        let turntable = if args.flag("turntable") {
+            Some(args.value("turntable").unwrap_or(10.0))
+        } else {
+            None
+        };
+
+        // Reconstruction filter: --filter=box|tent|gaussian|mitchell|lanczos with an optional
+        // --filter-radius=<pixels>, the default is a box covering a single pixel
+        let filter_name = args
+            .value::<String>("filter")
+            .unwrap_or_else(|| "box".to_string());
+        let (kind, default_radius) = match filter_name.as_str() {
+            "box" => (FilterKind::Box, 0.5),
+            "tent" => (FilterKind::Tent, 1.0),
+            "gaussian" => (FilterKind::Gaussian { alpha: 2.0 }, 1.5),
+            "mitchell" => (
+                FilterKind::Mitchell {
+                    b: 1.0 / 3.0,
+                    c: 1.0 / 3.0,
+                },
+                2.0,
+            ),
+            "lanczos" => (FilterKind::Lanczos, 3.0),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown filter: {}", filter_name),
+                ))
+            }
+        };
+        let filter = Filter::new(kind, args.value("filter-radius").unwrap_or(default_radius));
+
+        Ok(Scene {
+            args: args.clone(),
+            // the random spheres are the default
+            name: args.positional(0).unwrap_or("random").to_string(),
+            width,
+            height,
+            samples: args.value("samples").unwrap_or(RAY_SAMPLES_PER_PIXEL),
+            max_depth: RAY_MAX_DEPTH,
+            // Random numbers: --seed=<n> selects the random sequence, which makes renders (and
+            // the random scene) reproducible
+            seed: args.value("seed").unwrap_or(0),
+            filter,
+            filter_name,
+            viewport_width: aspect_ratio * VIEWPORT_HEIGHT,
+            // Still images: --time=<seconds> picks the point in time of animated scenes
+            time: args.value("time").unwrap_or(0.0),
+            // Spectral rendering: --spectral traces wavelengths instead of RGB colors, e.g. for
+            // dispersion
+            spectral: args.flag("spectral"),
+            projection,
+            eye_separation,
+            aperture_shape,
+            camera_path,
+            turntable,
+        })
+    }
+
+    /// Returns the viewport height.
+    pub fn viewport_height(&self) -> f64 {
+        VIEWPORT_HEIGHT
+    }
+
+    /// Build the scene and its camera.
+    ///
+    /// Animated objects and cameras move according to the given time, everything else is the same
+    /// for all points in time.
+    ///
+    /// * `time` - Point in time in seconds.
+    pub fn build(&self, time: f64) -> io::Result<(World<f64>, Camera)> {
+        // the same seed for every frame, so random scenes stay the same
+        rtweekend::seed(self.seed);
+
+        let (world, camera) = match self.name.as_str() {
+            "cornell" => {
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(278.0, 278.0, -800.0))
+                    .lookat(Vec3::new3(278.0, 278.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(40.0);
+                (cornell_box(), camera)
+            }
+            "lights" => {
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(13.0, 2.0, 3.0))
+                    .lookat(Vec3::new3(0.0, 0.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(30.0);
+                (lights_scene(), camera)
+            }
+            "environment" => {
+                // arguments: path to the environment map, optionally its rotation in degrees and
+                // its intensity
+                let path = self.args.positional(1).unwrap_or_default();
+                let rotation = self.args.positional_as(2).unwrap_or(0.0);
+                let intensity = self.args.positional_as(3).unwrap_or(1.0);
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(13.0, 2.0, 3.0))
+                    .lookat(Vec3::new3(0.0, 0.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(30.0);
+                (environment_scene(path, rotation, intensity)?, camera)
+            }
+            "sky" => {
+                // arguments: sun elevation in degrees, turbidity and "bake" to bake the sky
+                let elevation = self.args.positional_as(1).unwrap_or(30.0);
+                let turbidity = self.args.positional_as(2).unwrap_or(3.0);
+                let bake = self.args.positional(3) == Some("bake");
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(13.0, 2.0, 3.0))
+                    .lookat(Vec3::new3(0.0, 0.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(30.0);
+                (sky_scene(elevation, turbidity, bake), camera)
+            }
+            "materials" => {
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(0.0, 3.5, 15.0))
+                    .lookat(Vec3::new3(0.0, 1.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(35.0);
+                (materials_scene(), camera)
+            }
+            "nested" => {
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(0.0, 2.5, 9.0))
+                    .lookat(Vec3::new3(0.0, 1.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(35.0);
+                (nested_scene(), camera)
+            }
+            "principled" => {
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(0.0, 3.5, 15.0))
+                    .lookat(Vec3::new3(0.0, 1.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(35.0);
+                (principled_scene(), camera)
+            }
+            "rough" => {
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(0.0, 2.0, 12.0))
+                    .lookat(Vec3::new3(0.0, 1.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(35.0);
+                (rough_scene(), camera)
+            }
+            "dispersion" => {
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(0.0, 3.0, 10.0))
+                    .lookat(Vec3::new3(0.0, 0.8, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(35.0);
+                (dispersion_scene(), camera)
+            }
+            _ => {
+                let camera = Camera::new(self.viewport_width, VIEWPORT_HEIGHT)
+                    .lookfrom(Vec3::new3(13.0, 2.0, 3.0))
+                    .lookat(Vec3::new3(0.0, 0.0, 0.0))
+                    .up(Vec3::new3(0.0, 1.0, 0.0))
+                    .vfov(20.0)
+                    .lens(0.1, 10.0);
+                (random_scene(time), camera)
+            }
+        };
+        let camera = camera
+            .projection(self.projection)
+            .stereo(self.eye_separation);
+
+        // Lens effects: --anamorphic=<squeeze> and --cats-eye=<strength>
+        let camera = camera
+            .aperture_shape(self.aperture_shape.clone())
+            .anamorphic(self.args.value("anamorphic").unwrap_or(1.0))
+            .cats_eye(self.args.value("cats-eye").unwrap_or(0.0));
+
+        // Tilt-shift: --shift-x=<fraction> and --shift-y=<fraction> move the lens parallel to the
+        // film, --tilt=<degrees> and --swing=<degrees> rotate the focus plane, or
+        // --focus-plane=<ax,ay,az,bx,by,bz,cx,cy,cz> places it through three points
+        let camera = camera.shift(
+            self.args.value("shift-x").unwrap_or(0.0),
+            self.args.value("shift-y").unwrap_or(0.0),
+        );
+        let camera = if self.args.flag("tilt") || self.args.flag("swing") {
+            camera.tilt(
+                self.args.value("tilt").unwrap_or(0.0),
+                self.args.value("swing").unwrap_or(0.0),
+            )
+        } else if let Some(coords) = self.args.value::<String>("focus-plane") {
+            let coords: Vec<f64> = coords.split(',').filter_map(|c| c.parse().ok()).collect();
+            if coords.len() != 9 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--focus-plane expects nine comma separated coordinates",
+                ));
+            }
+            let point = |i: usize| Point3::new3(coords[i], coords[i + 1], coords[i + 2]);
+            camera.focus_plane(point(0), point(3), point(6))
+        } else {
+            camera
+        };
+
+        // the camera path overrides the initial camera placement
+        let camera = match (&self.camera_path, self.turntable) {
+            (Some(camera_path), _) => camera_path.apply(camera, time),
+            (None, Some(duration)) => {
+                let (lookfrom, lookat) = camera.placement();
+                CameraPath::orbit(lookfrom, lookat, duration).apply(camera, time)
+            }
+            (None, None) => camera,
+        };
+
+        Ok((world, camera))
+    }
+}
+
+/// Setup a random scene.
+///
+/// The three big spheres are animated in a four second loop: the glass sphere bounces and the
+/// brown one rolls back and forth.
+///
+/// * `time` - Point in time in seconds.
+fn random_scene(time: f64) -> World<f64> {
+    let mut world = World::new();
+
+    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
+    let sphere_ground_mat = material::Lambertian::new(Color::new3(0.5, 0.5, 0.5));
+    world.add(sphere_ground, sphere_ground_mat);
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let random = rtweekend::random(0.0..1.0);
+            let center = Point3::new3(a as f64 + 0.9 * random, 0.2, b as f64 + 0.9 * random);
+
+            if (center - Point3::new3(4.0, 0.2, 0.0)).length() > 0.9 {
+                if random < 0.8 {
+                    // diffuse
+                    let albedo = Color::new3(
+                        rtweekend::random(0.0..1.0) * rtweekend::random(0.0..1.0),
+                        rtweekend::random(0.0..1.0) * rtweekend::random(0.0..1.0),
+                        rtweekend::random(0.0..1.0) * rtweekend::random(0.0..1.0),
+                    );
+                    let material = material::Lambertian::new(albedo);
+                    let sphere = Sphere::new(center, 0.2);
+                    world.add(sphere, material);
+                } else if random < 0.95 {
+                    // metal
+                    let albedo = Color::new3(
+                        rtweekend::random(0.5..1.0),
+                        rtweekend::random(0.5..1.0),
+                        rtweekend::random(0.5..1.0),
+                    );
+                    let fuzz = rtweekend::random(0.0..0.5);
+                    let material = material::Metal::new(albedo, fuzz);
+                    let sphere = Sphere::new(center, 0.2);
+                    world.add(sphere, material);
+                } else {
+                    // glass
+                    let material = material::Dielectric::new(1.5);
+                    let sphere = Sphere::new(center, 0.2);
+                    world.add(sphere, material);
+                }
+            }
+        }
+    }
+
+    let mut bounce = TransformTrack::new(Interpolation::CatmullRom);
+    for (time, height) in &[(0.0, 1.0), (1.0, 2.5), (2.0, 1.0), (3.0, 2.5), (4.0, 1.0)] {
+        bounce.translation = bounce.translation.key(*time, Vec3::new3(0.0, *height, 0.0));
+    }
+    let material = material::Dielectric::new(1.5);
+    let sphere = Sphere::new(Point3::new3(0.0, 0.0, 0.0), 1.0);
+    world.add(Transformed::new(sphere, bounce.at(time)), material);
+
+    // rolling without slipping: one radian per unit of distance
+    let mut roll = TransformTrack::new(Interpolation::CatmullRom);
+    for (time, z) in &[(0.0, 0.0), (2.0, 2.0), (4.0, 0.0)] {
+        roll.translation = roll.translation.key(*time, Vec3::new3(-4.0, 1.0, *z));
+        roll.rotation = roll
+            .rotation
+            .key(*time, Vec3::new3(z.to_degrees(), 0.0, 0.0));
+    }
+    let material = material::Lambertian::new(Color::new3(0.4, 0.2, 0.1));
+    let sphere = Sphere::new(Point3::new3(0.0, 0.0, 0.0), 1.0);
+    world.add(Transformed::new(sphere, roll.at(time)), material);
+
+    let material = material::Metal::new(Color::new3(0.7, 0.6, 0.5), 0.0);
+    let sphere = Sphere::new(Point3::new3(4.0, 1.0, 0.0), 1.0);
+    world.add(sphere, material);
+
+    world
+}
+
+/// Setup the Cornell box scene, lit by a single small area light.
+fn cornell_box() -> World<f64> {
+    let mut world = World::new();
+    // the box is closed except for the front, nothing but the light illuminates it
+    world.set_background(Color::new3(0.0, 0.0, 0.0));
+
+    let red = Color::new3(0.65, 0.05, 0.05);
+    let white = Color::new3(0.73, 0.73, 0.73);
+    let green = Color::new3(0.12, 0.45, 0.15);
+    let light = Color::new3(15.0, 15.0, 15.0);
+    // one material for all white surfaces, so they share a material index
+    let white = Arc::new(material::Lambertian::new(white));
+
+    // walls: left, right, floor, ceiling, back
+    world.add(
+        Quad::new(
+            Point3::new3(555.0, 0.0, 0.0),
+            Vec3::new3(0.0, 0.0, 555.0),
+            Vec3::new3(0.0, 555.0, 0.0),
+        ),
+        material::Lambertian::new(green),
+    );
+    world.add(
+        Quad::new(
+            Point3::new3(0.0, 0.0, 0.0),
+            Vec3::new3(0.0, 555.0, 0.0),
+            Vec3::new3(0.0, 0.0, 555.0),
+        ),
+        material::Lambertian::new(red),
+    );
+    world.add_shared(
+        Quad::new(
+            Point3::new3(0.0, 0.0, 0.0),
+            Vec3::new3(0.0, 0.0, 555.0),
+            Vec3::new3(555.0, 0.0, 0.0),
+        ),
+        white.clone(),
+    );
+    world.add_shared(
+        Quad::new(
+            Point3::new3(555.0, 555.0, 555.0),
+            Vec3::new3(-555.0, 0.0, 0.0),
+            Vec3::new3(0.0, 0.0, -555.0),
+        ),
+        white.clone(),
+    );
+    world.add_shared(
+        Quad::new(
+            Point3::new3(0.0, 0.0, 555.0),
+            Vec3::new3(0.0, 555.0, 0.0),
+            Vec3::new3(555.0, 0.0, 0.0),
+        ),
+        white.clone(),
+    );
+
+    // small light in the ceiling, facing down
+    world.add(
+        Quad::new(
+            Point3::new3(213.0, 554.0, 227.0),
+            Vec3::new3(130.0, 0.0, 0.0),
+            Vec3::new3(0.0, 0.0, 105.0),
+        ),
+        material::DiffuseLight::new(light),
+    );
+
+    world.add(
+        Sphere::new(Point3::new3(190.0, 90.0, 190.0), 90.0),
+        material::Dielectric::new(1.5),
+    );
+    world.add_shared(Sphere::new(Point3::new3(370.0, 120.0, 370.0), 120.0), white);
+
+    world
+}
+
+/// Setup a scene lit by a point light, a spot light and the sun.
+fn lights_scene() -> World<f64> {
+    let mut world = World::new();
+    // night sky, so only the light sources illuminate the scene
+    world.set_background(Color::new3(0.01, 0.01, 0.02));
+
+    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
+    world.add(
+        sphere_ground,
+        material::Lambertian::new(Color::new3(0.5, 0.5, 0.5)),
+    );
+
+    world.add(
+        Sphere::new(Point3::new3(-4.0, 1.0, 0.0), 1.0),
+        material::Lambertian::new(Color::new3(0.4, 0.2, 0.1)),
+    );
+    world.add(
+        Sphere::new(Point3::new3(0.0, 1.0, 0.0), 1.0),
+        material::Metal::new(Color::new3(0.7, 0.6, 0.5), 0.2),
+    );
+    world.add(
+        Sphere::new(Point3::new3(4.0, 1.0, 0.0), 1.0),
+        material::Lambertian::new(Color::new3(0.1, 0.2, 0.5)),
+    );
+
+    // warm light bulb between the left and the center sphere
+    world.add_light(PointLight::new(
+        Point3::new3(-2.0, 2.5, 1.5),
+        Color::new3(8.0, 6.0, 4.0),
+    ));
+    // spot light aimed at the right sphere from above
+    world.add_light(SpotLight::new(
+        Point3::new3(4.0, 6.0, 2.0),
+        Point3::new3(4.0, 0.0, 0.0),
+        Color::new3(40.0, 40.0, 40.0),
+        40.0,
+        25.0,
+    ));
+    // low moonlight with soft shadows
+    world.add_light(DirectionalLight::new(
+        Vec3::new3(-1.0, 0.5, 1.0),
+        Color::new3(0.15, 0.15, 0.25),
+        2.0,
+    ));
+
+    world
+}
+
+/// Setup a few spheres lit by an HDR environment map.
+///
+/// * `path` - Path to an equirectangular .hdr or .pfm image.
+/// * `rotation` - Rotation of the environment around the vertical axis in degrees.
+/// * `intensity` - Brightness scale of the environment.
+fn environment_scene(path: &str, rotation: f64, intensity: f64) -> io::Result<World<f64>> {
+    let mut world = World::new();
+
+    let environment = EnvironmentMap::new(hdr::load(path)?)
+        .rotation(rotation)
+        .intensity(intensity);
+    world.set_environment(environment);
+
+    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
+    world.add(
+        sphere_ground,
+        material::Lambertian::new(Color::new3(0.5, 0.5, 0.5)),
+    );
+
+    world.add(
+        Sphere::new(Point3::new3(-4.0, 1.0, 0.0), 1.0),
+        material::Lambertian::new(Color::new3(0.4, 0.2, 0.1)),
+    );
+    world.add(
+        Sphere::new(Point3::new3(0.0, 1.0, 0.0), 1.0),
+        material::Dielectric::new(1.5),
+    );
+    world.add(
+        Sphere::new(Point3::new3(4.0, 1.0, 0.0), 1.0),
+        material::Metal::new(Color::new3(0.7, 0.6, 0.5), 0.1),
+    );
+
+    Ok(world)
+}
+
+/// Setup a few spheres under a daylight sky.
+///
+/// * `elevation` - Elevation of the sun above the horizon in degrees.
+/// * `turbidity` - Haziness of the atmosphere.
+/// * `bake` - Whether to bake the sky into an environment map for importance sampling.
+fn sky_scene(elevation: f64, turbidity: f64, bake: bool) -> World<f64> {
+    let mut world = World::new();
+
+    let ground = Color::new3(0.5, 0.5, 0.5);
+    let elevation = rtweekend::degrees_to_radians(elevation);
+    let sun_direction = Vec3::new3(
+        elevation.cos() * 0.6,
+        elevation.sin(),
+        elevation.cos() * 0.8,
+    );
+    let sky = Sky::new(sun_direction, turbidity, ground);
+
+    world.add_light(sky.sun());
+    if bake {
+        world.set_environment(EnvironmentMap::new(sky.bake(512, 256)));
+    } else {
+        world.set_environment(sky);
+    }
+
+    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
+    world.add(sphere_ground, material::Lambertian::new(ground));
+
+    world.add(
+        Sphere::new(Point3::new3(-4.0, 1.0, 0.0), 1.0),
+        material::Lambertian::new(Color::new3(0.4, 0.2, 0.1)),
+    );
+    world.add(
+        Sphere::new(Point3::new3(0.0, 1.0, 0.0), 1.0),
+        material::Dielectric::new(1.5),
+    );
+    world.add(
+        Sphere::new(Point3::new3(4.0, 1.0, 0.0), 1.0),
+        material::Metal::new(Color::new3(0.7, 0.6, 0.5), 0.1),
+    );
+
+    world
+}
+
+/// Setup two rows of spheres showing off the physically based materials, under a daylight sky.
+fn materials_scene() -> World<f64> {
+    let mut world = World::new();
+
+    let ground = Color::new3(0.5, 0.5, 0.5);
+    let sky = Sky::new(Vec3::new3(0.4, 0.6, 0.7), 3.0, ground);
+    world.add_light(sky.sun());
+    world.set_environment(sky);
+
+    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
+    world.add(sphere_ground, material::Lambertian::new(ground));
+
+    // metals from polished to rough, the aluminium one is brushed along the meridians
+    world.add(
+        Sphere::new(Point3::new3(-6.0, 1.0, 0.0), 1.0),
+        Conductor::preset(&ComplexIor::SILVER, 0.0),
+    );
+    world.add(
+        Sphere::new(Point3::new3(-3.0, 1.0, 0.0), 1.0),
+        Conductor::preset(&ComplexIor::GOLD, 0.2),
+    );
+    world.add(
+        Sphere::new(Point3::new3(0.0, 1.0, 0.0), 1.0),
+        Conductor::preset(&ComplexIor::COPPER, 0.35),
+    );
+    world.add(
+        Sphere::new(Point3::new3(3.0, 1.0, 0.0), 1.0),
+        Conductor::preset(&ComplexIor::ALUMINIUM, 0.4)
+            .anisotropy(0.9)
+            .tangent(Vec3::new3(0.0, 1.0, 0.0)),
+    );
+    world.add(
+        Sphere::new(Point3::new3(6.0, 1.0, 0.0), 1.0),
+        Conductor::preset(&ComplexIor::GOLD, 0.6),
+    );
+
+    // dielectrics in front, in between the metals: green and frosted glass, rippled water
+    world.add(
+        Sphere::new(Point3::new3(-4.5, 0.8, 3.0), 0.8),
+        material::Dielectric::new(1.5).absorption(Color::new3(0.3, 0.8, 0.5), 1.0),
+    );
+    world.add(
+        Sphere::new(Point3::new3(-1.5, 0.8, 3.0), 0.8),
+        RoughDielectric::new(1.5, 0.3),
+    );
+    world.add(
+        Sphere::new(Point3::new3(1.5, 0.8, 3.0), 0.8),
+        RoughDielectric::new(1.5, 0.6),
+    );
+    world.add(
+        Sphere::new(Point3::new3(4.5, 0.8, 3.0), 0.8),
+        RoughDielectric::new(1.33, 0.1).absorption(Color::new3(0.7, 0.9, 0.95), 1.0),
+    );
+
+    world
+}
+
+/// Setup dielectrics inside each other: a glass ball filled with water and a gem with a bubble.
+fn nested_scene() -> World<f64> {
+    let mut world = World::new();
+
+    let ground = Color::new3(0.5, 0.5, 0.5);
+    let sky = Sky::new(Vec3::new3(0.4, 0.6, 0.7), 3.0, ground);
+    world.add_light(sky.sun());
+    world.set_environment(sky);
+
+    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
+    world.add(sphere_ground, material::Lambertian::new(ground));
+
+    // A hollow glass ball, the negative radius turns the inner surface inside out. The water
+    // reaches into the glass wall, which wins there by its higher priority. An air bubble floats
+    // in the water.
+    let glass = Arc::new(material::Dielectric::new(1.5).priority(2));
+    world.add_shared(
+        Sphere::new(Point3::new3(-1.6, 1.2, 0.0), 1.2),
+        glass.clone(),
+    );
+    world.add_shared(Sphere::new(Point3::new3(-1.6, 1.2, 0.0), -1.1), glass);
+    world.add(
+        Sphere::new(Point3::new3(-1.6, 1.2, 0.0), 1.15),
+        material::Dielectric::new(1.33)
+            .absorption(Color::new3(0.6, 0.85, 0.95), 1.0)
+            .priority(1),
+    );
+    world.add(
+        Sphere::new(Point3::new3(-1.9, 1.5, 0.4), 0.3),
+        material::Dielectric::new(1.0).priority(3),
+    );
+
+    // a ruby with a bubble of air inside
+    world.add(
+        Sphere::new(Point3::new3(1.6, 1.0, 0.0), 1.0),
+        RoughDielectric::new(1.77, 0.05)
+            .absorption(Color::new3(0.9, 0.2, 0.3), 1.0)
+            .priority(1),
+    );
+    world.add(
+        Sphere::new(Point3::new3(1.5, 1.1, 0.3), 0.35),
+        RoughDielectric::new(1.0, 0.0).priority(2),
+    );
+
+    world
+}
+
+/// Setup spheres made of the principled material, on a textured floor under a daylight sky.
+fn principled_scene() -> World<f64> {
+    let mut world = World::new();
+
+    let sky = Sky::new(Vec3::new3(0.4, 0.6, 0.7), 3.0, Color::new3(0.5, 0.5, 0.5));
+    world.add_light(sky.sun());
+    world.set_environment(sky);
+
+    // tiles of polished stone between dull grout
+    let floor = Principled::new(Checker::new(
+        Color::new3(0.7, 0.7, 0.7),
+        Color::new3(0.3, 0.3, 0.35),
+        1.5,
+    ))
+    .roughness(Checker::new(0.2, 0.8, 1.5));
+    world.add(Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0), floor);
+
+    // back row: car paint, brushed gold, velvet, frosted blue glass, rusty iron
+    world.add(
+        Sphere::new(Point3::new3(-6.0, 1.0, 0.0), 1.0),
+        Principled::new(Color::new3(0.6, 0.02, 0.02))
+            .roughness(0.4)
+            .clearcoat(1.0),
+    );
+    world.add(
+        Sphere::new(Point3::new3(-3.0, 1.0, 0.0), 1.0),
+        Principled::new(Color::new3(1.0, 0.78, 0.34))
+            .metallic(1.0)
+            .roughness(0.4)
+            .anisotropy(0.8)
+            .tangent(Vec3::new3(0.0, 1.0, 0.0)),
+    );
+    world.add(
+        Sphere::new(Point3::new3(0.0, 1.0, 0.0), 1.0),
+        Principled::new(Color::new3(0.25, 0.05, 0.3))
+            .roughness(1.0)
+            .specular(0.2)
+            .sheen(1.0)
+            .sheen_tint(0.8),
+    );
+    world.add(
+        Sphere::new(Point3::new3(3.0, 1.0, 0.0), 1.0),
+        Principled::new(Color::new3(0.7, 0.85, 1.0))
+            .roughness(0.2)
+            .transmission(1.0)
+            .refraction(1.45),
+    );
+    world.add(
+        Sphere::new(Point3::new3(6.0, 1.0, 0.0), 1.0),
+        Principled::new(Checker::new(
+            Color::new3(0.9, 0.9, 0.9),
+            Color::new3(0.35, 0.12, 0.05),
+            0.25,
+        ))
+        .metallic(Checker::new(1.0, 0.0, 0.25))
+        .roughness(Checker::new(0.3, 0.9, 0.25)),
+    );
+
+    // front row: plastic from glossy to matte, the metallic and specular parameters in between
+    for index in 0..4 {
+        let t = index as f64 / 3.0;
+        world.add(
+            Sphere::new(Point3::new3(-4.5 + 3.0 * index as f64, 0.8, 3.0), 0.8),
+            Principled::new(Color::new3(0.1, 0.35, 0.8))
+                .metallic(0.5 * t)
+                .roughness(0.1 + 0.8 * t)
+                .specular_tint(t)
+                .clearcoat(1.0 - t)
+                .clearcoat_gloss(1.0 - t),
+        );
+    }
+
+    world
+}
+
+/// Setup clay spheres from smooth to rough matte, lit from behind the camera.
+///
+/// The rougher the surface, the flatter it looks: towards the rims, the grooves facing the
+/// viewer are the ones facing the light.
+fn rough_scene() -> World<f64> {
+    let mut world = World::new();
+
+    let ground = Color::new3(0.5, 0.5, 0.5);
+    let sky = Sky::new(Vec3::new3(0.2, 0.5, 1.0), 3.0, ground);
+    world.add_light(sky.sun());
+    world.set_environment(sky);
+
+    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
+    world.add(sphere_ground, material::OrenNayar::new(ground, 30.0));
+
+    let clay = Color::new3(0.7, 0.45, 0.3);
+    for (index, sigma) in [0.0, 20.0, 40.0, 60.0, 90.0].iter().enumerate() {
+        world.add(
+            Sphere::new(Point3::new3(-4.4 + 2.2 * index as f64, 1.0, 0.0), 1.0),
+            material::OrenNayar::new(clay, *sigma),
+        );
+    }
+
+    world
+}
+
+/// Setup glass spheres which split light into colors, best rendered with `--spectral`.
+///
+/// A bright light behind the spheres shows the colored fringes of the refracted highlight, from
+/// the weak dispersion of crown glass to flint glass, diamond and a strongly exaggerated glass.
+fn dispersion_scene() -> World<f64> {
+    let mut world = World::new();
+
+    let ground = Color::new3(0.5, 0.5, 0.5);
+    let sky = Sky::new(Vec3::new3(0.0, 0.25, -1.0), 3.0, ground);
+    world.add_light(sky.sun());
+    world.set_environment(sky);
+
+    let sphere_ground = Sphere::new(Point3::new3(0.0, -1000.0, 0.0), 1000.0);
+    world.add(sphere_ground, material::Lambertian::new(ground));
+
+    let glasses = [
+        Dispersion::BK7,
+        Dispersion::SF11,
+        Dispersion::DIAMOND,
+        Dispersion::Cauchy { a: 1.5, b: 0.05 },
+    ];
+    for (index, dispersion) in glasses.iter().enumerate() {
+        world.add(
+            Sphere::new(Point3::new3(-3.3 + 2.2 * index as f64, 0.9, 0.0), 0.9),
+            material::Dielectric::new(1.5).dispersion(*dispersion),
+        );
+    }
+
+    world
+}
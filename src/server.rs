@@ -0,0 +1,902 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::Args;
+use crate::film::Film;
+use crate::interrupt;
+use crate::png;
+use crate::render::Renderer;
+use crate::scene::Scene;
+use crate::tile::{self, TileOrder};
+
+/// Largest accepted request body, scene descriptions are short.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Largest accepted request head (request line and headers).
+const MAX_HEAD: usize = 16 * 1024;
+
+/// Number of finished jobs kept for status queries, older ones are forgotten.
+const KEPT_JOBS: usize = 64;
+
+/// Options which name a file to read, see `Scene::new`.
+const FILE_OPTIONS: &[&str] = &["aperture-mask", "camera-path"];
+
+/// Scenes whose arguments name a file to read, see `Scene::build`.
+const FILE_SCENES: &[&str] = &["environment"];
+
+/// State of a render job.
+#[derive(Debug, Clone, PartialEq)]
+enum Status {
+    /// Waiting in the queue.
+    Queued,
+    /// Being rendered, the image is available.
+    Rendering,
+    /// All samples are done.
+    Done,
+    /// Cancelled before it was done.
+    Cancelled,
+    /// The scene could not be built, or rendering it failed.
+    Failed(String),
+}
+
+impl Status {
+    /// Returns the name used in the API.
+    fn name(&self) -> &'static str {
+        match self {
+            Status::Queued => "queued",
+            Status::Rendering => "rendering",
+            Status::Done => "done",
+            Status::Cancelled => "cancelled",
+            Status::Failed(_) => "failed",
+        }
+    }
+
+    /// Whether the job will not change anymore.
+    fn is_finished(&self) -> bool {
+        matches!(self, Status::Done | Status::Cancelled | Status::Failed(_))
+    }
+}
+
+/// Progress of a render, as reported by the renderer after every tile.
+#[derive(Debug, Clone, Copy, Default)]
+struct Progress {
+    // samples per pixel of the last complete pass
+    samples: usize,
+    // samples per pixel the current pass renders up to
+    pass_samples: usize,
+    // tiles of the current pass which are done
+    tiles: usize,
+}
+
+/// Render job submitted over HTTP.
+struct Job {
+    id: u64,
+    scene: Scene,
+    status: Mutex<Status>,
+    progress: Mutex<Progress>,
+    cancel: AtomicBool,
+    // created once rendering starts
+    film: Mutex<Option<Arc<Film>>>,
+}
+
+impl Job {
+    /// Returns the status as JSON.
+    fn to_json(&self, tiles: usize) -> String {
+        let status = self.status.lock().unwrap().clone();
+        let progress = *self.progress.lock().unwrap();
+
+        // the current pass counts with the fraction of its tiles which are done
+        let pass = (progress.pass_samples - progress.samples) as f64 * progress.tiles as f64
+            / tiles.max(1) as f64;
+        let fraction = match status {
+            Status::Done => 1.0,
+            _ => (progress.samples as f64 + pass) / self.scene.samples.max(1) as f64,
+        };
+        let error = match &status {
+            Status::Failed(message) => json_string(message),
+            _ => "null".to_string(),
+        };
+
+        format!(
+            "{{\"id\": {}, \"status\": \"{}\", \"scene\": {}, \"width\": {}, \"height\": {}, \
+             \"samples\": {}, \"samples_total\": {}, \"progress\": {:.4}, \"error\": {}}}",
+            self.id,
+            status.name(),
+            json_string(&self.scene.name),
+            self.scene.width,
+            self.scene.height,
+            progress.samples,
+            self.scene.samples,
+            fraction,
+            error
+        )
+    }
+}
+
+/// Largest scenes the service accepts, larger submissions are rejected with 400.
+///
+/// Without limits, a single submission could ask for an image which does not fit into memory, or
+/// keep the service busy for days.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum image width in pixels.
+    pub width: usize,
+    /// Maximum image height in pixels.
+    pub height: usize,
+    /// Maximum number of samples per pixel.
+    pub samples: usize,
+    /// Maximum number of bounces of a path.
+    pub max_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            width: 4096,
+            height: 4096,
+            samples: 65536,
+            max_depth: 64,
+        }
+    }
+}
+
+impl Limits {
+    /// Returns why a scene exceeds the limits, if it does.
+    ///
+    /// * `scene` - Submitted scene.
+    fn check(&self, scene: &Scene) -> Option<String> {
+        let values = [
+            ("width", scene.width, self.width),
+            ("height", scene.height, self.height),
+            ("samples", scene.samples, self.samples),
+            ("maximum depth", scene.max_depth, self.max_depth),
+        ];
+        values
+            .iter()
+            .find(|(_, value, limit)| value > limit)
+            .map(|(name, value, limit)| {
+                format!("{} {} is above the limit of {}", name, value, limit)
+            })
+    }
+}
+
+/// HTTP service which renders scenes submitted by other programs.
+///
+/// Jobs are rendered one after another, each one with all threads. The API:
+///
+///     POST   /jobs            submit a scene, the body holds its command line arguments,
+///                             e.g. "cornell --samples=64 --filter=gaussian"
+///     GET    /jobs            status of all jobs
+///     GET    /jobs/<id>       status of a job, including its progress from 0 to 1
+///     GET    /jobs/<id>/image current image as PNG, refined with every pass
+///     DELETE /jobs/<id>       cancel a queued or running job, forget a finished one
+///
+/// Statuses are JSON objects. Scenes larger than the limits are rejected with 400, as are scenes
+/// which reference files, e.g. an environment map: they would be read on the machine running the
+/// service, on behalf of anyone who can reach it. If the queue of waiting jobs is full,
+/// submissions are rejected with 503 until a job has been started.
+pub struct Server {
+    capacity: usize,
+    limits: Limits,
+    pass_samples: usize,
+    tile_size: usize,
+    tile_order: TileOrder,
+    next_id: AtomicU64,
+    // all jobs which are not forgotten, in the order they were submitted
+    jobs: Mutex<Vec<Arc<Job>>>,
+    queue: Mutex<VecDeque<Arc<Job>>>,
+    queued: Condvar,
+}
+
+impl Server {
+    /// Create a new server without jobs, accepting scenes up to the default limits.
+    ///
+    /// * `capacity` - Maximum number of jobs waiting in the queue.
+    /// * `pass_samples` - Number of samples per pixel added in each pass.
+    /// * `tile_size` - Size of the tiles in pixels.
+    /// * `tile_order` - Order in which the tiles are rendered.
+    pub fn new(
+        capacity: usize,
+        pass_samples: usize,
+        tile_size: usize,
+        tile_order: TileOrder,
+    ) -> Self {
+        Server {
+            capacity,
+            limits: Limits::default(),
+            pass_samples,
+            tile_size,
+            tile_order,
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::new(Vec::new()),
+            queue: Mutex::new(VecDeque::new()),
+            queued: Condvar::new(),
+        }
+    }
+
+    /// Set the largest scenes which are accepted.
+    ///
+    /// * `limits` - Limits of submitted scenes.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Serve requests until an interruption is requested.
+    ///
+    /// Every connection is handled on its own thread and closed after one response, jobs are
+    /// rendered on another one.
+    ///
+    /// * `listener` - Socket to accept connections on.
+    pub fn run(&self, listener: TcpListener) -> io::Result<()> {
+        listener.set_nonblocking(true)?;
+        thread::scope(|s| {
+            s.spawn(|| self.render_jobs());
+
+            while !interrupt::interrupted() {
+                match listener.accept() {
+                    Ok((stream, address)) => {
+                        s.spawn(move || {
+                            if let Err(err) = self.handle(stream) {
+                                eprintln!(">> Request from {} failed: {}", address, err);
+                            }
+                        });
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Render queued jobs until an interruption is requested.
+    fn render_jobs(&self) {
+        loop {
+            let job = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if interrupt::interrupted() {
+                        return;
+                    }
+                    if let Some(job) = queue.pop_front() {
+                        // while the queue is locked, so a cancellation sees either state
+                        *job.status.lock().unwrap() = Status::Rendering;
+                        break job;
+                    }
+                    // wake up now and then to check for interruptions
+                    queue = self
+                        .queued
+                        .wait_timeout(queue, Duration::from_millis(100))
+                        .unwrap()
+                        .0;
+                }
+            };
+
+            eprintln!(">> Job {}: rendering {}", job.id, job.scene.name);
+            // a panicking job fails on its own instead of stopping the service
+            let status = match panic::catch_unwind(AssertUnwindSafe(|| self.render(&job))) {
+                Ok(Ok(())) if job.cancel.load(Ordering::Relaxed) || interrupt::interrupted() => {
+                    Status::Cancelled
+                }
+                Ok(Ok(())) => Status::Done,
+                Ok(Err(err)) => Status::Failed(err.to_string()),
+                Err(payload) => {
+                    // the film may be left half-written behind a poisoned lock
+                    *job.film.lock().unwrap() = None;
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown error".to_string());
+                    Status::Failed(format!("render panicked: {}", message))
+                }
+            };
+            eprintln!(">> Job {}: {}", job.id, status.name());
+            *job.status.lock().unwrap() = status;
+        }
+    }
+
+    /// Render a job on the existing rendering loop, reporting progress after every tile.
+    fn render(&self, job: &Job) -> io::Result<()> {
+        let scene = &job.scene;
+        let (world, camera) = scene.build(scene.time)?;
+        let film = Arc::new(Film::new(scene.width, scene.height, scene.filter));
+        *job.film.lock().unwrap() = Some(Arc::clone(&film));
+
+        let tiles = tile::tiles(scene.width, scene.height, self.tile_size, self.tile_order);
+        let renderer = Renderer {
+            world: &world,
+            camera: &camera,
+            width: scene.width,
+            height: scene.height,
+            max_depth: scene.max_depth,
+            seed: scene.seed,
+            spectral: scene.spectral,
+            cancel: Some(&job.cancel),
+            aovs: None,
+        };
+        renderer.render(
+            &film,
+            &tiles,
+            scene.samples,
+            self.pass_samples,
+            |_, samples| {
+                let mut progress = job.progress.lock().unwrap();
+                if samples != progress.pass_samples {
+                    // a new pass started, so the previous one is complete
+                    progress.samples = progress.pass_samples;
+                    progress.pass_samples = samples;
+                    progress.tiles = 0;
+                }
+                progress.tiles += 1;
+            },
+            |samples| {
+                let mut progress = job.progress.lock().unwrap();
+                progress.samples = samples;
+                progress.tiles = 0;
+                Ok(())
+            },
+        )
+    }
+
+    /// Returns the number of tiles of a job, which its progress is measured in.
+    fn tiles(&self, job: &Job) -> usize {
+        let scene = &job.scene;
+        let columns = scene.width.div_ceil(self.tile_size.max(1));
+        let rows = scene.height.div_ceil(self.tile_size.max(1));
+        columns * rows
+    }
+
+    /// Returns a job by its id.
+    fn job(&self, id: &str) -> Option<Arc<Job>> {
+        let id: u64 = id.parse().ok()?;
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().find(|job| job.id == id).cloned()
+    }
+
+    /// Answer a single request.
+    fn handle(&self, mut stream: TcpStream) -> io::Result<()> {
+        // accepted sockets may inherit the non-blocking mode of the listener
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+        let response = match Request::read(&mut BufReader::new(&stream)) {
+            Ok(request) => self.route(&request),
+            Err(err) => Response::error(400, &err.to_string()),
+        };
+        response.write(&mut stream)
+    }
+
+    /// Dispatch a request to its handler.
+    fn route(&self, request: &Request) -> Response {
+        let segments: Vec<&str> = request
+            .path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["jobs"]) => self.submit(&request.body),
+            ("GET", ["jobs"]) => {
+                let jobs = self.jobs.lock().unwrap();
+                let statuses: Vec<String> = jobs
+                    .iter()
+                    .map(|job| job.to_json(self.tiles(job)))
+                    .collect();
+                Response::json(200, format!("[{}]", statuses.join(", ")))
+            }
+            ("GET", ["jobs", id]) => match self.job(id) {
+                Some(job) => Response::json(200, job.to_json(self.tiles(&job))),
+                None => Response::error(404, "no such job"),
+            },
+            ("GET", ["jobs", id, "image"]) => match self.job(id) {
+                Some(job) => self.image(&job),
+                None => Response::error(404, "no such job"),
+            },
+            ("DELETE", ["jobs", id]) => match self.job(id) {
+                Some(job) => self.cancel(&job),
+                None => Response::error(404, "no such job"),
+            },
+            (_, ["jobs"]) | (_, ["jobs", _]) | (_, ["jobs", _, "image"]) => {
+                Response::error(405, "method not allowed")
+            }
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    /// Queue a new job.
+    fn submit(&self, body: &[u8]) -> Response {
+        let description = match std::str::from_utf8(body) {
+            Ok(description) => description,
+            Err(_) => return Response::error(400, "scene description is not UTF-8"),
+        };
+        let args = Args::from(description.split_whitespace().map(|arg| arg.to_string()));
+        // checked before building anything, so nothing tells which files exist
+        if let Some(option) = FILE_OPTIONS.iter().find(|option| args.flag(option)) {
+            return Response::error(400, &format!("--{} is not available here", option));
+        }
+        if let Some(name) = args.positional(0).filter(|name| FILE_SCENES.contains(name)) {
+            return Response::error(400, &format!("the {} scene is not available here", name));
+        }
+        let scene = match Scene::new(&args) {
+            Ok(scene) => scene,
+            Err(err) => return Response::error(400, &err.to_string()),
+        };
+        if let Some(message) = self.limits.check(&scene) {
+            return Response::error(400, &message);
+        }
+
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() >= self.capacity {
+            return Response::error(503, "job queue is full").header("Retry-After", "10");
+        }
+        let job = Arc::new(Job {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            scene,
+            status: Mutex::new(Status::Queued),
+            progress: Mutex::new(Progress::default()),
+            cancel: AtomicBool::new(false),
+            film: Mutex::new(None),
+        });
+        eprintln!(">> Job {}: queued {}", job.id, description.trim());
+        queue.push_back(Arc::clone(&job));
+        self.queued.notify_one();
+        drop(queue);
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.push(Arc::clone(&job));
+        // forget the oldest finished jobs
+        let finished = jobs
+            .iter()
+            .filter(|job| job.status.lock().unwrap().is_finished())
+            .count();
+        let mut excess = finished.saturating_sub(KEPT_JOBS);
+        jobs.retain(|job| {
+            let forget = excess > 0 && job.status.lock().unwrap().is_finished();
+            if forget {
+                excess -= 1;
+            }
+            !forget
+        });
+        drop(jobs);
+
+        Response::json(202, job.to_json(self.tiles(&job)))
+            .header("Location", &format!("/jobs/{}", job.id))
+    }
+
+    /// Returns the current image of a job as PNG.
+    fn image(&self, job: &Job) -> Response {
+        let film = match job.film.lock().unwrap().clone() {
+            Some(film) => film,
+            None => return Response::error(409, "job has no image"),
+        };
+
+        let mut body = Vec::new();
+        match png::write(&mut body, &film.image()) {
+            Ok(()) => Response {
+                status: 200,
+                content_type: "image/png",
+                headers: Vec::new(),
+                body,
+            },
+            Err(err) => Response::error(500, &err.to_string()),
+        }
+    }
+
+    /// Cancel a queued or running job, or forget a finished one.
+    fn cancel(&self, job: &Arc<Job>) -> Response {
+        let finished = {
+            let mut queue = self.queue.lock().unwrap();
+            let mut status = job.status.lock().unwrap();
+            match *status {
+                Status::Queued => {
+                    queue.retain(|queued| queued.id != job.id);
+                    *status = Status::Cancelled;
+                    eprintln!(">> Job {}: cancelled", job.id);
+                    false
+                }
+                Status::Rendering => {
+                    // the renderer stops at the next pixel, the status follows once it did
+                    job.cancel.store(true, Ordering::Relaxed);
+                    false
+                }
+                _ => true,
+            }
+        };
+
+        if finished {
+            self.jobs.lock().unwrap().retain(|known| known.id != job.id);
+        }
+        Response::json(200, job.to_json(self.tiles(job)))
+    }
+}
+
+/// Parsed HTTP request.
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Read a request: request line, headers and a body of the length given by the headers.
+    fn read<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut head = reader.take(MAX_HEAD as u64);
+
+        let mut line = String::new();
+        head.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method.to_string(), target),
+            _ => return Err(invalid("malformed request line")),
+        };
+        // the query string is not used
+        let path = target.split('?').next().unwrap_or_default().to_string();
+
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            if head.read_line(&mut line)? == 0 {
+                return Err(invalid("incomplete request head"));
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    length = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid("invalid content length"))?;
+                }
+            }
+        }
+        if length > MAX_BODY {
+            return Err(invalid("request body too large"));
+        }
+
+        let mut body = vec![0u8; length];
+        head.into_inner().read_exact(&mut body)?;
+        Ok(Request { method, path, body })
+    }
+}
+
+/// HTTP response, the connection is closed after sending it.
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// Create a response with a JSON body.
+    fn json(status: u16, body: String) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: body.into_bytes(),
+        }
+    }
+
+    /// Create an error response with a JSON body holding the message.
+    fn error(status: u16, message: &str) -> Self {
+        Response::json(status, format!("{{\"error\": {}}}", json_string(message)))
+    }
+
+    /// Add a header.
+    fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    /// Send the response.
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        };
+
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len()
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        writer.write_all(head.as_bytes())?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// Returns a string as a quoted JSON string.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Returns a parsed request.
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    /// Send a request to a running server, returns the status, headers and body of the response.
+    fn send(
+        address: &str,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, Vec<(String, String)>, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            address,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+
+        let end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .expect("response without head");
+        let head = String::from_utf8(response[..end].to_vec()).unwrap();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap().split_whitespace().nth(1).unwrap();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+            .collect();
+        (
+            status.parse().unwrap(),
+            headers,
+            response[end + 4..].to_vec(),
+        )
+    }
+
+    /// Returns the value of a header.
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> &'a str {
+        headers
+            .iter()
+            .find(|(known, _)| known == name)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_else(|| panic!("no {} header", name))
+    }
+
+    #[test]
+    fn requests_are_read_with_their_body() {
+        let mut input = Cursor::new(
+            "POST /jobs?verbose=1 HTTP/1.1\r\nHost: localhost\r\ncontent-length: 7\r\n\r\ncornell"
+                .as_bytes(),
+        );
+        let request = Request::read(&mut input).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/jobs");
+        assert_eq!(request.body, b"cornell");
+
+        let mut input = Cursor::new("GET /jobs HTTP/1.1\r\n\r\n".as_bytes());
+        let request = Request::read(&mut input).unwrap();
+        assert_eq!(request.method, "GET");
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn malformed_requests_are_rejected() {
+        let too_large = format!(
+            "POST /jobs HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        let long_head = format!("GET /jobs HTTP/1.1\r\nX: {}\r\n\r\n", "x".repeat(MAX_HEAD));
+        let inputs = [
+            "",
+            "GET\r\n\r\n",
+            "GET /jobs HTTP/1.1\r\nHost: localhost\r\n",
+            "POST /jobs HTTP/1.1\r\nContent-Length: seven\r\n\r\n",
+            "POST /jobs HTTP/1.1\r\nContent-Length: 7\r\n\r\ncorn",
+            &too_large,
+            &long_head,
+        ];
+        for input in &inputs {
+            assert!(
+                Request::read(&mut Cursor::new(input.as_bytes())).is_err(),
+                "accepted {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_paths_and_methods_are_rejected() {
+        let server = Server::new(1, 1, 16, TileOrder::Scanline);
+        let requests = [
+            ("GET", "/", 404),
+            ("GET", "/scenes", 404),
+            ("GET", "/jobs/1", 404),
+            ("GET", "/jobs/one", 404),
+            ("GET", "/jobs/1/image", 404),
+            ("DELETE", "/jobs/1", 404),
+            ("GET", "/jobs/1/film", 404),
+            ("PUT", "/jobs", 405),
+            ("DELETE", "/jobs", 405),
+            ("POST", "/jobs/1", 405),
+            ("POST", "/jobs/1/image", 405),
+        ];
+        for (method, path, status) in &requests {
+            let response = server.route(&request(method, path, ""));
+            assert_eq!(response.status, *status, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn scenes_above_the_limits_are_rejected() {
+        let server = Server::new(8, 1, 16, TileOrder::Scanline).limits(Limits {
+            width: 64,
+            height: 64,
+            samples: 16,
+            max_depth: 64,
+        });
+        for description in &[
+            "cornell --width=65",
+            "cornell --width=64 --stereo",
+            "cornell --width=16 --samples=17",
+            "cornell --filter=unknown",
+        ] {
+            let response = server.route(&request("POST", "/jobs", description));
+            assert_eq!(response.status, 400, "{}", description);
+        }
+
+        let response = server.route(&request("POST", "/jobs", "cornell --width=64 --samples=16"));
+        assert_eq!(response.status, 202);
+
+        let strict = Server::new(8, 1, 16, TileOrder::Scanline).limits(Limits {
+            max_depth: 10,
+            ..Limits::default()
+        });
+        let response = strict.route(&request("POST", "/jobs", "cornell --width=16"));
+        assert_eq!(response.status, 400);
+    }
+
+    #[test]
+    fn scenes_reading_files_are_rejected() {
+        let server = Server::new(8, 1, 16, TileOrder::Scanline);
+        for description in &[
+            "cornell --aperture-mask=/etc/passwd",
+            "cornell --aperture-mask",
+            "cornell --camera-path=/nonexistent.txt",
+            "environment /etc/passwd",
+            "environment",
+        ] {
+            let response = server.route(&request("POST", "/jobs", description));
+            assert_eq!(response.status, 400, "{}", description);
+            let body = String::from_utf8(response.body).unwrap();
+            assert!(body.contains("not available"), "{}: {}", description, body);
+        }
+        assert!(server.jobs.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn full_queues_reject_submissions() {
+        // without a running server, nothing takes jobs off the queue
+        let server = Server::new(1, 1, 16, TileOrder::Scanline);
+        let first = server.route(&request("POST", "/jobs", "cornell --width=16"));
+        assert_eq!(first.status, 202);
+
+        let rejected = server.route(&request("POST", "/jobs", "cornell --width=16"));
+        assert_eq!(rejected.status, 503);
+        assert!(rejected
+            .headers
+            .iter()
+            .any(|(name, _)| *name == "Retry-After"));
+
+        // cancelling the queued job makes room again
+        let cancelled = server.route(&request("DELETE", "/jobs/1", ""));
+        assert_eq!(cancelled.status, 200);
+        assert_eq!(
+            *server.job("1").unwrap().status.lock().unwrap(),
+            Status::Cancelled
+        );
+        let second = server.route(&request("POST", "/jobs", "cornell --width=16"));
+        assert_eq!(second.status, 202);
+    }
+
+    #[test]
+    fn jobs_are_rendered_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        // the server runs until the tests end
+        let server: &'static Server =
+            Box::leak(Box::new(Server::new(1, 1, 8, TileOrder::Scanline)));
+        thread::spawn(move || server.run(listener));
+
+        let (status, headers, body) =
+            send(&address, "POST", "/jobs", "cornell --width=16 --samples=2");
+        assert_eq!(status, 202, "{}", String::from_utf8_lossy(&body));
+        let job = header(&headers, "location").to_string();
+
+        let start = Instant::now();
+        loop {
+            let (status, _, body) = send(&address, "GET", &job, "");
+            assert_eq!(status, 200);
+            let body = String::from_utf8(body).unwrap();
+            if body.contains("\"status\": \"done\"") {
+                assert!(body.contains("\"progress\": 1.0000"), "{}", body);
+                break;
+            }
+            assert!(!body.contains("\"status\": \"failed\""), "{}", body);
+            assert!(start.elapsed() < Duration::from_secs(120), "job not done");
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let (status, headers, body) = send(&address, "GET", &format!("{}/image", job), "");
+        assert_eq!(status, 200);
+        assert_eq!(header(&headers, "content-type"), "image/png");
+        assert!(body.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        // finished jobs are forgotten when deleted
+        let (status, _, body) = send(&address, "DELETE", &job, "");
+        assert_eq!(status, 200);
+        assert!(String::from_utf8(body)
+            .unwrap()
+            .contains("\"status\": \"done\""));
+        assert_eq!(send(&address, "GET", &job, "").0, 404);
+
+        assert_eq!(send(&address, "GET", "/nothing", "").0, 404);
+        assert_eq!(send(&address, "PATCH", "/jobs", "").0, 405);
+        let (status, _, body) = send(&address, "POST", "/jobs", "cornell --width=100000");
+        assert_eq!(status, 400);
+        assert!(String::from_utf8(body).unwrap().contains("above the limit"));
+    }
+}
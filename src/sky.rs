@@ -0,0 +1,305 @@
+use std::f64::consts::PI;
+
+use crate::color::Color;
+use crate::light::{DirectionalLight, Light, LightSample};
+use crate::ppm::Image;
+use crate::ray::Point3;
+use crate::rtweekend;
+use crate::vec::Vec3;
+
+/// Converts luminance values of the sky model (kcd/m²) into scene radiance.
+///
+/// With this scale, the zenith of a clear sky has a radiance in the order of 0.5.
+const LUMINANCE_SCALE: f64 = 1.0 / 15.0;
+
+/// Illuminance of the sun above the atmosphere in klx, in the same units as the sky luminance.
+const SUN_ILLUMINANCE: f64 = 100.0;
+
+/// Angular radius of the sun disk in degrees.
+const SUN_ANGULAR_RADIUS: f64 = 0.27;
+
+/// Coefficients of the Perez sky luminance distribution.
+///
+/// The distribution describes the relative brightness of a sky element depending on its angle θ
+/// from the zenith and its angle γ from the sun:
+///
+///     F(θ, γ) = (1 + A⋅e^(B / cosθ))⋅(1 + C⋅e^(D⋅γ) + E⋅cos²γ)
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    fn eval(&self, cos_theta: f64, gamma: f64) -> f64 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+/// Analytic daylight sky after Preetham, Shirley and Smits: "A Practical Analytic Model for
+/// Daylight" (SIGGRAPH 1999).
+///
+/// The model computes the luminance Y and the chromaticity (x, y) of the sky at the zenith from
+/// the sun position and the turbidity (haziness, 2 = very clear, 10 = hazy). Every other direction
+/// is given relative to the zenith by the Perez distribution:
+///
+///     Y(θ, γ) = Yz⋅F(θ, γ) / F(0, θs)
+///
+/// where θs is the angle of the sun from the zenith. The same holds for x and y.
+///
+/// Below the horizon, the sky shows a diffuse ground lit by the sun and the sky.
+pub struct Sky {
+    sun_direction: Vec3<f64>,
+    turbidity: f64,
+    ground_albedo: Color,
+    // distributions for luminance and chromaticity
+    perez: [Perez; 3],
+    // zenith values (Y, x, y) divided by F(0, θs)
+    zenith: [f64; 3],
+    // radiance of the ground below the horizon
+    ground: Color,
+}
+
+impl Sky {
+    /// Create a new sky.
+    ///
+    /// * `sun_direction` - Direction pointing towards the sun, the y axis points to the zenith.
+    /// * `turbidity` - Haziness of the atmosphere in the range [1.7, 10.0].
+    /// * `ground_albedo` - Reflectance of the ground visible below the horizon.
+    pub fn new(sun_direction: Vec3<f64>, turbidity: f64, ground_albedo: Color) -> Self {
+        let sun_direction = sun_direction.normalized();
+        let t = rtweekend::clamp(turbidity, 1.7, 10.0);
+        // the model breaks down for a sun below the horizon, so keep it just above
+        let theta_s = rtweekend::clamp(sun_direction.y(), 0.0, 1.0)
+            .acos()
+            .min(PI / 2.0 - 0.01);
+
+        let perez = [
+            Perez {
+                a: 0.1787 * t - 1.4630,
+                b: -0.3554 * t + 0.4275,
+                c: -0.0227 * t + 5.3251,
+                d: 0.1206 * t - 2.5771,
+                e: -0.0670 * t + 0.3703,
+            },
+            Perez {
+                a: -0.0193 * t - 0.2592,
+                b: -0.0665 * t + 0.0008,
+                c: -0.0004 * t + 0.2125,
+                d: -0.0641 * t - 0.8989,
+                e: -0.0033 * t + 0.0452,
+            },
+            Perez {
+                a: -0.0167 * t - 0.2608,
+                b: -0.0950 * t + 0.0092,
+                c: -0.0079 * t + 0.2102,
+                d: -0.0441 * t - 1.6537,
+                e: -0.0109 * t + 0.0529,
+            },
+        ];
+
+        // zenith luminance in kcd/m²
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+
+        // zenith chromaticity, cubic polynomials in θs for each power of T
+        let (t2, th, th2, th3) = (t * t, theta_s, theta_s * theta_s, theta_s.powi(3));
+        let zenith_x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let zenith_yc = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        let zenith = [
+            zenith_y / perez[0].eval(1.0, theta_s),
+            zenith_x / perez[1].eval(1.0, theta_s),
+            zenith_yc / perez[2].eval(1.0, theta_s),
+        ];
+
+        let mut sky = Sky {
+            sun_direction,
+            turbidity: t,
+            ground_albedo,
+            perez,
+            zenith,
+            ground: Color::new3(0.0, 0.0, 0.0),
+        };
+        sky.update_ground();
+        sky
+    }
+
+    /// Returns the radiance of the sky (without the sun disk) in a given direction.
+    ///
+    /// * `direction` - Unit direction pointing away from the scene.
+    pub fn radiance(&self, direction: &Vec3<f64>) -> Color {
+        if direction.y() <= 0.0 {
+            return self.ground;
+        }
+        self.sky_radiance(direction)
+    }
+
+    /// Returns the radiance of the upper hemisphere in a given direction.
+    fn sky_radiance(&self, direction: &Vec3<f64>) -> Color {
+        // avoid the singularity of the distribution at the horizon
+        let cos_theta = direction.y().max(0.001);
+        let cos_gamma = rtweekend::clamp(Vec3::dot(direction, &self.sun_direction), -1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        let luminance = self.zenith[0] * self.perez[0].eval(cos_theta, gamma);
+        let x = self.zenith[1] * self.perez[1].eval(cos_theta, gamma);
+        let y = self.zenith[2] * self.perez[2].eval(cos_theta, gamma);
+
+        xyy_to_rgb(x, y, luminance) * LUMINANCE_SCALE
+    }
+
+    /// Returns the color of the sunlight after passing through the atmosphere.
+    ///
+    /// Sunlight is scattered away by air molecules (Rayleigh scattering, which mostly affects
+    /// short wavelengths and makes sunsets red) and by aerosols (haze). Both follow the
+    /// Beer–Lambert law with an optical depth proportional to the relative air mass m, i.e. how
+    /// much atmosphere the light passes through compared to a sun at the zenith:
+    ///
+    ///     τR(λ) = exp(-0.008735⋅λ^-4.08⋅m)
+    ///     τa(λ) = exp(-β⋅λ^-α⋅m), with α = 1.3 and β = 0.04608⋅T - 0.04586
+    ///
+    /// We evaluate the transmittance at representative wavelengths (in μm) for each channel.
+    fn sun_transmittance(&self) -> Color {
+        if self.sun_direction.y() <= 0.0 {
+            return Color::new3(0.0, 0.0, 0.0);
+        }
+
+        let theta_s = self.sun_direction.y().acos();
+        let theta_s_degrees = theta_s.to_degrees();
+        let air_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s_degrees).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        let transmittance = |lambda: f64| {
+            let rayleigh = (-0.008735 * lambda.powf(-4.08) * air_mass).exp();
+            let aerosol = (-beta * lambda.powf(-1.3) * air_mass).exp();
+            rayleigh * aerosol
+        };
+        Color::new3(
+            transmittance(0.65),
+            transmittance(0.55),
+            transmittance(0.45),
+        )
+    }
+
+    /// Returns the sun matching this sky.
+    ///
+    /// The sun is a directional light with the angular radius of the real sun, so it casts soft
+    /// shadows. It has to be added to the world separately.
+    pub fn sun(&self) -> DirectionalLight {
+        let irradiance = self.sun_transmittance() * (SUN_ILLUMINANCE * LUMINANCE_SCALE);
+        DirectionalLight::new(self.sun_direction, irradiance, SUN_ANGULAR_RADIUS)
+    }
+
+    /// Compute the radiance of the ground below the horizon.
+    ///
+    /// The ground is a diffuse surface with radiance E⋅albedo / π, where the irradiance E is the
+    /// sum of the direct sunlight and the light from the sky hemisphere. The latter is integrated
+    /// numerically: E = ∫ L(ω)⋅cosθ dω.
+    fn update_ground(&mut self) {
+        const STEPS_THETA: usize = 16;
+        const STEPS_PHI: usize = 32;
+        let d_theta = PI / 2.0 / STEPS_THETA as f64;
+        let d_phi = 2.0 * PI / STEPS_PHI as f64;
+
+        let mut irradiance = Color::new3(0.0, 0.0, 0.0);
+        for i in 0..STEPS_THETA {
+            let theta = (i as f64 + 0.5) * d_theta;
+            for j in 0..STEPS_PHI {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let direction = Vec3::new3(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                // solid angle of the element is sinθ⋅dθ⋅dφ
+                let weight = theta.cos() * theta.sin() * d_theta * d_phi;
+                irradiance = irradiance + self.sky_radiance(&direction) * weight;
+            }
+        }
+
+        let sun =
+            self.sun_transmittance() * (SUN_ILLUMINANCE * LUMINANCE_SCALE * self.sun_direction.y());
+        self.ground = (irradiance + sun) * self.ground_albedo / PI;
+    }
+
+    /// Bake the sky into an equirectangular image.
+    ///
+    /// The image can be turned into an environment map, which importance samples the bright parts
+    /// of the sky. The sun disk is not part of the image, since it is much smaller than a pixel.
+    ///
+    /// * `width` - Width in pixels.
+    /// * `height` - Height in pixels.
+    pub fn bake(&self, width: usize, height: usize) -> Image<Color> {
+        let mut img = Image::new(width, height, Color::new3(0.0, 0.0, 0.0));
+        for j in 0..height {
+            // same parameterization as the environment map
+            let theta = PI * (j as f64 + 0.5) / height as f64;
+            for i in 0..width {
+                let phi = 2.0 * PI * (i as f64 + 0.5) / width as f64;
+                let direction = Vec3::new3(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                img[j][i] = self.radiance(&direction);
+            }
+        }
+        img
+    }
+}
+
+/// Convert a color given as chromaticity (x, y) and luminance Y into linear sRGB.
+///
+/// First, we compute the CIE XYZ tristimulus values:
+///
+///     X = x / y⋅Y
+///     Z = (1 - x - y) / y⋅Y
+///
+/// Then we apply the XYZ to sRGB matrix (D65 white point).
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    if y <= 0.0 {
+        return Color::new3(0.0, 0.0, 0.0);
+    }
+
+    let cx = x / y * luminance;
+    let cy = luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+
+    Color::new3(
+        (3.2406 * cx - 1.5372 * cy - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * cy + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * cy + 1.0570 * cz).max(0.0),
+    )
+}
+
+impl Light for Sky {
+    fn sample(&self, _point: &Point3<f64>) -> Option<LightSample> {
+        // Without baking, we have no distribution of the sky brightness at hand, so pick
+        // directions uniformly. Bake the sky into an environment map for better sampling.
+        let direction = rtweekend::random_unit_vec();
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.radiance(&direction),
+            pdf: 1.0 / (4.0 * PI),
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, _point: &Point3<f64>, _direction: &Vec3<f64>) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn background(&self, direction: &Vec3<f64>) -> Color {
+        self.radiance(direction)
+    }
+}
@@ -0,0 +1,279 @@
+use crate::color::Color;
+
+/// Shortest wavelength that is sampled, in nm.
+const LAMBDA_MIN: f64 = 360.0;
+/// Longest wavelength that is sampled, in nm.
+const LAMBDA_MAX: f64 = 830.0;
+/// Number of wavelengths carried by a path, one per color channel.
+const COUNT: usize = 3;
+
+/// Integral of the CIE ȳ color matching function over wavelength in nm.
+const CIE_Y_INTEGRAL: f64 = 106.856895;
+
+/// Converts CIE XYZ into linear sRGB (D65 white point).
+const XYZ_TO_RGB: [[f64; 3]; 3] = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+/// Wavelengths in nm sampled by a path, see `Wavelengths`.
+///
+/// Light is carried as one value per wavelength in the channels of a `Color`, so all the
+/// arithmetic on colors applies to spectral values as well.
+#[derive(Debug, Clone, Copy)]
+pub struct Wavelengths {
+    lambda: [f64; COUNT],
+    pdf: [f64; COUNT],
+    /// Whether only the hero wavelength is left, see `terminate_secondary`.
+    terminated: bool,
+}
+
+impl Wavelengths {
+    /// Sample the wavelengths of a path (hero wavelength sampling).
+    ///
+    /// Following Wilkie et al. ("Hero Wavelength Spectral Sampling", 2014), one hero wavelength
+    /// is picked at random and the others are spread evenly from it, wrapping around at the end
+    /// of the range. Every path sees several parts of the spectrum, which gives much less color
+    /// noise than one wavelength per path. As in pbrt, the wavelengths are distributed
+    /// proportional to how visible they are:
+    ///
+    ///     p(λ) ∝ 1 / cosh²(0.0072⋅(λ - 538))
+    ///
+    /// * `u` - Uniform random number in [0, 1).
+    pub fn sample(u: f64) -> Self {
+        let mut lambda = [0.0; COUNT];
+        let mut pdf = [0.0; COUNT];
+        for i in 0..COUNT {
+            let u = (u + i as f64 / COUNT as f64).fract();
+            // inverse of the cumulative distribution
+            lambda[i] = (538.0 - 138.888889 * (0.85691062 - 1.82750197 * u).atanh())
+                .clamp(LAMBDA_MIN, LAMBDA_MAX);
+            let c = (0.0072 * (lambda[i] - 538.0)).cosh();
+            pdf[i] = 0.0039398042 / (c * c);
+        }
+
+        Wavelengths {
+            lambda,
+            pdf,
+            terminated: false,
+        }
+    }
+
+    /// Returns the hero wavelength, which decides directions that depend on the wavelength.
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    /// Returns the values of a color at the wavelengths, see `rgb_to_spectrum`.
+    ///
+    /// * `rgb` - Linear RGB color, reflectance or radiance.
+    pub fn spectrum(&self, rgb: &Color) -> Color {
+        self.map(|lambda| rgb_to_spectrum(rgb, lambda))
+    }
+
+    /// Returns the values of a function of the wavelength at the wavelengths.
+    ///
+    /// * `f` - Function of the wavelength in nm, e.g. a measured reflectance.
+    pub fn map<F: Fn(f64) -> f64>(&self, f: F) -> Color {
+        Color::new3(f(self.lambda[0]), f(self.lambda[1]), f(self.lambda[2]))
+    }
+
+    /// Drop the secondary wavelengths, e.g. when light is dispersed.
+    ///
+    /// Dispersion sends each wavelength into another direction, so the path can only follow the
+    /// hero wavelength from here on. Returns whether the wavelengths were not terminated before,
+    /// the light found from then on has to be passed through `keep_hero` once.
+    pub fn terminate_secondary(&mut self) -> bool {
+        let first = !self.terminated;
+        self.terminated = true;
+        first
+    }
+
+    /// Returns spectral values with only the hero wavelength left.
+    ///
+    /// The image averages the estimates of all wavelengths, so the hero wavelength stands in for
+    /// the dropped ones.
+    ///
+    /// * `values` - Values at the wavelengths.
+    pub fn keep_hero(values: &Color) -> Color {
+        Color::new3(values.x() * COUNT as f64, 0.0, 0.0)
+    }
+
+    /// Convert values at the wavelengths into a linear sRGB color.
+    ///
+    /// The values are weighted by the CIE 1931 color matching functions, which gives the color
+    /// in XYZ coordinates as a Monte Carlo estimate:
+    ///
+    ///     X = 1 / (N⋅∫ȳ) ⋅ Σ L(λi)⋅x̄(λi) / p(λi)      (Y and Z alike)
+    ///
+    /// The XYZ coordinates are converted to sRGB and finally white balanced, so a flat spectrum
+    /// (illuminant E), which is what white upsamples to, becomes white again instead of pink.
+    ///
+    /// * `values` - Values at the wavelengths.
+    pub fn to_rgb(self, values: &Color) -> Color {
+        let values = [values.x(), values.y(), values.z()];
+        let mut xyz = [0.0; 3];
+        for ((value, lambda), pdf) in values.iter().zip(self.lambda).zip(self.pdf) {
+            if pdf <= 0.0 {
+                continue;
+            }
+            let (x, y, z) = cie_xyz(lambda);
+            let weight = value / (pdf * COUNT as f64 * CIE_Y_INTEGRAL);
+            xyz[0] += x * weight;
+            xyz[1] += y * weight;
+            xyz[2] += z * weight;
+        }
+
+        let mut rgb = [0.0; 3];
+        for (channel, row) in rgb.iter_mut().zip(XYZ_TO_RGB.iter()) {
+            // dividing by the row sum maps XYZ = (1, 1, 1) of illuminant E to white
+            let dot = row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2];
+            *channel = dot / (row[0] + row[1] + row[2]);
+        }
+        Color::new3(rgb[0], rgb[1], rgb[2])
+    }
+}
+
+/// Returns the CIE 1931 color matching functions x̄, ȳ, z̄ at a wavelength.
+///
+/// Uses the multi-lobe fit by Wyman et al. ("Simple Analytic Approximations to the CIE XYZ Color
+/// Matching Functions", 2013), a sum of Gaussians which are wider on one side:
+///
+///     g(λ; μ, σ1, σ2) = exp(-(λ - μ)² / (2σ²)),  σ = σ1 for λ < μ, σ2 otherwise
+///
+/// * `lambda` - Wavelength in nm.
+pub fn cie_xyz(lambda: f64) -> (f64, f64, f64) {
+    let g = |mu: f64, sigma_1: f64, sigma_2: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma_1 } else { sigma_2 };
+        (-0.5 * t * t).exp()
+    };
+    (
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+/// Basis spectra by Smits, sampled in 10 bins from 380 nm to 720 nm.
+const SMITS_WHITE: [f64; 10] = [1.0, 1.0, 0.9999, 0.9993, 0.9992, 0.9998, 1.0, 1.0, 1.0, 1.0];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0, 0.0, 0.0,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0, 1.0, 0.9685, 0.2229, 0.0, 0.0458, 0.8369, 1.0, 1.0, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0, 0.1088, 0.6651, 1.0, 1.0, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0, 0.0, 0.0, 0.0, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0, 0.0, 0.0273, 0.7937, 1.0, 0.9418, 0.1719, 0.0, 0.0, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0, 1.0, 0.8916, 0.3323, 0.0, 0.0, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// Returns the value of a basis spectrum, linearly interpolated between the bin centers.
+///
+/// * `table` - Basis spectrum.
+/// * `lambda` - Wavelength in nm, clamped to the range of the bins.
+fn smits_basis(table: &[f64; 10], lambda: f64) -> f64 {
+    let x = ((lambda - 380.0) / 34.0 - 0.5).clamp(0.0, 9.0);
+    let i = (x as usize).min(8);
+    let t = x - i as f64;
+    table[i] * (1.0 - t) + table[i + 1] * t
+}
+
+/// Returns the value of a smooth spectrum with a given RGB color at a wavelength.
+///
+/// Rendering with spectra needs a spectrum for every color in the scene. Many spectra have the
+/// same color, so we pick a smooth one after Smits ("An RGB to Spectrum Conversion for
+/// Reflectances", 1999): the smallest channel is made up of white, the next of cyan, magenta or
+/// yellow and the rest of red, green or blue:
+///
+///     r ≤ g ≤ b:  s(λ) = r⋅white(λ) + (g - r)⋅cyan(λ) + (b - g)⋅blue(λ)
+///
+/// and alike for the other orders. White becomes a flat spectrum. The conversion is linear in
+/// the brightness, so it works for radiances above one as well.
+///
+/// * `rgb` - Linear RGB color.
+/// * `lambda` - Wavelength in nm.
+pub fn rgb_to_spectrum(rgb: &Color, lambda: f64) -> f64 {
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    let basis = |table: &[f64; 10]| smits_basis(table, lambda);
+    if r <= g && r <= b {
+        let white = r * basis(&SMITS_WHITE);
+        if g <= b {
+            white + (g - r) * basis(&SMITS_CYAN) + (b - g) * basis(&SMITS_BLUE)
+        } else {
+            white + (b - r) * basis(&SMITS_CYAN) + (g - b) * basis(&SMITS_GREEN)
+        }
+    } else if g <= r && g <= b {
+        let white = g * basis(&SMITS_WHITE);
+        if r <= b {
+            white + (r - g) * basis(&SMITS_MAGENTA) + (b - r) * basis(&SMITS_BLUE)
+        } else {
+            white + (b - g) * basis(&SMITS_MAGENTA) + (r - b) * basis(&SMITS_RED)
+        }
+    } else {
+        let white = b * basis(&SMITS_WHITE);
+        if r <= g {
+            white + (r - b) * basis(&SMITS_YELLOW) + (g - r) * basis(&SMITS_GREEN)
+        } else {
+            white + (g - b) * basis(&SMITS_YELLOW) + (r - g) * basis(&SMITS_RED)
+        }
+    }
+}
+
+/// Wavelength dependent index of refraction of a dielectric, which splits light into colors.
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    /// Cauchy's equation n(λ) = A + B / λ², with λ in µm.
+    Cauchy { a: f64, b: f64 },
+    /// Sellmeier equation n²(λ) = 1 + Σ Bi⋅λ² / (λ² - Ci), with λ in µm and Ci in µm².
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Borosilicate crown glass (Schott N-BK7), a common optical glass.
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+
+    /// Flint glass (Schott SF11), which disperses much stronger, e.g. for prisms.
+    pub const SF11: Dispersion = Dispersion::Sellmeier {
+        b: [1.73759695, 0.313747346, 1.89878101],
+        c: [0.013188707, 0.0623068142, 155.23629],
+    };
+
+    /// Diamond, after Peter (1923).
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.0],
+        c: [0.030625, 0.011236, 0.0],
+    };
+
+    /// Returns the index of refraction at a wavelength.
+    ///
+    /// * `lambda` - Wavelength in nm.
+    pub fn refraction(&self, lambda: f64) -> f64 {
+        let micrometers = lambda / 1000.0;
+        let l2 = micrometers * micrometers;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = b.iter().zip(c.iter()).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    /// Returns the index of refraction at the yellow helium d line (587.6 nm), which is the one
+    /// usually given for a material.
+    pub fn nominal(&self) -> f64 {
+        self.refraction(587.56)
+    }
+}
@@ -1,5 +1,9 @@
+use std::f64::consts::PI;
+
 use crate::hittable::{HitRecord, Hittable};
+use crate::onb::Onb;
 use crate::ray::{Point3, Ray};
+use crate::rtweekend;
 use crate::vec::Vec3;
 
 pub struct Sphere<T: Copy> {
@@ -82,4 +86,46 @@ impl Hittable<f64> for Sphere<f64> {
         let outward_normal = (point - self.center) / self.radius;
         Some(HitRecord::new(point, outward_normal, root, ray))
     }
+
+    fn sample(&self, origin: &Point3<f64>) -> Option<(Vec3<f64>, f64)> {
+        let direction = self.center - *origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        if distance_squared <= radius_squared {
+            // The origin lies inside the sphere, so every direction hits it. Sample the whole
+            // sphere of directions uniformly.
+            return Some((rtweekend::random_unit_vec(), 1.0 / (4.0 * PI)));
+        }
+
+        // Seen from the outside, the sphere covers a cone of directions around the vector pointing
+        // towards its center. The half opening angle θmax of that cone satisfies:
+        //
+        //      sinθmax = r / d
+        //
+        // where d is the distance to the center. We sample directions uniformly inside the cone,
+        // the density is constant at the inverse of the cone's solid angle 2π⋅(1 - cosθmax).
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let local = rtweekend::random_vec_in_cone(cos_theta_max);
+
+        let onb = Onb::new(&direction);
+        let pdf = 1.0 / (2.0 * PI * (1.0 - cos_theta_max));
+        Some((onb.local(&local), pdf))
+    }
+
+    fn pdf(&self, origin: &Point3<f64>, direction: &Vec3<f64>) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        if self.is_hit(&ray, 0.001, f64::MAX).is_none() {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1.0 / (4.0 * PI);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
 }
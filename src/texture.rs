@@ -0,0 +1,62 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+
+/// Material parameter which varies over the surface of an object.
+///
+/// Hit records carry no surface coordinates, so textures are solid: they are defined in world
+/// space and evaluated at the hit point, as if the object was carved out of a block of material.
+/// Plain values are textures too, which are the same everywhere.
+pub trait Texture<V> {
+    /// Returns the value of the texture at a hit point.
+    ///
+    /// * `rec` - Hit record of a ray on the object.
+    fn value(&self, rec: &HitRecord<f64>) -> V;
+}
+
+impl Texture<f64> for f64 {
+    fn value(&self, _rec: &HitRecord<f64>) -> f64 {
+        *self
+    }
+}
+
+impl Texture<Color> for Color {
+    fn value(&self, _rec: &HitRecord<f64>) -> Color {
+        *self
+    }
+}
+
+/// Texture of boxes alternating between two values, like a three dimensional checkerboard.
+///
+/// The boxes are cubes of the given size, aligned with the world axes:
+///
+///     even:  ⌊x / size⌋ + ⌊y / size⌋ + ⌊z / size⌋ is even
+///     odd:   otherwise
+pub struct Checker<V> {
+    even: V,
+    odd: V,
+    /// Edge length of the cubes.
+    size: f64,
+}
+
+impl<V> Checker<V> {
+    /// Create a new checker texture.
+    ///
+    /// * `even` - Value of the cube at the origin and every second one from there.
+    /// * `odd` - Value of the other cubes.
+    /// * `size` - Edge length of the cubes in scene units.
+    pub fn new(even: V, odd: V, size: f64) -> Self {
+        Checker { even, odd, size }
+    }
+}
+
+impl<V: Copy> Texture<V> for Checker<V> {
+    fn value(&self, rec: &HitRecord<f64>) -> V {
+        let p = rec.point / self.size;
+        let sum = p.x().floor() + p.y().floor() + p.z().floor();
+        if sum.rem_euclid(2.0) < 1.0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::f64::consts::PI;
+use std::sync::mpsc;
+use std::thread;
+
+/// Rectangular block of pixels which is rendered as one unit of work.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tile {
+    /// Column of the left edge.
+    pub x: usize,
+    /// Row of the bottom edge, zero is the bottom row of the image.
+    pub y: usize,
+    /// Width in pixels, smaller than the tile size at the right border.
+    pub width: usize,
+    /// Height in pixels, smaller than the tile size at the top border.
+    pub height: usize,
+}
+
+impl Tile {
+    /// Returns the tile enlarged by a margin on every side, clipped to the image.
+    ///
+    /// * `margin` - Number of pixels to add on each side.
+    /// * `width` - Image width in pixels.
+    /// * `height` - Image height in pixels.
+    pub fn grow(&self, margin: usize, width: usize, height: usize) -> Tile {
+        let x = self.x.saturating_sub(margin);
+        let y = self.y.saturating_sub(margin);
+        Tile {
+            x,
+            y,
+            width: (self.x + self.width + margin).min(width) - x,
+            height: (self.y + self.height + margin).min(height) - y,
+        }
+    }
+}
+
+/// Order in which the tiles are handed out to the threads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileOrder {
+    /// Rows of tiles from top to bottom, each one from left to right.
+    Scanline,
+    /// Z-order curve, which visits the tiles in nested 2x2 blocks.
+    Morton,
+    /// Hilbert curve, consecutive tiles are always neighbors.
+    Hilbert,
+    /// Spiral from the image center outwards, so the interesting part shows up first.
+    Spiral,
+}
+
+/// Split an image into tiles.
+///
+/// Neighboring tiles share parts of the scene, so visiting them one after another keeps the
+/// geometry and textures they touch in the CPU caches. The space filling curves (Morton and
+/// Hilbert) are defined on a square grid with a power of two side length, tiles outside the image
+/// are simply skipped.
+///
+/// * `width` - Image width in pixels.
+/// * `height` - Image height in pixels.
+/// * `size` - Edge length of the (square) tiles in pixels.
+/// * `order` - Order of the returned tiles.
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+
+    // The curves start in the upper left corner, which is the last row of tiles since the image
+    // rows are counted bottom up.
+    let mut keyed: Vec<(f64, Tile)> = Vec::with_capacity(columns * rows);
+    for ty in 0..rows {
+        for tx in 0..columns {
+            let top = rows - 1 - ty;
+            let key = match order {
+                TileOrder::Scanline => (top * columns + tx) as f64,
+                TileOrder::Morton => morton(tx, top) as f64,
+                TileOrder::Hilbert => {
+                    let n = columns.max(rows).next_power_of_two();
+                    hilbert(n, tx, top) as f64
+                }
+                TileOrder::Spiral => {
+                    // Rings of tiles around the center, each one walked around by angle. The ring
+                    // index dominates the key, the angle in [0, 1) orders the tiles within a ring.
+                    let dx = tx as f64 + 0.5 - columns as f64 / 2.0;
+                    let dy = ty as f64 + 0.5 - rows as f64 / 2.0;
+                    let ring = dx.abs().max(dy.abs()).floor();
+                    let angle = (dy.atan2(dx) + PI) / (2.0 * PI);
+                    ring + angle.min(0.999_999)
+                }
+            };
+
+            let tile = Tile {
+                x: tx * size,
+                y: ty * size,
+                width: size.min(width - tx * size),
+                height: size.min(height - ty * size),
+            };
+            keyed.push((key, tile));
+        }
+    }
+
+    keyed.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    keyed.into_iter().map(|(_, tile)| tile).collect()
+}
+
+/// Returns the index of a grid cell on the Morton (Z-order) curve.
+///
+/// The index is made up of the interleaved bits of both coordinates:
+///
+///     x = x₁x₀, y = y₁y₀  =>  index = y₁x₁y₀x₀
+fn morton(x: usize, y: usize) -> usize {
+    let mut index = 0;
+    for bit in 0..(usize::BITS as usize / 2) {
+        index |= ((x >> bit) & 1) << (2 * bit);
+        index |= ((y >> bit) & 1) << (2 * bit + 1);
+    }
+    index
+}
+
+/// Returns the index of a grid cell on the Hilbert curve.
+///
+/// The curve is built recursively: each quadrant contains a smaller copy of the curve, rotated or
+/// mirrored so that its end meets the start of the next quadrant.
+///
+/// * `n` - Side length of the grid, a power of two.
+/// * `x`, `y` - Cell coordinates.
+fn hilbert(n: usize, mut x: usize, mut y: usize) -> usize {
+    let mut index = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        index += s * s * ((3 * rx) ^ ry);
+
+        // rotate the quadrant so the sub-curve has the right orientation
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// Render tiles in parallel.
+///
+/// Each tile becomes a task in the rayon thread pool. The pool uses work stealing: tasks are
+/// queued in order (FIFO), idle threads steal the oldest pending tile from busy ones, so there is
+/// no synchronization point until the very last tile is done. The calling thread is notified about
+/// every finished tile, e.g. to update a preview window.
+///
+/// * `tiles` - Tiles in the order they should be started.
+/// * `render_tile` - Renders one tile, called concurrently from the worker threads.
+/// * `done` - Called on the current thread for every finished tile.
+pub fn render<R, D>(tiles: &[Tile], render_tile: R, mut done: D)
+where
+    R: Fn(&Tile) + Sync,
+    D: FnMut(&Tile),
+{
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|s| {
+        let render_tile = &render_tile;
+        s.spawn(move || {
+            rayon::scope_fifo(|scope| {
+                for tile in tiles {
+                    let sender = sender.clone();
+                    scope.spawn_fifo(move |_| {
+                        render_tile(tile);
+                        // the receiver only goes away if the caller panicked
+                        let _ = sender.send(*tile);
+                    });
+                }
+            });
+        });
+
+        // the channel closes once the last task dropped its sender
+        for tile in receiver {
+            done(&tile);
+        }
+    });
+}
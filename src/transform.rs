@@ -0,0 +1,139 @@
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::{Point3, Ray};
+use crate::rtweekend;
+use crate::vec::Vec3;
+
+/// Rigid transformation with uniform scaling.
+///
+/// A point p in object space ends up at:
+///
+///     p' = R⋅(s⋅p) + t
+///
+/// where R is the rotation, s the scale factor and t the translation. Rotation and uniform
+/// scaling preserve angles, so normals only need to be rotated and solid angles stay the same.
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    translation: Vec3<f64>,
+    // rows of the rotation matrix
+    rotation: [Vec3<f64>; 3],
+    scale: f64,
+}
+
+impl Transform {
+    /// Create a new identity transformation.
+    pub fn new() -> Self {
+        Transform {
+            translation: Vec3::new3(0.0, 0.0, 0.0),
+            rotation: [
+                Vec3::new3(1.0, 0.0, 0.0),
+                Vec3::new3(0.0, 1.0, 0.0),
+                Vec3::new3(0.0, 0.0, 1.0),
+            ],
+            scale: 1.0,
+        }
+    }
+
+    /// Adjusts the translation.
+    ///
+    /// * `translation` - Offset added after rotating and scaling.
+    pub fn translate(mut self, translation: Vec3<f64>) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    /// Adjusts the rotation.
+    ///
+    /// The object is rotated around the x axis first, then the y axis and finally the z axis:
+    ///
+    ///     R = Rz⋅Ry⋅Rx
+    ///
+    /// * `degrees` - Rotation angles around the x, y and z axis.
+    pub fn rotate(mut self, degrees: Vec3<f64>) -> Self {
+        let (sx, cx) = rtweekend::degrees_to_radians(degrees.x()).sin_cos();
+        let (sy, cy) = rtweekend::degrees_to_radians(degrees.y()).sin_cos();
+        let (sz, cz) = rtweekend::degrees_to_radians(degrees.z()).sin_cos();
+        self.rotation = [
+            Vec3::new3(cz * cy, cz * sy * sx - sz * cx, cz * sy * cx + sz * sx),
+            Vec3::new3(sz * cy, sz * sy * sx + cz * cx, sz * sy * cx - cz * sx),
+            Vec3::new3(-sy, cy * sx, cy * cx),
+        ];
+        self
+    }
+
+    /// Adjusts the scale factor.
+    ///
+    /// * `scale` - Uniform scale factor, must be positive.
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Rotate a vector from object to world space.
+    fn rotate_vec(&self, v: &Vec3<f64>) -> Vec3<f64> {
+        Vec3::new3(
+            Vec3::dot(&self.rotation[0], v),
+            Vec3::dot(&self.rotation[1], v),
+            Vec3::dot(&self.rotation[2], v),
+        )
+    }
+
+    /// Rotate a vector from world to object space (the inverse rotation is the transpose).
+    fn unrotate_vec(&self, v: &Vec3<f64>) -> Vec3<f64> {
+        self.rotation[0] * v.x() + self.rotation[1] * v.y() + self.rotation[2] * v.z()
+    }
+
+    /// Transform a point from world to object space.
+    fn local_point(&self, point: &Point3<f64>) -> Point3<f64> {
+        self.unrotate_vec(&(*point - self.translation)) / self.scale
+    }
+}
+
+/// Object placed in the scene by a transformation.
+///
+/// Instead of transforming the object, we transform the rays into object space. The ray direction
+/// is transformed without normalizing it, so the ray parameter t is the same in both spaces.
+pub struct Transformed<H> {
+    object: H,
+    transform: Transform,
+}
+
+impl<H> Transformed<H> {
+    /// Create a new transformed object.
+    ///
+    /// * `object` - Object in object space.
+    /// * `transform` - Transformation from object to world space.
+    pub fn new(object: H, transform: Transform) -> Self {
+        Transformed { object, transform }
+    }
+}
+
+impl<H: Hittable<f64>> Hittable<f64> for Transformed<H> {
+    fn is_hit(&self, ray: &Ray<f64>, t_min: f64, t_max: f64) -> Option<HitRecord<f64>> {
+        let local = Ray::new(
+            self.transform.local_point(&ray.origin()),
+            self.transform.unrotate_vec(&ray.direction()) / self.transform.scale,
+        );
+        let rec = self.object.is_hit(&local, t_min, t_max)?;
+
+        Some(HitRecord {
+            point: ray.at(rec.t),
+            normal: self.transform.rotate_vec(&rec.normal),
+            t: rec.t,
+            front_face: rec.front_face,
+            exterior_refraction: rec.exterior_refraction,
+            wavelengths: rec.wavelengths,
+        })
+    }
+
+    fn sample(&self, origin: &Point3<f64>) -> Option<(Vec3<f64>, f64)> {
+        let (direction, pdf) = self.object.sample(&self.transform.local_point(origin))?;
+        Some((self.transform.rotate_vec(&direction), pdf))
+    }
+
+    fn pdf(&self, origin: &Point3<f64>, direction: &Vec3<f64>) -> f64 {
+        self.object.pdf(
+            &self.transform.local_point(origin),
+            &self.transform.unrotate_vec(direction),
+        )
+    }
+}
@@ -114,6 +114,22 @@ where
     }
 }
 
+// Vector * Vector
+
+impl<T: Copy, const N: usize> Mul for Vec<T, N>
+where
+    T: Mul<Output = T>,
+{
+    type Output = Vec<T, N>;
+
+    fn mul(mut self, other: Vec<T, N>) -> Vec<T, N> {
+        for i in 0..N {
+            self[i] = self[i] * other[i];
+        }
+        self
+    }
+}
+
 // Vector / Scalar
 
 impl<T: Copy, const N: usize> Div<T> for Vec<T, N>
@@ -1,27 +1,112 @@
+use std::sync::Arc;
+
+use crate::color::Color;
 use crate::hittable::{HitRecord, Hittable};
+use crate::light::{Light, LightSample};
 use crate::material::Material;
-use crate::ray::Ray;
+use crate::ray::{Point3, Ray};
+use crate::rtweekend;
+use crate::vec::Vec3;
 
 pub struct World<T: Copy> {
     objects: Vec<(
         Box<dyn Hittable<T> + Send + Sync>,
-        Box<dyn Material<T> + Send + Sync>,
+        Arc<dyn Material<T> + Send + Sync>,
     )>,
+    /// Material index of each object, objects sharing a material have the same one.
+    material_ids: Vec<usize>,
+    /// Number of distinct materials.
+    material_count: usize,
+    /// Indices of all objects with emissive materials.
+    emitters: Vec<usize>,
+    /// Light sources which are not part of the geometry.
+    lights: Vec<Box<dyn Light + Send + Sync>>,
+    /// Constant background color, the default sky gradient is used if none.
+    background: Option<Color>,
 }
 
 impl<T: Copy> World<T> {
     pub fn new() -> Self {
         World {
             objects: Vec::new(),
+            material_ids: Vec::new(),
+            material_count: 0,
+            emitters: Vec::new(),
+            lights: Vec::new(),
+            background: None,
         }
     }
 
+    /// Replace the default sky with a constant background color.
+    ///
+    /// * `color` - Background color, use black for scenes lit only by their own light sources.
+    pub fn set_background(&mut self, color: Color) {
+        self.background = Some(color);
+    }
+
+    /// Surround the scene with an environment, e.g. an environment map or a sky.
+    ///
+    /// The environment replaces the background and is sampled like any other light source.
+    ///
+    /// * `environment` - Infinitely distant light source covering all directions.
+    pub fn set_environment<L>(&mut self, environment: L)
+    where
+        L: Light + Send + Sync + 'static,
+    {
+        self.background = Some(Color::new3(0.0, 0.0, 0.0));
+        self.lights.push(Box::new(environment));
+    }
+
+    /// Returns the constant background color, if any.
+    pub fn background(&self) -> Option<Color> {
+        self.background
+    }
+
     pub fn add<H, M>(&mut self, hittable: H, material: M)
     where
         H: Hittable<T> + Send + Sync + 'static,
         M: Material<T> + Send + Sync + 'static,
     {
-        self.objects.push((Box::new(hittable), Box::new(material)));
+        self.add_shared(hittable, Arc::new(material));
+    }
+
+    /// Add an object whose material may also be used by other objects.
+    ///
+    /// Objects added with the same material share its material index, see `material_id`.
+    ///
+    /// * `hittable` - Geometry of the object.
+    /// * `material` - Material of the object.
+    pub fn add_shared<H>(&mut self, hittable: H, material: Arc<dyn Material<T> + Send + Sync>)
+    where
+        H: Hittable<T> + Send + Sync + 'static,
+    {
+        let shared = self
+            .objects
+            .iter()
+            .position(|(_, other)| Arc::ptr_eq(other, &material));
+        let material_id = match shared {
+            Some(object) => self.material_ids[object],
+            None => {
+                self.material_count += 1;
+                self.material_count - 1
+            }
+        };
+
+        if material.is_emissive() {
+            self.emitters.push(self.objects.len());
+        }
+        self.objects.push((Box::new(hittable), material));
+        self.material_ids.push(material_id);
+    }
+
+    /// Add a light source which is not part of the geometry, e.g. a point light.
+    ///
+    /// * `light` - Light source.
+    pub fn add_light<L>(&mut self, light: L)
+    where
+        L: Light + Send + Sync + 'static,
+    {
+        self.lights.push(Box::new(light));
     }
 
     pub fn trace(
@@ -30,10 +115,23 @@ impl<T: Copy> World<T> {
         t_min: T,
         t_max: T,
     ) -> Option<(HitRecord<T>, &dyn Material<T>)> {
-        let mut hit: Option<(HitRecord<T>, &dyn Material<T>)> = None;
+        self.hit(ray, t_min, t_max)
+            .map(|(rec, object)| (rec, self.material(object)))
+    }
+
+    /// Find the closest object hit by a ray.
+    ///
+    /// Returns the hit record and the index of the object, objects are numbered in the order they
+    /// were added.
+    ///
+    /// * `ray` - Ray to trace.
+    /// * `t_min` - Minimum ray position parameter.
+    /// * `t_max` - Maximum ray position parameter.
+    pub fn hit(&self, ray: &Ray<T>, t_min: T, t_max: T) -> Option<(HitRecord<T>, usize)> {
+        let mut hit: Option<(HitRecord<T>, usize)> = None;
 
         for i in 0..self.objects.len() {
-            let (hittable, material) = &self.objects[i];
+            let (hittable, _) = &self.objects[i];
 
             let t_max = if let Some((ref rec, _)) = hit {
                 rec.t
@@ -42,10 +140,123 @@ impl<T: Copy> World<T> {
             };
 
             if let Some(rec) = hittable.is_hit(ray, t_min, t_max) {
-                hit = Some((rec, material.as_ref()));
+                hit = Some((rec, i));
             }
         }
 
         hit
     }
+
+    /// Returns the material of an object.
+    ///
+    /// * `object` - Object index, see `hit`.
+    pub fn material(&self, object: usize) -> &dyn Material<T> {
+        self.objects[object].1.as_ref()
+    }
+
+    /// Returns the material index of an object.
+    ///
+    /// Materials are numbered in the order they were first added, objects sharing a material
+    /// have the same index.
+    ///
+    /// * `object` - Object index, see `hit`.
+    pub fn material_id(&self, object: usize) -> usize {
+        self.material_ids[object]
+    }
+}
+
+impl World<f64> {
+    /// Returns the total number of light sources, including emissive objects.
+    fn light_count(&self) -> usize {
+        self.emitters.len() + self.lights.len()
+    }
+
+    /// Sample the incident light from one of the light sources.
+    ///
+    /// A light is picked uniformly at random, then a direction towards it is sampled. The returned
+    /// probability density accounts for both steps. Occlusion is not tested, so the caller has to
+    /// trace a shadow ray.
+    ///
+    /// * `point` - Point which receives the light.
+    pub fn sample_light(&self, point: &Point3<f64>) -> Option<LightSample> {
+        let count = self.light_count();
+        if count == 0 {
+            return None;
+        }
+
+        let index = rtweekend::random(0..count);
+        let mut sample = if index < self.emitters.len() {
+            let (hittable, material) = &self.objects[self.emitters[index]];
+
+            // find the point on the light which the sampled direction hits, we need it to compute
+            // the distance for the shadow ray and the emitted radiance
+            let (direction, pdf) = hittable.sample(point)?;
+            let ray = Ray::new(*point, direction);
+            let rec = hittable.is_hit(&ray, 0.001, f64::MAX)?;
+
+            LightSample {
+                direction,
+                distance: rec.t,
+                radiance: material.emitted(&ray, &rec),
+                pdf,
+                delta: false,
+            }
+        } else {
+            self.lights[index - self.emitters.len()].sample(point)?
+        };
+
+        sample.pdf /= count as f64;
+        Some(sample)
+    }
+
+    /// Returns the probability density with which `sample_light` picks a given direction.
+    ///
+    /// Only the light that is actually seen along the direction counts: the object hit there, or
+    /// the infinitely distant lights if the direction escapes the scene. Lights behind the hit
+    /// object are occluded, so their light samples contribute nothing and must not lower the MIS
+    /// weight of the hit.
+    ///
+    /// * `point` - Point which receives the light.
+    /// * `direction` - Direction from the point, does not need to be normalized.
+    /// * `object` - Index of the object hit along the direction, none if it escapes.
+    pub fn light_pdf(
+        &self,
+        point: &Point3<f64>,
+        direction: &Vec3<f64>,
+        object: Option<usize>,
+    ) -> f64 {
+        let count = self.light_count();
+        if count == 0 {
+            return 0.0;
+        }
+
+        let pdf = match object {
+            Some(object) if self.emitters.contains(&object) => {
+                self.objects[object].0.pdf(point, direction)
+            }
+            Some(_) => 0.0,
+            None => {
+                let direction = direction.normalized();
+                self.lights
+                    .iter()
+                    .map(|light| light.pdf(point, &direction))
+                    .sum()
+            }
+        };
+        pdf / count as f64
+    }
+
+    /// Returns the light arriving from infinitely distant light sources along a direction.
+    ///
+    /// This is what a ray sees when it leaves the scene, in addition to the background.
+    ///
+    /// * `direction` - Direction of the ray, does not need to be normalized.
+    pub fn escaped(&self, direction: &Vec3<f64>) -> Color {
+        let direction = direction.normalized();
+        let mut color = Color::new3(0.0, 0.0, 0.0);
+        for light in &self.lights {
+            color = color + light.background(&direction);
+        }
+        color
+    }
 }